@@ -108,6 +108,19 @@ impl eframe::App for SlidetownApp {
 
                     ui.separator();
 
+                    let patch_button = egui::Button::new("Build Patch Archive…");
+                    if ui
+                        .add_enabled(self.current_project_dialog.is_some(), patch_button)
+                        .clicked()
+                    {
+                        if let Some(project_dialog) = self.current_project_dialog.as_mut() {
+                            project_dialog.open_patch_archive_dialog();
+                        }
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
                     if ui.button("Quit").clicked() {
                         frame.close();
                     }
@@ -0,0 +1,319 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use slidetown::parsers::agt::{Agt, AgtFile};
+
+use crate::project::EncryptionKey;
+
+pub fn is_archive_path(path: &Utf8Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("agt"))
+        .unwrap_or(false)
+}
+
+#[derive(Debug)]
+pub struct Archive {
+    path: Utf8PathBuf,
+    key: EncryptionKey,
+    agt: Agt,
+    /// Normalized paths of the archived files in archive order, with their index in `agt.files`.
+    /// Files with names that would escape the extraction directory are left out.
+    paths: Vec<(Utf8PathBuf, usize)>,
+    index: HashMap<Utf8PathBuf, usize>,
+}
+
+impl Archive {
+    pub fn open(path: &Utf8Path, key: &EncryptionKey) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+        let agt = Agt::read(&mut reader, key.as_bytes())?;
+
+        let mut paths = Vec::new();
+        for (idx, file) in agt.files.iter().enumerate() {
+            match normalized_path(&file.file_name) {
+                Ok(file_path) => paths.push((file_path, idx)),
+                Err(err) => eprintln!("Skipping {:?} in {}: {}", file.file_name, path, err),
+            }
+        }
+        let index = paths.iter().cloned().collect();
+
+        Ok(Self {
+            path: path.to_owned(),
+            key: key.clone(),
+            agt,
+            paths,
+            index,
+        })
+    }
+
+    pub fn files(&self) -> &[AgtFile] {
+        &self.agt.files
+    }
+
+    pub fn read_file(&self, file: &AgtFile) -> anyhow::Result<Vec<u8>> {
+        let reader = File::open(&self.path)?;
+        let mut reader = BufReader::new(reader);
+        self.agt.read_file(&mut reader, file, self.key.as_bytes())
+    }
+
+    /// Paths of the archived files relative to the archive, and their sizes.
+    pub fn file_paths_with_sizes(&self) -> Vec<(Utf8PathBuf, u64)> {
+        self.paths
+            .iter()
+            .map(|(path, idx)| (path.clone(), self.agt.files[*idx].file_length as u64))
+            .collect()
+    }
+
+    pub fn find_file(&self, inner_path: &Utf8Path) -> Option<&AgtFile> {
+        self.index
+            .get(inner_path)
+            .and_then(|idx| self.agt.files.get(*idx))
+    }
+
+    /// Whether any archived file lies below `inner_path`.
+    pub fn has_dir(&self, inner_path: &Utf8Path) -> bool {
+        self.paths
+            .iter()
+            .any(|(path, _)| path != inner_path && path.starts_with(inner_path))
+    }
+
+    pub fn read_path(&self, inner_path: &Utf8Path) -> anyhow::Result<Vec<u8>> {
+        match self.find_file(inner_path) {
+            Some(file) => self.read_file(file),
+            None => anyhow::bail!("{} does not contain {}", self.path, inner_path),
+        }
+    }
+
+    /// Writes the given archived files below `target_dir`, keeping their paths relative to `root`.
+    pub fn extract(
+        &self,
+        inner_paths: &[Utf8PathBuf],
+        root: &Utf8Path,
+        target_dir: &Utf8Path,
+    ) -> anyhow::Result<()> {
+        for inner_path in inner_paths {
+            let relative_path = inner_path.strip_prefix(root).unwrap_or(inner_path);
+            if !is_contained(relative_path) {
+                anyhow::bail!("refusing to extract {} outside {}", inner_path, target_dir);
+            }
+            let target_path = target_dir.join(relative_path);
+            if let Some(parent) = target_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&target_path, self.read_path(inner_path)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Turns an archived file name into a relative path, refusing names that would land outside
+/// the directory the archive is extracted to.
+fn normalized_path(name: &str) -> anyhow::Result<Utf8PathBuf> {
+    if name.starts_with(|c| c == '/' || c == '\\') {
+        anyhow::bail!("absolute path");
+    }
+    let mut path = Utf8PathBuf::new();
+    for part in name.split(|c| c == '/' || c == '\\') {
+        match part {
+            "" | "." => {}
+            ".." => anyhow::bail!("path leaves the archive root"),
+            // drive prefixes such as C: and alternate data streams
+            _ if part.contains(':') => anyhow::bail!("path contains a drive or stream prefix"),
+            _ => path.push(part),
+        }
+    }
+    if path.as_str().is_empty() {
+        anyhow::bail!("empty path");
+    }
+    Ok(path)
+}
+
+/// Whether joining `path` onto a directory stays inside that directory.
+fn is_contained(path: &Utf8Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Utf8Component::Normal(_)))
+}
+
+/// A parsed archive and the modification time of the file it was parsed from.
+type CachedArchive = (Option<SystemTime>, Arc<Archive>);
+
+/// Parsed archives, shared by everything reading through the project so an archive is only
+/// decrypted again once it changes on disk.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveCache {
+    archives: Arc<Mutex<HashMap<Utf8PathBuf, CachedArchive>>>,
+}
+
+impl ArchiveCache {
+    pub fn open(&self, path: &Utf8Path, key: &EncryptionKey) -> anyhow::Result<Arc<Archive>> {
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if let Some((cached_modified, archive)) = self.archives.lock().unwrap().get(path) {
+            if *cached_modified == modified && archive.key == *key {
+                return Ok(archive.clone());
+            }
+        }
+
+        // parsing happens outside the lock, another thread may parse the same archive meanwhile
+        let archive = Arc::new(Archive::open(path, key)?);
+        self.archives
+            .lock()
+            .unwrap()
+            .insert(path.to_owned(), (modified, archive.clone()));
+        Ok(archive)
+    }
+}
+
+/// Splits a virtual path such as `data/tracks.agt/world/terrain0.lf` into the
+/// archive on disk and the path of the file inside it.
+pub fn split_archive_path(path: &Utf8Path) -> Option<(&Utf8Path, &Utf8Path)> {
+    path.ancestors()
+        .skip(1)
+        .find(|ancestor| is_archive_path(ancestor) && ancestor.is_file())
+        .map(|archive_path| (archive_path, path.strip_prefix(archive_path).unwrap()))
+}
+
+/// Checks that `key` can decrypt the first archive found in `game_dir`.
+///
+/// Returns `Ok(false)` when the directory contains no archives to test against.
+pub fn verify_key(game_dir: &Utf8Path, key: &EncryptionKey) -> anyhow::Result<bool> {
+    match find_first_archive(game_dir)? {
+        Some(path) => {
+            let archive = Archive::open(&path, key)?;
+            if let Some(file) = archive.files().first() {
+                archive.read_file(file)?;
+            }
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+fn find_first_archive(dir: &Utf8Path) -> anyhow::Result<Option<Utf8PathBuf>> {
+    let mut subdirs = Vec::new();
+    for entry in dir.read_dir_utf8()? {
+        let path = entry?.into_path();
+        if path.is_dir() {
+            subdirs.push(path);
+        } else if is_archive_path(&path) {
+            return Ok(Some(path));
+        }
+    }
+    for subdir in subdirs {
+        if let Some(path) = find_first_archive(&subdir)? {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+/// Extracts virtual archive paths below `target_dir`, keeping their paths relative to `root`.
+pub fn extract_paths(
+    paths: &[Utf8PathBuf],
+    root: &Utf8Path,
+    target_dir: &Utf8Path,
+    archives: &ArchiveCache,
+    key: &EncryptionKey,
+) -> anyhow::Result<()> {
+    let mut paths_by_archive: Vec<(&Utf8Path, Vec<Utf8PathBuf>)> = Vec::new();
+    for path in paths {
+        let (archive_path, inner_path) = match split_archive_path(path) {
+            Some(split) => split,
+            None => anyhow::bail!("{} is not inside an archive", path),
+        };
+        match paths_by_archive
+            .iter_mut()
+            .find(|(p, _)| *p == archive_path)
+        {
+            Some((_, inner_paths)) => inner_paths.push(inner_path.to_owned()),
+            None => paths_by_archive.push((archive_path, vec![inner_path.to_owned()])),
+        }
+    }
+
+    for (archive_path, inner_paths) in paths_by_archive {
+        let archive = archives.open(archive_path, key)?;
+        let inner_root = root.strip_prefix(archive_path).unwrap_or(Utf8Path::new(""));
+        archive.extract(&inner_paths, inner_root, target_dir)?;
+    }
+
+    Ok(())
+}
+
+/// Writes `files` (archive path, contents) into a new archive and verifies it by reading it back.
+pub fn write_archive(
+    path: &Utf8Path,
+    files: &[(String, Vec<u8>)],
+    key: &EncryptionKey,
+) -> anyhow::Result<()> {
+    {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        Agt::write(&mut writer, files, key.as_bytes())?;
+    }
+
+    let archive = Archive::open(path, key)?;
+    if archive.files().len() != files.len() {
+        anyhow::bail!(
+            "archive contains {} files, expected {}",
+            archive.files().len(),
+            files.len()
+        );
+    }
+    for (name, data) in files {
+        let inner_path = normalized_path(name)?;
+        if archive.read_path(&inner_path)? != *data {
+            anyhow::bail!("{} did not match after reading it back", name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_separators() {
+        assert_eq!(
+            normalized_path("world\\track1/terrain0.lf").unwrap(),
+            ["world", "track1", "terrain0.lf"]
+                .iter()
+                .collect::<Utf8PathBuf>()
+        );
+        assert_eq!(
+            normalized_path("./data//model.nif").unwrap(),
+            ["data", "model.nif"].iter().collect::<Utf8PathBuf>()
+        );
+    }
+
+    #[test]
+    fn rejects_escaping_names() {
+        for name in [
+            "..\\..\\x",
+            "data/../../x",
+            "/etc/passwd",
+            "\\windows\\x",
+            "C:\\x",
+            "C:x",
+            "data/file.nif:stream",
+            "",
+            "./",
+        ] {
+            assert!(normalized_path(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn only_relative_paths_are_contained() {
+        assert!(is_contained(Utf8Path::new("data/model.nif")));
+        assert!(!is_contained(Utf8Path::new("../model.nif")));
+        assert!(!is_contained(Utf8Path::new("/model.nif")));
+    }
+}
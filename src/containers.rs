@@ -0,0 +1,532 @@
+//! Containers that embed NIF files by offset and length: terrain (LF), block objects (LBF) and
+//! model tables (LOF).
+
+use std::{
+    collections::HashSet,
+    io::{Cursor, Read, Seek, SeekFrom},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use nif::Nif;
+use serde::{Deserialize, Serialize};
+use slidetown::parsers::{lbf::Lbf, lf::Lf, lof::Lof};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContainerKind {
+    Lf,
+    Lbf,
+    Lof,
+}
+
+impl ContainerKind {
+    pub fn from_path(path: &Utf8Path) -> Option<Self> {
+        match path.extension().map(str::to_lowercase).as_deref() {
+            Some("lf") => Some(ContainerKind::Lf),
+            Some("lbf") => Some(ContainerKind::Lbf),
+            Some("lof") => Some(ContainerKind::Lof),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedNif {
+    /// File name to use when the NIF is written out on its own.
+    pub file_name: String,
+    pub file_offset: u32,
+    pub file_length: u32,
+}
+
+impl EmbeddedNif {
+    fn range(&self) -> std::ops::Range<usize> {
+        self.file_offset as usize..self.file_offset as usize + self.file_length as usize
+    }
+}
+
+pub fn lf_block_file_name(block_index: u32) -> String {
+    format!("block_{}.nif", block_index)
+}
+
+pub fn lbf_object_file_name(block_idx: usize, object_idx: usize) -> String {
+    format!("block_{}_object_{}.nif", block_idx, object_idx)
+}
+
+/// Every NIF embedded in the container, in the order the container lists them.
+pub fn embedded_nifs(kind: ContainerKind, data: &[u8]) -> anyhow::Result<Vec<EmbeddedNif>> {
+    let entries = read_index(kind, &mut Cursor::new(data))?;
+    for entry in entries.iter() {
+        embedded_nif_data(data, entry)?;
+    }
+    Ok(entries)
+}
+
+fn read_index<R: Read + Seek>(
+    kind: ContainerKind,
+    mut reader: R,
+) -> anyhow::Result<Vec<EmbeddedNif>> {
+    Ok(match kind {
+        ContainerKind::Lf => Lf::read(&mut reader)?
+            .blocks
+            .iter()
+            .map(|block| EmbeddedNif {
+                file_name: lf_block_file_name(block.index),
+                file_offset: block.file_offset,
+                file_length: block.file_length,
+            })
+            .collect(),
+        ContainerKind::Lbf => Lbf::parse(&mut reader)?
+            .blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(block_idx, block)| {
+                block
+                    .objects
+                    .iter()
+                    .enumerate()
+                    .map(move |(object_idx, object)| EmbeddedNif {
+                        file_name: lbf_object_file_name(block_idx, object_idx),
+                        file_offset: object.file_offset,
+                        file_length: object.file_length,
+                    })
+            })
+            .collect(),
+        ContainerKind::Lof => Lof::read_without_data(&mut reader)?
+            .models
+            .iter()
+            .map(|model| EmbeddedNif {
+                file_name: model.file_name.clone(),
+                file_offset: model.file_offset,
+                file_length: model.file_length,
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// The bytes of an embedded NIF, or an error when the entry points outside the container.
+pub fn embedded_nif_data<'a>(data: &'a [u8], entry: &EmbeddedNif) -> anyhow::Result<&'a [u8]> {
+    match data.get(entry.range()) {
+        Some(nif_data) => Ok(nif_data),
+        None => anyhow::bail!("{} points past the end of the container", entry.file_name),
+    }
+}
+
+pub fn read_embedded_nif(data: &[u8], entry: &EmbeddedNif) -> anyhow::Result<Vec<u8>> {
+    embedded_nif_data(data, entry).map(<[u8]>::to_vec)
+}
+
+/// Returns the container with the embedded NIF at `entry_idx` swapped for `nif_data`.
+///
+/// The offset and length fields of every entry are located where the container's parser reads
+/// them, shifted along with the data and the result is parsed again to make sure it is intact.
+pub fn replace_embedded_nif(
+    kind: ContainerKind,
+    data: &[u8],
+    entry_idx: usize,
+    nif_data: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    if let Err(err) = Nif::parse(&mut Cursor::new(nif_data)) {
+        anyhow::bail!("replacement is not a valid NIF: {}", err);
+    }
+
+    let entries = embedded_nifs(kind, data)?;
+    let target = match entries.get(entry_idx) {
+        Some(target) => target.clone(),
+        None => anyhow::bail!("container has no entry {}", entry_idx),
+    };
+    let fields = locate_fields(kind, data, &entries)?;
+
+    let target_range = target.range();
+    let delta = nif_data.len() as i64 - target.file_length as i64;
+    let shift = |position: usize| {
+        if position >= target_range.end {
+            (position as i64 + delta) as usize
+        } else {
+            position
+        }
+    };
+
+    let mut result = Vec::with_capacity((data.len() as i64 + delta) as usize);
+    result.extend_from_slice(&data[..target_range.start]);
+    result.extend_from_slice(nif_data);
+    result.extend_from_slice(&data[target_range.end..]);
+
+    let mut expected = Vec::with_capacity(entries.len());
+    for (idx, (entry, (offset_position, length_position))) in entries.iter().zip(fields).enumerate()
+    {
+        let file_offset = if entry.file_offset > target.file_offset {
+            u32::try_from(entry.file_offset as i64 + delta)?
+        } else {
+            entry.file_offset
+        };
+        let file_length = if idx == entry_idx {
+            u32::try_from(nif_data.len())?
+        } else {
+            entry.file_length
+        };
+
+        let offset_position = shift(offset_position);
+        let length_position = shift(length_position);
+        result[offset_position..offset_position + 4].copy_from_slice(&file_offset.to_le_bytes());
+        result[length_position..length_position + 4].copy_from_slice(&file_length.to_le_bytes());
+
+        expected.push(EmbeddedNif {
+            file_offset,
+            file_length,
+            ..entry.clone()
+        });
+    }
+
+    let reparsed = embedded_nifs(kind, &result)?;
+    if reparsed != expected {
+        anyhow::bail!("rewritten container does not list the expected entries");
+    }
+    Nif::parse(&mut Cursor::new(embedded_nif_data(
+        &result,
+        &reparsed[entry_idx],
+    )?))?;
+
+    Ok(result)
+}
+
+/// Describes extracted NIFs so they can be put back into their container.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtractManifest {
+    pub container: String,
+    pub kind: ContainerKind,
+    pub entries: Vec<ExtractManifestEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtractManifestEntry {
+    /// Position of the entry in the container.
+    pub index: usize,
+    pub file_name: String,
+    /// Extracted file, relative to the manifest.
+    pub path: Utf8PathBuf,
+    pub file_offset: u32,
+    pub file_length: u32,
+}
+
+pub fn manifest_file_name(container_path: &Utf8Path) -> String {
+    format!(
+        "{}.manifest.json",
+        container_path.file_name().unwrap_or("container")
+    )
+}
+
+/// Writes the given entries of the container to `target_dir` and adds them to the container's
+/// manifest there, creating it if needed. Existing files are never overwritten, a name that is
+/// taken gets a numbered suffix instead.
+///
+/// Returns the path of the manifest.
+pub fn extract_embedded_nifs(
+    kind: ContainerKind,
+    container_path: &Utf8Path,
+    data: &[u8],
+    entries: &[(usize, &EmbeddedNif)],
+    target_dir: &Utf8Path,
+) -> anyhow::Result<Utf8PathBuf> {
+    std::fs::create_dir_all(target_dir)?;
+
+    let container = container_path.file_name().unwrap_or_default().to_string();
+    let manifest_path = target_dir.join(manifest_file_name(container_path));
+    let mut manifest = if manifest_path.is_file() {
+        let manifest: ExtractManifest = serde_json::from_slice(&std::fs::read(&manifest_path)?)?;
+        if manifest.kind != kind || manifest.container != container {
+            anyhow::bail!(
+                "{} belongs to another container ({})",
+                manifest_path,
+                manifest.container
+            );
+        }
+        manifest
+    } else {
+        ExtractManifest {
+            container,
+            kind,
+            entries: Vec::new(),
+        }
+    };
+
+    let mut used_names = manifest
+        .entries
+        .iter()
+        .map(|entry| entry.path.as_str().to_lowercase())
+        .collect::<HashSet<_>>();
+    for (index, entry) in entries {
+        // model tables may store directories and duplicate names
+        let mut name = entry
+            .file_name
+            .rsplit(|c| c == '/' || c == '\\')
+            .next()
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("entry_{}", index));
+        if !name.to_lowercase().ends_with(".nif") {
+            name.push_str(".nif");
+        }
+        let name = unique_file_name(target_dir, &name, &used_names);
+        used_names.insert(name.to_lowercase());
+
+        std::fs::write(target_dir.join(&name), embedded_nif_data(data, entry)?)?;
+        manifest
+            .entries
+            .retain(|manifest_entry| manifest_entry.index != *index);
+        manifest.entries.push(ExtractManifestEntry {
+            index: *index,
+            file_name: entry.file_name.clone(),
+            path: name.into(),
+            file_offset: entry.file_offset,
+            file_length: entry.file_length,
+        });
+    }
+    manifest.entries.sort_by_key(|entry| entry.index);
+
+    std::fs::write(&manifest_path, serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(manifest_path)
+}
+
+/// `name`, or `name` with the first numbered suffix that is neither in `used_names` nor an
+/// existing file in `dir`.
+fn unique_file_name(dir: &Utf8Path, name: &str, used_names: &HashSet<String>) -> String {
+    let is_free =
+        |name: &str| !used_names.contains(&name.to_lowercase()) && !dir.join(name).exists();
+    if is_free(name) {
+        return name.to_string();
+    }
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    (1..)
+        .map(|suffix| format!("{}_{}.{}", stem, suffix, extension))
+        .find(|name| is_free(name))
+        .unwrap()
+}
+
+/// Puts every extracted file listed in the manifest that differs from the container back in.
+///
+/// Returns the rewritten container and how many entries were replaced.
+pub fn repack_from_manifest(
+    kind: ContainerKind,
+    data: &[u8],
+    manifest_path: &Utf8Path,
+) -> anyhow::Result<(Vec<u8>, usize)> {
+    let manifest: ExtractManifest = serde_json::from_slice(&std::fs::read(manifest_path)?)?;
+    if manifest.kind != kind {
+        anyhow::bail!(
+            "manifest was written for a {:?} container, not {:?}",
+            manifest.kind,
+            kind
+        );
+    }
+    let manifest_dir = manifest_path.parent().unwrap_or_else(|| Utf8Path::new(""));
+
+    let mut data = data.to_vec();
+    let mut replaced = 0;
+    for manifest_entry in manifest.entries.iter() {
+        let entries = embedded_nifs(kind, &data)?;
+        let entry = match entries.get(manifest_entry.index) {
+            Some(entry) if entry.file_name == manifest_entry.file_name => entry,
+            _ => anyhow::bail!(
+                "container has no entry {} named {}",
+                manifest_entry.index,
+                manifest_entry.file_name
+            ),
+        };
+
+        let nif_data = std::fs::read(manifest_dir.join(&manifest_entry.path))?;
+        if nif_data != embedded_nif_data(&data, entry)? {
+            data = replace_embedded_nif(kind, &data, manifest_entry.index, &nif_data)?;
+            replaced += 1;
+        }
+    }
+    Ok((data, replaced))
+}
+
+/// Positions of the offset and length field of every entry.
+fn locate_fields(
+    kind: ContainerKind,
+    data: &[u8],
+    entries: &[EmbeddedNif],
+) -> anyhow::Result<Vec<(usize, usize)>> {
+    let mut recorder = FieldRecorder::new(data);
+    read_index(kind, &mut recorder)?;
+    match_fields(data, &recorder.positions, entries)
+}
+
+/// Reader that remembers where 4 byte values were read, which is how the parsers read every
+/// offset and length field.
+struct FieldRecorder<'a> {
+    cursor: Cursor<&'a [u8]>,
+    positions: Vec<usize>,
+}
+
+impl<'a> FieldRecorder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            cursor: Cursor::new(data),
+            positions: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Read for FieldRecorder<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let position = self.cursor.position() as usize;
+        let read = self.cursor.read(buf)?;
+        if buf.len() == 4 && read == 4 {
+            self.positions.push(position);
+        }
+        Ok(read)
+    }
+}
+
+impl<'a> Seek for FieldRecorder<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.cursor.seek(pos)
+    }
+}
+
+/// Picks the offset and length field of every entry out of the positions of the values the
+/// parser read. Entries are read in the order they are listed and the whole index reads a
+/// length either right after or right before its offset.
+fn match_fields(
+    data: &[u8],
+    positions: &[usize],
+    entries: &[EmbeddedNif],
+) -> anyhow::Result<Vec<(usize, usize)>> {
+    let value = |idx: usize| {
+        positions.get(idx).and_then(|&position| {
+            data.get(position..position + 4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        })
+    };
+
+    let mut last_err = None;
+    for length_follows_offset in [true, false] {
+        let mut fields = Vec::with_capacity(entries.len());
+        let mut next = 0;
+        for entry in entries {
+            let found = (next..positions.len())
+                .filter(|&idx| value(idx) == Some(entry.file_offset))
+                .find_map(|idx| {
+                    let length_idx = if length_follows_offset {
+                        idx + 1
+                    } else {
+                        idx.checked_sub(1)
+                            .filter(|&length_idx| length_idx >= next)?
+                    };
+                    (value(length_idx) == Some(entry.file_length)).then_some((idx, length_idx))
+                });
+            match found {
+                Some((offset_idx, length_idx)) => {
+                    fields.push((positions[offset_idx], positions[length_idx]));
+                    next = offset_idx.max(length_idx) + 1;
+                }
+                None => {
+                    last_err = Some(anyhow::anyhow!(
+                        "could not find the offset and length of {}",
+                        entry.file_name
+                    ));
+                    break;
+                }
+            }
+        }
+        if fields.len() == entries.len() {
+            return Ok(fields);
+        }
+    }
+    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("container has no entries")))
+}
+
+/// Replaces the file at `path` through a temporary file, keeping the previous version as
+/// `<name>.bak`.
+pub fn write_with_backup(path: &Utf8Path, data: &[u8]) -> anyhow::Result<()> {
+    let file_name = match path.file_name() {
+        Some(file_name) => file_name,
+        None => anyhow::bail!("{} is not a file path", path),
+    };
+    let temp_path = path.with_file_name(format!("{}.tmp", file_name));
+    std::fs::write(&temp_path, data)?;
+    if path.is_file() {
+        std::fs::copy(path, path.with_file_name(format!("{}.bak", file_name)))?;
+    }
+    if let Err(err) = std::fs::rename(&temp_path, path) {
+        std::fs::remove_file(&temp_path).ok();
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file_offset: u32, file_length: u32) -> EmbeddedNif {
+        EmbeddedNif {
+            file_name: format!("at_{}.nif", file_offset),
+            file_offset,
+            file_length,
+        }
+    }
+
+    /// Reads an index of `count` entries the way the parsers do, a value at a time.
+    fn read_test_index(data: &[u8], fields_per_entry: usize) -> Vec<usize> {
+        let mut recorder = FieldRecorder::new(data);
+        let mut value = [0u8; 4];
+        recorder.read_exact(&mut value).unwrap();
+        for _ in 0..u32::from_le_bytes(value) as usize * fields_per_entry {
+            recorder.read_exact(&mut value).unwrap();
+        }
+        recorder.positions
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn finds_offset_then_length() {
+        // count, then index, offset and length per entry
+        let data = words(&[2, 0, 28, 4, 1, 32, 4, 0, 0]);
+        let positions = read_test_index(&data, 3);
+        let fields = match_fields(&data, &positions, &[entry(28, 4), entry(32, 4)]).unwrap();
+        assert_eq!(fields, vec![(8, 12), (20, 24)]);
+    }
+
+    #[test]
+    fn finds_length_then_offset() {
+        let data = words(&[2, 4, 20, 4, 24, 0, 0]);
+        let positions = read_test_index(&data, 2);
+        let fields = match_fields(&data, &positions, &[entry(20, 4), entry(24, 4)]).unwrap();
+        assert_eq!(fields, vec![(8, 4), (16, 12)]);
+    }
+
+    #[test]
+    fn ignores_matching_values_in_unread_bytes() {
+        // the unread tail repeats the first entry's offset and length
+        let data = words(&[1, 12, 4, 12, 4]);
+        let positions = read_test_index(&data, 2);
+        let fields = match_fields(&data, &positions, &[entry(12, 4)]).unwrap();
+        assert_eq!(fields, vec![(4, 8)]);
+    }
+
+    #[test]
+    fn taken_names_get_a_suffix() {
+        let dir = Utf8Path::new("does/not/exist");
+        let used_names = ["car.nif", "car_1.nif"]
+            .into_iter()
+            .map(str::to_string)
+            .collect::<HashSet<_>>();
+        assert_eq!(unique_file_name(dir, "wheel.nif", &used_names), "wheel.nif");
+        assert_eq!(unique_file_name(dir, "Car.nif", &used_names), "Car_2.nif");
+    }
+
+    #[test]
+    fn missing_fields_are_an_error() {
+        let data = words(&[1, 12, 8, 0]);
+        let positions = read_test_index(&data, 2);
+        assert!(match_fields(&data, &positions, &[entry(12, 4)]).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use eframe::egui;
+
+use crate::{
+    archive,
+    containers::{self, ContainerKind, EmbeddedNif},
+    project::Project,
+    storage::{
+        prompt_open_manifest_file, prompt_open_nif_file, prompt_output_directory,
+        prompt_save_container_file,
+    },
+};
+
+/// Lists the NIFs embedded in an LF, LBF or LOF container, extracts and replaces them.
+#[derive(Debug)]
+pub struct EmbeddedNifsDialog {
+    path: Utf8PathBuf,
+    kind: ContainerKind,
+    data: Vec<u8>,
+    entries: Vec<EmbeddedNif>,
+    status: Option<Result<String, String>>,
+}
+
+impl EmbeddedNifsDialog {
+    pub fn new(project: &Project, path: Utf8PathBuf) -> anyhow::Result<Self> {
+        let kind = match ContainerKind::from_path(&path) {
+            Some(kind) => kind,
+            None => anyhow::bail!("{} does not embed NIF files", path),
+        };
+        let data = project.read_file(&path)?;
+        let entries = containers::embedded_nifs(kind, &data)?;
+        Ok(Self {
+            path,
+            kind,
+            data,
+            entries,
+            status: None,
+        })
+    }
+
+    /// Returns false once the dialog has been closed.
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        let mut action = None;
+
+        egui::Window::new(format!(
+            "Embedded NIFs: {}",
+            self.path.file_name().unwrap_or_default()
+        ))
+        .open(&mut open)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} embedded files", self.entries.len()));
+                if ui.button("Extract all…").clicked() {
+                    action = Some(Action::ExtractAll);
+                }
+                if ui.button("Repack from manifest…").clicked() {
+                    action = Some(Action::Repack);
+                }
+            });
+            egui::ScrollArea::vertical()
+                .max_height(400.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("embedded_nifs")
+                        .num_columns(5)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (idx, entry) in self.entries.iter().enumerate() {
+                                ui.label(idx.to_string());
+                                ui.label(&entry.file_name);
+                                ui.label(format!("{} bytes", entry.file_length));
+                                if ui.button("Extract…").clicked() {
+                                    action = Some(Action::Extract(idx));
+                                }
+                                if ui.button("Replace with…").clicked() {
+                                    action = Some(Action::Replace(idx));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            match &self.status {
+                Some(Ok(status)) => {
+                    ui.label(egui::RichText::new(status).color(egui::Color32::GREEN));
+                }
+                Some(Err(status)) => {
+                    ui.label(egui::RichText::new(status).color(egui::Color32::RED));
+                }
+                None => {}
+            }
+        });
+
+        match action {
+            Some(Action::Extract(idx)) => {
+                if let Some(target_dir) = prompt_output_directory() {
+                    self.status = Some(
+                        self.extract(&[idx], &target_dir)
+                            .map_err(|err| format!("Failed to extract entry {}: {}", idx, err)),
+                    );
+                }
+            }
+            Some(Action::ExtractAll) => {
+                if let Some(target_dir) = prompt_output_directory() {
+                    let all = (0..self.entries.len()).collect::<Vec<_>>();
+                    self.status = Some(
+                        self.extract(&all, &target_dir)
+                            .map_err(|err| format!("Failed to extract: {}", err)),
+                    );
+                }
+            }
+            Some(Action::Replace(idx)) => {
+                if let Some(nif_path) = prompt_open_nif_file() {
+                    self.status = Some(
+                        self.replace(idx, &nif_path)
+                            .map_err(|err| format!("Failed to replace entry {}: {}", idx, err)),
+                    );
+                }
+            }
+            Some(Action::Repack) => {
+                if let Some(manifest_path) = prompt_open_manifest_file() {
+                    self.status = Some(
+                        self.repack(&manifest_path)
+                            .map_err(|err| format!("Failed to repack: {}", err)),
+                    );
+                }
+            }
+            None => {}
+        }
+
+        open
+    }
+
+    fn extract(&self, indices: &[usize], target_dir: &Utf8Path) -> anyhow::Result<String> {
+        let entries = indices
+            .iter()
+            .map(|&idx| (idx, &self.entries[idx]))
+            .collect::<Vec<_>>();
+        let manifest_path = containers::extract_embedded_nifs(
+            self.kind, &self.path, &self.data, &entries, target_dir,
+        )?;
+        Ok(format!(
+            "Extracted {} files and added them to {}",
+            entries.len(),
+            manifest_path
+        ))
+    }
+
+    fn replace(&mut self, idx: usize, nif_path: &Utf8Path) -> anyhow::Result<String> {
+        let nif_data = std::fs::read(nif_path)?;
+        let data = containers::replace_embedded_nif(self.kind, &self.data, idx, &nif_data)?;
+        let file_name = self.entries[idx].file_name.clone();
+        let output_path = self.write_container(data)?;
+        Ok(format!("Replaced {} and wrote {}", file_name, output_path))
+    }
+
+    fn repack(&mut self, manifest_path: &Utf8Path) -> anyhow::Result<String> {
+        let (data, replaced) =
+            containers::repack_from_manifest(self.kind, &self.data, manifest_path)?;
+        if replaced == 0 {
+            return Ok("All extracted files match the container, nothing to repack".into());
+        }
+        let output_path = self.write_container(data)?;
+        Ok(format!(
+            "Replaced {} entries and wrote {}",
+            replaced, output_path
+        ))
+    }
+
+    /// Writes a rewritten container back in place, or to a chosen file for archived containers.
+    fn write_container(&mut self, data: Vec<u8>) -> anyhow::Result<Utf8PathBuf> {
+        let output_path = if archive::split_archive_path(&self.path).is_some() {
+            match prompt_save_container_file(&self.path) {
+                Some(output_path) => output_path,
+                None => anyhow::bail!("no output file chosen"),
+            }
+        } else {
+            self.path.clone()
+        };
+        containers::write_with_backup(&output_path, &data)?;
+
+        if output_path == self.path {
+            self.entries = containers::embedded_nifs(self.kind, &data)?;
+            self.data = data;
+        }
+        Ok(output_path)
+    }
+}
+
+enum Action {
+    Extract(usize),
+    ExtractAll,
+    Replace(usize),
+    Repack,
+}
@@ -1,61 +1,183 @@
-use std::{
-    fs::File,
-    io::{BufReader, Cursor, Read, Seek, SeekFrom},
-};
+use std::io::Cursor;
 
 use camino::Utf8PathBuf;
 use eframe::egui;
 use nif::Nif;
 use slidetown::parsers::lbf::Lbf;
 
-use crate::widgets::nif::NifWidget;
+use crate::{
+    containers::{self, ContainerKind},
+    widgets::nif::{CameraState, NifWidget},
+};
 
-use super::ProjectFileDialog;
+use super::{OpenRequest, ProjectFileDialog};
 
 #[derive(Debug)]
 pub struct LbfFileDialog {
     path: Utf8PathBuf,
     nif_widget: NifWidget,
+    /// Mesh groups of the objects in every block.
+    blocks: Vec<Vec<String>>,
+    open_request: Option<OpenRequest>,
 }
 
-impl ProjectFileDialog for LbfFileDialog {
-    fn create(path: Utf8PathBuf, frame: &mut eframe::Frame) -> Self {
-        let file = File::open(&path).unwrap();
-        let mut reader = BufReader::new(file);
-
+impl LbfFileDialog {
+    pub fn create(
+        path: Utf8PathBuf,
+        data: Vec<u8>,
+        frame: &mut eframe::Frame,
+    ) -> anyhow::Result<Self> {
         let render_state = frame.wgpu_render_state().unwrap();
         let mut nif_widget = NifWidget::new(render_state);
 
-        let data = Lbf::parse(&mut reader).unwrap();
-        for block in data.blocks.iter() {
-            for object in block.objects.iter() {
-                reader
-                    .seek(SeekFrom::Start(object.file_offset as _))
-                    .unwrap();
+        let lbf = Lbf::parse(&mut Cursor::new(&data))?;
+        // entries list every block's objects in turn, named after them
+        let mut entries = containers::embedded_nifs(ContainerKind::Lbf, &data)?.into_iter();
+        let mut blocks = Vec::new();
+        for block in lbf.blocks.iter() {
+            let mut objects = Vec::new();
+            for entry in entries.by_ref().take(block.objects.len()) {
+                let nif_data = containers::embedded_nif_data(&data, &entry)?;
+                let nif = Nif::parse(&mut Cursor::new(nif_data))?;
 
-                let mut nif_data = vec![0u8; object.file_length as _];
-                reader.read_exact(&mut nif_data).unwrap();
-
-                let nif = Nif::parse(&mut Cursor::new(nif_data)).unwrap();
-
-                nif_widget.add_nif(&nif, render_state, 0.0, None, None);
+                nif_widget.add_nif(
+                    &nif,
+                    render_state,
+                    Some(0.0),
+                    Some(entry.file_name.clone()),
+                    None,
+                );
+                objects.push(entry.file_name);
             }
+            blocks.push(objects);
         }
 
         nif_widget.reset_camera_from_bounds();
 
-        Self { path, nif_widget }
+        Ok(Self {
+            path,
+            nif_widget,
+            blocks,
+            open_request: None,
+        })
     }
+}
 
+impl ProjectFileDialog for LbfFileDialog {
     fn title(&self) -> String {
         self.path.file_name().unwrap().into()
     }
 
+    fn camera_state(&mut self) -> Option<CameraState> {
+        Some(self.nif_widget.camera_state())
+    }
+
+    fn set_camera_state(&mut self, state: CameraState) {
+        self.nif_widget.set_camera_state(state);
+    }
+
+    fn take_open_request(&mut self) -> Option<OpenRequest> {
+        self.open_request.take()
+    }
+
     fn show(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
-        let Self { nif_widget, .. } = self;
+        let Self {
+            path,
+            nif_widget,
+            blocks,
+            open_request,
+        } = self;
+
+        let render_state = frame.wgpu_render_state().unwrap().clone();
 
         ui.horizontal_top(|ui| {
-            nif_widget.show(ui, frame, None);
+            nif_widget.show(ui, frame, Some(egui::vec2(260.0, 0.0)));
+            ui.separator();
+            ui.vertical(|ui| {
+                ui.label(format!(
+                    "{} blocks, {} objects",
+                    blocks.len(),
+                    blocks.iter().map(Vec::len).sum::<usize>()
+                ));
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for (block_idx, objects) in blocks.iter().enumerate() {
+                            let id = ui.make_persistent_id(("lbf_block", block_idx));
+                            egui::collapsing_header::CollapsingState::load_with_default_open(
+                                ui.ctx(),
+                                id,
+                                false,
+                            )
+                            .show_header(ui, |ui| {
+                                let mut visible = objects
+                                    .iter()
+                                    .any(|group| nif_widget.is_group_visible(group));
+                                if ui
+                                    .checkbox(&mut visible, format!("Block {}", block_idx))
+                                    .changed()
+                                {
+                                    for group in objects.iter() {
+                                        nif_widget.set_group_visible(group, visible);
+                                    }
+                                }
+                                if ui
+                                    .small_button("🔍")
+                                    .on_hover_text("Focus camera on block")
+                                    .clicked()
+                                {
+                                    let bounds = objects
+                                        .iter()
+                                        .filter_map(|group| {
+                                            nif_widget.group_bounds(&render_state, group)
+                                        })
+                                        .reduce(|(min_a, max_a), (min_b, max_b)| {
+                                            (min_a.min(min_b), max_a.max(max_b))
+                                        });
+                                    if let Some((min, max)) = bounds {
+                                        nif_widget.focus_on_bounds(min, max);
+                                    }
+                                }
+                            })
+                            .body(|ui| {
+                                for (object_idx, group) in objects.iter().enumerate() {
+                                    ui.horizontal(|ui| {
+                                        let mut visible = nif_widget.is_group_visible(group);
+                                        if ui
+                                            .checkbox(
+                                                &mut visible,
+                                                format!("Object {}", object_idx),
+                                            )
+                                            .changed()
+                                        {
+                                            nif_widget.set_group_visible(group, visible);
+                                        }
+                                        if ui
+                                            .small_button("🔍")
+                                            .on_hover_text("Focus camera on object")
+                                            .clicked()
+                                        {
+                                            if let Some((min, max)) =
+                                                nif_widget.group_bounds(&render_state, group)
+                                            {
+                                                nif_widget.focus_on_bounds(min, max);
+                                            }
+                                        }
+                                        if ui
+                                            .small_button("↗")
+                                            .on_hover_text("Open object in its own tab")
+                                            .clicked()
+                                        {
+                                            *open_request =
+                                                Some(OpenRequest::new(path.join(group)));
+                                        }
+                                    });
+                                }
+                            });
+                        }
+                    });
+            });
         });
     }
 }
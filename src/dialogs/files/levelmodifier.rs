@@ -54,17 +54,22 @@ fn get_value_id_name(id: u32) -> String {
     }
 }
 
-impl ProjectFileDialog for LevelmodifierFileDialog {
-    fn create(path: Utf8PathBuf, _frame: &mut eframe::Frame) -> Self {
-        let data_buf = std::fs::read(&path).unwrap();
-        let data = LevelModifier::read(&mut std::io::Cursor::new(data_buf)).unwrap();
-        Self {
+impl LevelmodifierFileDialog {
+    pub fn create(
+        path: Utf8PathBuf,
+        data_buf: Vec<u8>,
+        _frame: &mut eframe::Frame,
+    ) -> anyhow::Result<Self> {
+        let data = LevelModifier::read(&mut std::io::Cursor::new(data_buf))?;
+        Ok(Self {
             data,
             path,
             selected_stat: Stat::Accel,
-        }
+        })
     }
+}
 
+impl ProjectFileDialog for LevelmodifierFileDialog {
     fn title(&self) -> String {
         self.path.file_name().unwrap().into()
     }
@@ -1,59 +1,142 @@
-use std::{
-    fs::File,
-    io::{BufReader, Cursor, Read, Seek, SeekFrom},
-};
+use std::io::Cursor;
 
 use camino::Utf8PathBuf;
 use eframe::egui;
 use nif::Nif;
 use slidetown::parsers::lf::Lf;
 
-use crate::widgets::nif::NifWidget;
+use crate::{
+    containers::{self, ContainerKind},
+    widgets::nif::{CameraState, NifWidget},
+};
 
-use super::ProjectFileDialog;
+use super::{OpenRequest, ProjectFileDialog};
 
 #[derive(Debug)]
 pub struct LfFileDialog {
     path: Utf8PathBuf,
     nif_widget: NifWidget,
+    /// Block indices and the mesh group each block was added to.
+    blocks: Vec<(u32, String)>,
+    open_request: Option<OpenRequest>,
 }
 
-impl ProjectFileDialog for LfFileDialog {
-    fn create(path: Utf8PathBuf, frame: &mut eframe::Frame) -> Self {
-        let file = File::open(&path).unwrap();
-        let mut reader = BufReader::new(file);
-
+impl LfFileDialog {
+    pub fn create(
+        path: Utf8PathBuf,
+        data: Vec<u8>,
+        frame: &mut eframe::Frame,
+    ) -> anyhow::Result<Self> {
         let render_state = frame.wgpu_render_state().unwrap();
         let mut nif_widget = NifWidget::new(render_state);
 
-        let data = Lf::read(&mut reader).unwrap();
-        for block in data.blocks.iter() {
-            reader
-                .seek(SeekFrom::Start(block.file_offset as _))
-                .unwrap();
+        let lf = Lf::read(&mut Cursor::new(&data))?;
+        // entries come in the order of the blocks, named after them
+        let entries = containers::embedded_nifs(ContainerKind::Lf, &data)?;
+        let mut blocks = Vec::new();
+        for (block, entry) in lf.blocks.iter().zip(entries) {
+            let nif_data = containers::embedded_nif_data(&data, &entry)?;
+            let nif = Nif::parse(&mut Cursor::new(nif_data))?;
 
-            let mut nif_data = vec![0u8; block.file_length as _];
-            reader.read_exact(&mut nif_data).unwrap();
-
-            let nif = Nif::parse(&mut Cursor::new(nif_data)).unwrap();
-
-            nif_widget.add_nif(&nif, render_state, 0.0, None, None);
+            nif_widget.add_nif(
+                &nif,
+                render_state,
+                Some(0.0),
+                Some(entry.file_name.clone()),
+                None,
+            );
+            blocks.push((block.index, entry.file_name));
         }
 
         nif_widget.reset_camera_from_bounds();
 
-        Self { path, nif_widget }
+        Ok(Self {
+            path,
+            nif_widget,
+            blocks,
+            open_request: None,
+        })
     }
+}
 
+impl ProjectFileDialog for LfFileDialog {
     fn title(&self) -> String {
         self.path.file_name().unwrap().into()
     }
 
+    fn camera_state(&mut self) -> Option<CameraState> {
+        Some(self.nif_widget.camera_state())
+    }
+
+    fn set_camera_state(&mut self, state: CameraState) {
+        self.nif_widget.set_camera_state(state);
+    }
+
+    fn take_open_request(&mut self) -> Option<OpenRequest> {
+        self.open_request.take()
+    }
+
     fn show(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
-        let Self { nif_widget, .. } = self;
+        let Self {
+            path,
+            nif_widget,
+            blocks,
+            open_request,
+        } = self;
 
         ui.horizontal_top(|ui| {
-            nif_widget.show(ui, frame, None);
+            nif_widget.show(ui, frame, Some(egui::vec2(220.0, 0.0)));
+            ui.separator();
+            ui.vertical(|ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} blocks", blocks.len()));
+                    if ui.small_button("Show all").clicked() {
+                        for (_, group) in blocks.iter() {
+                            nif_widget.set_group_visible(group, true);
+                        }
+                    }
+                    if ui.small_button("Hide all").clicked() {
+                        for (_, group) in blocks.iter() {
+                            nif_widget.set_group_visible(group, false);
+                        }
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for (index, group) in blocks.iter() {
+                            ui.horizontal(|ui| {
+                                let mut visible = nif_widget.is_group_visible(group);
+                                if ui
+                                    .checkbox(&mut visible, format!("Block {}", index))
+                                    .changed()
+                                {
+                                    nif_widget.set_group_visible(group, visible);
+                                }
+                                if ui
+                                    .small_button("🔍")
+                                    .on_hover_text("Focus camera on block")
+                                    .clicked()
+                                {
+                                    let render_state = frame.wgpu_render_state().unwrap();
+                                    if let Some((min, max)) =
+                                        nif_widget.group_bounds(render_state, group)
+                                    {
+                                        nif_widget.focus_on_bounds(min, max);
+                                    }
+                                }
+                                if ui
+                                    .small_button("↗")
+                                    .on_hover_text("Open block in its own tab")
+                                    .clicked()
+                                {
+                                    *open_request = Some(OpenRequest::new(path.join(group)));
+                                }
+                            });
+                        }
+                    });
+            });
         });
     }
 }
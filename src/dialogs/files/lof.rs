@@ -0,0 +1,319 @@
+use std::{collections::HashMap, io::Cursor, sync::mpsc, time::Duration};
+
+use camino::Utf8PathBuf;
+use eframe::egui;
+use nif::Nif;
+use slidetown::parsers::lof::{Lof, LofModel};
+
+use crate::{
+    containers::{self, EmbeddedNif},
+    project::{FileLister, Project},
+    widgets::nif::{CameraState, NifWidget},
+    world_data::{self, WorldObjectRef},
+};
+
+use super::{OpenRequest, ProjectFileDialog};
+
+#[derive(Debug)]
+struct ModelUsage {
+    object: WorldObjectRef,
+    position: glam::Vec3,
+}
+
+/// Placed objects of every track, by model index.
+type ModelUsages = HashMap<u32, Vec<ModelUsage>>;
+
+#[derive(Debug)]
+pub struct LofFileDialog {
+    path: Utf8PathBuf,
+    data: Vec<u8>,
+    models: Vec<LofModel>,
+    nif_widget: NifWidget,
+    filter_text: String,
+    selected_model: Option<usize>,
+    /// Why the selected model could not be previewed.
+    preview_error: Option<String>,
+    usages: ModelUsages,
+    usages_error: Option<String>,
+    /// Reading every track of the world happens off the UI thread.
+    usages_loading: Option<mpsc::Receiver<Result<ModelUsages, String>>>,
+    open_request: Option<OpenRequest>,
+}
+
+impl LofFileDialog {
+    pub fn create(
+        project: &Project,
+        path: Utf8PathBuf,
+        data: Vec<u8>,
+        frame: &mut eframe::Frame,
+    ) -> anyhow::Result<Self> {
+        let render_state = frame.wgpu_render_state().unwrap();
+        let nif_widget = NifWidget::new(render_state);
+
+        let lof = Lof::read_without_data(&mut Cursor::new(&data))?;
+
+        let usages_loading = path.parent().map(|dir| {
+            let (sender, receiver) = mpsc::channel();
+            let (files, dir) = (project.file_lister(), dir.to_path_buf());
+            std::thread::spawn(move || {
+                let usages = collect_usages(&files, &dir).map_err(|err| err.to_string());
+                sender.send(usages).ok();
+            });
+            receiver
+        });
+
+        Ok(Self {
+            path,
+            data,
+            models: lof.models,
+            nif_widget,
+            filter_text: String::new(),
+            selected_model: None,
+            preview_error: None,
+            usages: Default::default(),
+            usages_error: None,
+            usages_loading,
+            open_request: None,
+        })
+    }
+
+    fn poll_usages(&mut self, ctx: &egui::Context) {
+        let receiver = match &self.usages_loading {
+            Some(receiver) => receiver,
+            None => return,
+        };
+        match receiver.try_recv() {
+            Ok(Ok(usages)) => {
+                self.usages = usages;
+                self.usages_loading = None;
+            }
+            Ok(Err(err)) => {
+                self.usages_error = Some(err);
+                self.usages_loading = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.usages_error = Some("counting stopped unexpectedly".into());
+                self.usages_loading = None;
+            }
+        }
+    }
+
+    fn select_model(&mut self, idx: usize, frame: &mut eframe::Frame) {
+        self.selected_model = Some(idx);
+        let render_state = frame.wgpu_render_state().unwrap();
+        self.preview_error = match self.load_model(idx, render_state) {
+            Ok(()) => None,
+            Err(err) => {
+                self.nif_widget.clear_nifs(render_state);
+                Some(format!("{:#}", err))
+            }
+        };
+    }
+
+    fn load_model(
+        &mut self,
+        idx: usize,
+        render_state: &eframe::egui_wgpu::RenderState,
+    ) -> anyhow::Result<()> {
+        let model = match self.models.get(idx) {
+            Some(model) => model,
+            None => anyhow::bail!("there is no model {}", idx),
+        };
+        let entry = EmbeddedNif {
+            file_name: model.file_name.clone(),
+            file_offset: model.file_offset,
+            file_length: model.file_length,
+        };
+        let nif_data = containers::embedded_nif_data(&self.data, &entry)?;
+        let nif = Nif::parse(&mut Cursor::new(nif_data))?;
+        self.nif_widget
+            .set_nif(&nif, render_state, Some(0.0), None, None);
+        self.nif_widget.reset_camera_from_bounds();
+        Ok(())
+    }
+}
+
+/// Objects placed by the enabled blocks of every track in the world directory.
+fn collect_usages(files: &FileLister, dir_path: &camino::Utf8Path) -> anyhow::Result<ModelUsages> {
+    let lf = world_data::read_lf(files, dir_path)?;
+
+    let mut usages = ModelUsages::new();
+    for track in world_data::available_tracks(files, dir_path) {
+        let enabled_blocks = world_data::enabled_blocks(files, dir_path, &track)?;
+        let loi = world_data::read_loi(files, dir_path, &track, lf.block_count)?;
+        for block in loi
+            .blocks
+            .iter()
+            .filter(|block| enabled_blocks.contains(&block.block_index))
+        {
+            for (object_index, object) in block.objects.iter().enumerate() {
+                usages
+                    .entry(object.model_table_index)
+                    .or_default()
+                    .push(ModelUsage {
+                        object: WorldObjectRef {
+                            track: track.clone(),
+                            block_index: block.block_index,
+                            object_index,
+                        },
+                        position: world_data::object_position(object),
+                    });
+            }
+        }
+    }
+    Ok(usages)
+}
+
+impl ProjectFileDialog for LofFileDialog {
+    fn title(&self) -> String {
+        self.path.file_name().unwrap().into()
+    }
+
+    fn camera_state(&mut self) -> Option<CameraState> {
+        Some(self.nif_widget.camera_state())
+    }
+
+    fn set_camera_state(&mut self, state: CameraState) {
+        self.nif_widget.set_camera_state(state);
+    }
+
+    fn take_open_request(&mut self) -> Option<OpenRequest> {
+        self.open_request.take()
+    }
+
+    fn show(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
+        self.poll_usages(ctx);
+        let mut clicked_model = None;
+        let mut jump_to = None;
+
+        ui.horizontal_top(|ui| {
+            self.nif_widget
+                .show(ui, frame, Some(egui::vec2(420.0, 0.0)));
+            ui.separator();
+            ui.vertical(|ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.filter_text);
+                });
+                if self.usages_loading.is_some() {
+                    ui.label("Counting uses in every track…");
+                }
+                if let Some(err) = &self.usages_error {
+                    ui.label(
+                        egui::RichText::new(format!("Usage counts unavailable: {}", err))
+                            .color(egui::Color32::YELLOW),
+                    );
+                }
+                ui.separator();
+
+                let filter = self.filter_text.to_lowercase();
+                egui::ScrollArea::vertical()
+                    .id_source("lof_models")
+                    .max_height(ui.available_height() / 2.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        egui::Grid::new("lof_models_grid")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Index");
+                                ui.strong("File name");
+                                ui.strong("Size");
+                                ui.strong("Uses");
+                                ui.end_row();
+
+                                for (idx, model) in self.models.iter().enumerate() {
+                                    if !filter.is_empty()
+                                        && !model.file_name.to_lowercase().contains(&filter)
+                                        && model.index.to_string() != filter
+                                    {
+                                        continue;
+                                    }
+                                    let selected = self.selected_model == Some(idx);
+                                    if ui
+                                        .selectable_label(selected, model.index.to_string())
+                                        .clicked()
+                                        | ui.selectable_label(selected, &model.file_name).clicked()
+                                    {
+                                        clicked_model = Some(idx);
+                                    }
+                                    ui.label(format!("{} bytes", model.file_length));
+                                    let uses = self.usages.get(&model.index).map(Vec::len);
+                                    ui.label(uses.unwrap_or(0).to_string());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.separator();
+
+                let model = match self.selected_model.and_then(|idx| self.models.get(idx)) {
+                    Some(model) => model,
+                    None => {
+                        ui.label("Select a model to preview it and list where it is placed");
+                        return;
+                    }
+                };
+                ui.heading(format!("{} (index {})", model.file_name, model.index));
+                if let Some(err) = &self.preview_error {
+                    ui.label(
+                        egui::RichText::new(format!("Failed to load the model: {}", err))
+                            .color(egui::Color32::RED),
+                    );
+                }
+                let usages = self
+                    .usages
+                    .get(&model.index)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                if usages.is_empty() && self.usages_loading.is_none() {
+                    ui.label("Not placed by any track");
+                }
+                egui::ScrollArea::vertical()
+                    .id_source("lof_model_usages")
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        let mut current_track = None;
+                        for (idx, usage) in usages.iter().enumerate() {
+                            if current_track != Some(&usage.object.track) {
+                                current_track = Some(&usage.object.track);
+                                // usages are grouped by track
+                                let count = usages[idx..]
+                                    .iter()
+                                    .take_while(|other| other.object.track == usage.object.track)
+                                    .count();
+                                ui.strong(format!("{}: {} instances", usage.object.track, count));
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label(format!(
+                                    "Block {} #{} at {:.1} {:.1} {:.1}",
+                                    usage.object.block_index,
+                                    usage.object.object_index,
+                                    usage.position.x,
+                                    usage.position.y,
+                                    usage.position.z
+                                ));
+                                if ui.small_button("Jump").clicked() {
+                                    jump_to = Some(usage.object.clone());
+                                }
+                            });
+                        }
+                    });
+            });
+        });
+
+        if let Some(idx) = clicked_model {
+            self.select_model(idx, frame);
+        }
+        if let Some(object) = jump_to {
+            if let Some(dir_path) = self.path.parent() {
+                self.open_request = Some(OpenRequest {
+                    path: dir_path.to_owned(),
+                    select: Some(object),
+                    activate: true,
+                });
+            }
+        }
+    }
+}
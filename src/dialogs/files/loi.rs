@@ -0,0 +1,415 @@
+use std::{collections::HashMap, io::Cursor};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use eframe::egui;
+use serde::Serialize;
+use slidetown::parsers::{lof::Lof, loi::Loi};
+
+use crate::{
+    project::{FileLister, Project},
+    storage::prompt_save_table_file,
+    world_data::{self, WorldObjectRef},
+};
+
+use super::{OpenRequest, ProjectFileDialog};
+
+#[derive(Debug, Serialize)]
+struct LoiRow {
+    block_index: u32,
+    object_index: usize,
+    model_index: u32,
+    model_name: String,
+    position: [f32; 3],
+    /// XYZ euler angles in degrees.
+    rotation: [f32; 3],
+    scale: f32,
+    extra_index: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortColumn {
+    Block,
+    Model,
+    Name,
+    X,
+    Y,
+    Z,
+    Scale,
+    Extra,
+}
+
+/// Filter typed into the search box, e.g. `model:42 block:17 extra:yes lamp`.
+#[derive(Default)]
+struct RowFilter {
+    block: Option<u32>,
+    model: Option<u32>,
+    has_extra: Option<bool>,
+    terms: Vec<String>,
+}
+
+impl RowFilter {
+    fn parse(text: &str) -> Self {
+        let mut filter = Self::default();
+        for token in text.split_whitespace() {
+            let token = token.to_lowercase();
+            match token.split_once(':') {
+                Some(("block", value)) if value.parse::<u32>().is_ok() => {
+                    filter.block = value.parse().ok()
+                }
+                Some(("model", value)) if value.parse::<u32>().is_ok() => {
+                    filter.model = value.parse().ok()
+                }
+                Some(("extra", "yes")) => filter.has_extra = Some(true),
+                Some(("extra", "no")) => filter.has_extra = Some(false),
+                _ => filter.terms.push(token),
+            }
+        }
+        filter
+    }
+
+    fn matches(&self, row: &LoiRow) -> bool {
+        self.block.map_or(true, |block| row.block_index == block)
+            && self.model.map_or(true, |model| row.model_index == model)
+            && self
+                .has_extra
+                .map_or(true, |has_extra| (row.extra_index >= 0) == has_extra)
+            && self
+                .terms
+                .iter()
+                .all(|term| row.model_name.to_lowercase().contains(term))
+    }
+}
+
+#[derive(Debug)]
+pub struct LoiFileDialog {
+    path: Utf8PathBuf,
+    rows: Vec<LoiRow>,
+    /// Indices into `rows` after filtering and sorting.
+    visible_rows: Vec<usize>,
+    filter_text: String,
+    sort: (SortColumn, bool),
+    selected_row: Option<usize>,
+    status: Option<Result<String, String>>,
+    open_request: Option<OpenRequest>,
+}
+
+impl LoiFileDialog {
+    pub fn create(
+        project: &Project,
+        path: Utf8PathBuf,
+        data: Vec<u8>,
+        _frame: &mut eframe::Frame,
+    ) -> anyhow::Result<Self> {
+        let rows = load_rows(&project.file_lister(), &path, data)?;
+
+        let mut me = Self {
+            path,
+            rows,
+            visible_rows: Vec::new(),
+            filter_text: String::new(),
+            sort: (SortColumn::Block, true),
+            selected_row: None,
+            status: None,
+            open_request: None,
+        };
+        me.update_visible_rows();
+        Ok(me)
+    }
+
+    fn world_dir(&self) -> Option<&Utf8Path> {
+        self.path.parent()?.parent()
+    }
+
+    fn track(&self) -> Option<&str> {
+        self.path.parent()?.file_name()
+    }
+
+    fn update_visible_rows(&mut self) {
+        let filter = RowFilter::parse(&self.filter_text);
+        let rows = &self.rows;
+        self.visible_rows = (0..rows.len())
+            .filter(|&idx| filter.matches(&rows[idx]))
+            .collect();
+
+        let (column, ascending) = self.sort;
+        self.visible_rows.sort_by(|&a, &b| {
+            let (a, b) = (&rows[a], &rows[b]);
+            let ordering = match column {
+                SortColumn::Block => a.block_index.cmp(&b.block_index),
+                SortColumn::Model => a.model_index.cmp(&b.model_index),
+                SortColumn::Name => a.model_name.cmp(&b.model_name),
+                SortColumn::X => a.position[0].total_cmp(&b.position[0]),
+                SortColumn::Y => a.position[1].total_cmp(&b.position[1]),
+                SortColumn::Z => a.position[2].total_cmp(&b.position[2]),
+                SortColumn::Scale => a.scale.total_cmp(&b.scale),
+                SortColumn::Extra => a.extra_index.cmp(&b.extra_index),
+            }
+            .then(a.block_index.cmp(&b.block_index))
+            .then(a.object_index.cmp(&b.object_index));
+            if ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+    }
+
+    fn request_world_selection(&mut self, row_idx: usize, activate: bool) {
+        let (world_dir, track) = match (self.world_dir(), self.track()) {
+            (Some(world_dir), Some(track)) => (world_dir.to_owned(), track.to_string()),
+            _ => return,
+        };
+        let row = &self.rows[row_idx];
+        self.open_request = Some(OpenRequest {
+            path: world_dir,
+            select: Some(WorldObjectRef {
+                track,
+                block_index: row.block_index,
+                object_index: row.object_index,
+            }),
+            activate,
+        });
+    }
+
+    fn export(&self, path: &Utf8Path) -> anyhow::Result<()> {
+        let rows = self
+            .visible_rows
+            .iter()
+            .map(|&idx| &self.rows[idx])
+            .collect::<Vec<_>>();
+
+        let is_json = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("json"))
+            .unwrap_or(false);
+        if is_json {
+            std::fs::write(path, serde_json::to_vec_pretty(&rows)?)?;
+            return Ok(());
+        }
+
+        let mut csv = String::from(
+            "block_index,object_index,model_index,model_name,x,y,z,rotation_x,rotation_y,rotation_z,scale,extra_index\n",
+        );
+        for row in rows {
+            csv.push_str(&format!(
+                "{},{},{},\"{}\",{},{},{},{},{},{},{},{}\n",
+                row.block_index,
+                row.object_index,
+                row.model_index,
+                row.model_name.replace('"', "\"\""),
+                row.position[0],
+                row.position[1],
+                row.position[2],
+                row.rotation[0],
+                row.rotation[1],
+                row.rotation[2],
+                row.scale,
+                row.extra_index
+            ));
+        }
+        std::fs::write(path, csv)?;
+        Ok(())
+    }
+}
+
+/// Reads the objects of `path`, with the block count and model names from the world directory
+/// around it, which may be inside an archive as well.
+fn load_rows(files: &FileLister, path: &Utf8Path, data: Vec<u8>) -> anyhow::Result<Vec<LoiRow>> {
+    let world_dir = match path.parent().and_then(Utf8Path::parent) {
+        Some(world_dir) => world_dir,
+        None => anyhow::bail!("{} is not inside a world directory", path),
+    };
+
+    let lf = world_data::read_lf(files, world_dir)?;
+    let loi = Loi::read(&mut Cursor::new(data), lf.block_count as _)?;
+
+    // model names are only for display, a missing model table is not fatal
+    let model_names = files
+        .read_file(&world_dir.join("modeltable0.LOF"))
+        .and_then(|data| Lof::read_without_data(&mut Cursor::new(data)))
+        .map(|lof| {
+            lof.models
+                .into_iter()
+                .map(|model| (model.index, model.file_name))
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+
+    let mut rows = Vec::new();
+    for block in loi.blocks.iter() {
+        for (object_index, object) in block.objects.iter().enumerate() {
+            let position = world_data::object_position(object);
+            let (x, y, z) = world_data::object_rotation(object).to_euler(glam::EulerRot::XYZ);
+            rows.push(LoiRow {
+                block_index: block.block_index,
+                object_index,
+                model_index: object.model_table_index,
+                model_name: model_names
+                    .get(&object.model_table_index)
+                    .cloned()
+                    .unwrap_or_default(),
+                position: position.to_array(),
+                rotation: [x.to_degrees(), y.to_degrees(), z.to_degrees()],
+                scale: object.scale,
+                extra_index: object.object_extra_index,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+const COLUMNS: [(&str, Option<SortColumn>, f32); 11] = [
+    ("Block", Some(SortColumn::Block), 50.0),
+    ("#", None, 36.0),
+    ("Model", Some(SortColumn::Model), 50.0),
+    ("Name", Some(SortColumn::Name), 180.0),
+    ("X", Some(SortColumn::X), 70.0),
+    ("Y", Some(SortColumn::Y), 70.0),
+    ("Z", Some(SortColumn::Z), 70.0),
+    ("Rotation", None, 150.0),
+    ("Scale", Some(SortColumn::Scale), 50.0),
+    ("Extra", Some(SortColumn::Extra), 50.0),
+    ("", None, 90.0),
+];
+
+impl ProjectFileDialog for LoiFileDialog {
+    fn title(&self) -> String {
+        match self.track() {
+            Some(track) => format!("{} ({})", self.path.file_name().unwrap(), track),
+            None => self.path.file_name().unwrap().into(),
+        }
+    }
+
+    fn take_open_request(&mut self) -> Option<OpenRequest> {
+        self.open_request.take()
+    }
+
+    fn show(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        let mut filter_changed = false;
+        let mut clicked_row = None;
+        let mut show_in_world = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            filter_changed = ui
+                .add(
+                    egui::TextEdit::singleline(&mut self.filter_text)
+                        .hint_text("model:42 block:17 extra:yes name"),
+                )
+                .changed();
+            ui.label(format!(
+                "{} of {} objects",
+                self.visible_rows.len(),
+                self.rows.len()
+            ));
+            if ui.button("Export…").clicked() {
+                if let Some(path) = prompt_save_table_file() {
+                    self.status = Some(
+                        self.export(&path)
+                            .map(|_| {
+                                format!("Exported {} rows to {}", self.visible_rows.len(), path)
+                            })
+                            .map_err(|err| format!("Failed to export: {}", err)),
+                    );
+                }
+            }
+        });
+        match &self.status {
+            Some(Ok(status)) => {
+                ui.label(egui::RichText::new(status).color(egui::Color32::GREEN));
+            }
+            Some(Err(status)) => {
+                ui.label(egui::RichText::new(status).color(egui::Color32::RED));
+            }
+            None => {}
+        }
+        ui.separator();
+
+        let mut sort_changed = false;
+        ui.horizontal(|ui| {
+            for (label, sort_column, width) in COLUMNS {
+                match sort_column {
+                    Some(sort_column) => {
+                        let label = match self.sort {
+                            (column, true) if column == sort_column => format!("{} ⏶", label),
+                            (column, false) if column == sort_column => format!("{} ⏷", label),
+                            _ => label.to_string(),
+                        };
+                        if ui
+                            .add_sized([width, 18.0], egui::Button::new(label).frame(false))
+                            .clicked()
+                        {
+                            self.sort = match self.sort {
+                                (column, ascending) if column == sort_column => {
+                                    (column, !ascending)
+                                }
+                                _ => (sort_column, true),
+                            };
+                            sort_changed = true;
+                        }
+                    }
+                    None => {
+                        ui.add_sized([width, 18.0], egui::Label::new(label));
+                    }
+                }
+            }
+        });
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body) + 4.0;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show_rows(ui, row_height, self.visible_rows.len(), |ui, range| {
+                for &row_idx in &self.visible_rows[range] {
+                    let row = &self.rows[row_idx];
+                    let selected = self.selected_row == Some(row_idx);
+                    let cells = [
+                        row.block_index.to_string(),
+                        row.object_index.to_string(),
+                        row.model_index.to_string(),
+                        row.model_name.clone(),
+                        format!("{:.1}", row.position[0]),
+                        format!("{:.1}", row.position[1]),
+                        format!("{:.1}", row.position[2]),
+                        format!(
+                            "{:.0}° {:.0}° {:.0}°",
+                            row.rotation[0], row.rotation[1], row.rotation[2]
+                        ),
+                        format!("{:.2}", row.scale),
+                        if row.extra_index >= 0 {
+                            row.extra_index.to_string()
+                        } else {
+                            "-".into()
+                        },
+                    ];
+                    ui.horizontal(|ui| {
+                        for (cell, (_, _, width)) in cells.into_iter().zip(COLUMNS) {
+                            if ui
+                                .add_sized(
+                                    [width, row_height],
+                                    egui::SelectableLabel::new(selected, cell),
+                                )
+                                .clicked()
+                            {
+                                clicked_row = Some(row_idx);
+                            }
+                        }
+                        if ui.small_button("Show in World").clicked() {
+                            show_in_world = Some(row_idx);
+                        }
+                    });
+                }
+            });
+
+        if filter_changed || sort_changed {
+            self.update_visible_rows();
+        }
+        if let Some(row_idx) = clicked_row {
+            self.selected_row = Some(row_idx);
+            self.request_world_selection(row_idx, false);
+        }
+        if let Some(row_idx) = show_in_world {
+            self.selected_row = Some(row_idx);
+            self.request_world_selection(row_idx, true);
+        }
+    }
+}
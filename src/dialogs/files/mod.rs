@@ -1,33 +1,124 @@
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use eframe::egui;
 
-use crate::project::ProjectFilesEntry;
+use crate::{
+    project::{Project, ProjectFilesEntry},
+    widgets::nif::CameraState,
+    world_data::WorldObjectRef,
+};
 
 pub mod lbf;
 pub mod levelmodifier;
 pub mod lf;
+pub mod lof;
+pub mod loi;
 pub mod nif;
 pub mod world;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Nif,
+    Lf,
+    Lbf,
+    Lif,
+    Loi,
+    Lof,
+    Levelmodifier,
+    Texture,
+    Archive,
+    Other,
+}
+
+impl FileKind {
+    pub fn from_path(path: &Utf8Path) -> Self {
+        let file_name = path.file_stem().map(str::to_lowercase);
+        let extension = path.extension().map(str::to_lowercase);
+
+        match (file_name.as_deref(), extension.as_deref()) {
+            (Some("levelmodifier") | Some("oldlevelmodifier"), Some("dat")) => {
+                FileKind::Levelmodifier
+            }
+            (_, Some("nif")) => FileKind::Nif,
+            (_, Some("lf")) => FileKind::Lf,
+            (_, Some("lbf")) => FileKind::Lbf,
+            (_, Some("lif")) => FileKind::Lif,
+            (_, Some("loi")) => FileKind::Loi,
+            (_, Some("lof")) => FileKind::Lof,
+            (_, Some("dds") | Some("tga") | Some("bmp") | Some("png") | Some("jpg")) => {
+                FileKind::Texture
+            }
+            (_, Some("agt")) => FileKind::Archive,
+            (_, _) => FileKind::Other,
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            FileKind::Nif => "🏠",
+            FileKind::Lf => "🗻",
+            FileKind::Lbf => "🏢",
+            FileKind::Lif => "🗺",
+            FileKind::Loi => "📍",
+            FileKind::Lof => "📚",
+            FileKind::Levelmodifier => "📈",
+            FileKind::Texture => "🖼",
+            FileKind::Archive => "📦",
+            FileKind::Other => "📄",
+        }
+    }
+
+    /// Whether there is a viewer for this kind of file.
+    pub fn is_supported(&self) -> bool {
+        matches!(
+            self,
+            FileKind::Nif
+                | FileKind::Lf
+                | FileKind::Lbf
+                | FileKind::Loi
+                | FileKind::Lof
+                | FileKind::Levelmodifier
+        )
+    }
+}
+
+/// Creates a viewer for `path` from its already loaded contents,
+/// which may come from a loose file or from an entry inside a packed archive.
+/// Files that need their siblings read them through `project`.
 pub fn create_dialog_for_file(
+    project: &Project,
     path: &Utf8PathBuf,
+    data: Vec<u8>,
     frame: &mut eframe::Frame,
-) -> Box<dyn ProjectFileDialog> {
-    let file_name = path.file_stem().map(str::to_lowercase);
-    let extension = path.extension().map(str::to_lowercase);
-
-    match (file_name.as_deref(), extension.as_deref()) {
-        (Some("levelmodifier") | Some("oldlevelmodifier"), Some("dat")) => Box::new(
-            levelmodifier::LevelmodifierFileDialog::create(path.clone(), frame),
-        ),
-        (_, Some("nif")) => Box::new(nif::NifFileDialog::create(path.clone(), frame)),
-        (_, Some("lf")) => Box::new(lf::LfFileDialog::create(path.clone(), frame)),
-        (_, Some("lbf")) => Box::new(lbf::LbfFileDialog::create(path.clone(), frame)),
-        (None, _) => panic!("no filename ({:?}, {:?})", file_name, extension),
-        (_, _) => Box::new(PlaceholderFileDialog::create(path.clone(), frame)),
+) -> anyhow::Result<Box<dyn ProjectFileDialog>> {
+    if path.file_name().is_none() {
+        anyhow::bail!("no filename ({:?})", path);
     }
+
+    let path = path.clone();
+    Ok(match FileKind::from_path(&path) {
+        FileKind::Levelmodifier => Box::new(levelmodifier::LevelmodifierFileDialog::create(
+            path, data, frame,
+        )?),
+        FileKind::Nif => Box::new(nif::NifFileDialog::create(path, data, frame)?),
+        FileKind::Lf => Box::new(lf::LfFileDialog::create(path, data, frame)?),
+        FileKind::Lbf => Box::new(lbf::LbfFileDialog::create(path, data, frame)?),
+        FileKind::Lof => Box::new(lof::LofFileDialog::create(project, path, data, frame)?),
+        FileKind::Loi => Box::new(loi::LoiFileDialog::create(project, path, data, frame)?),
+        _ => Box::new(PlaceholderFileDialog::create(path)),
+    })
+}
+
+/// Reads `path` through the project (loose or archived) and creates a viewer for it.
+pub fn open_file_dialog(
+    project: &Project,
+    path: &Utf8PathBuf,
+    frame: &mut eframe::Frame,
+) -> anyhow::Result<Box<dyn ProjectFileDialog>> {
+    let data = project.read_file(path)?;
+    create_dialog_for_file(project, path, data, frame)
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum DirDialogKind {
     World,
 }
@@ -41,13 +132,19 @@ impl std::fmt::Display for DirDialogKind {
 }
 
 impl DirDialogKind {
+    /// Creates the view for `path`, which may be a directory inside an archive.
     pub fn create_dialog(
         &self,
+        project: &Project,
         path: &Utf8PathBuf,
         frame: &mut eframe::Frame,
-    ) -> Box<dyn ProjectFileDialog> {
+    ) -> anyhow::Result<Box<dyn ProjectFileDialog>> {
         match self {
-            DirDialogKind::World => Box::new(world::WorldDirDialog::create(path.clone(), frame)),
+            DirDialogKind::World => Ok(Box::new(world::WorldDirDialog::create(
+                project,
+                path.clone(),
+                frame,
+            )?)),
         }
     }
 }
@@ -57,7 +154,7 @@ pub fn get_dir_dialog(_path: &Utf8PathBuf, entries: &[ProjectFilesEntry]) -> Opt
 
     fn has_file(name: &str, entries: &[ProjectFilesEntry]) -> bool {
         entries.iter().any(|e| match e {
-            ProjectFilesEntry::File(f) => f.file_name().unwrap() == name,
+            ProjectFilesEntry::File((f, _)) => f.file_name().unwrap() == name,
             _ => false,
         })
     }
@@ -70,21 +167,59 @@ pub fn get_dir_dialog(_path: &Utf8PathBuf, entries: &[ProjectFilesEntry]) -> Opt
 }
 
 pub trait ProjectFileDialog: std::fmt::Debug {
-    fn create(path: Utf8PathBuf, frame: &mut eframe::Frame) -> Self
-    where
-        Self: Sized;
     fn title(&self) -> String;
     fn show(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, frame: &mut eframe::Frame);
+
+    /// Camera placement to carry over when the dialog is re-created after a reload.
+    fn camera_state(&mut self) -> Option<CameraState> {
+        None
+    }
+
+    fn set_camera_state(&mut self, _state: CameraState) {}
+
+    /// Whether the dialog holds edits that are not saved yet.
+    fn is_modified(&self) -> bool {
+        false
+    }
+
+    /// A file or directory the dialog asked to open in its own tab, such as an embedded NIF.
+    fn take_open_request(&mut self) -> Option<OpenRequest> {
+        None
+    }
+
+    /// Selects and focuses an object placed by one of the world's tracks.
+    fn select_world_object(&mut self, _object: &WorldObjectRef, _frame: &mut eframe::Frame) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenRequest {
+    pub path: Utf8PathBuf,
+    /// Object to select once the world view is open.
+    pub select: Option<WorldObjectRef>,
+    /// Switch to the tab. Without this the request only applies to a tab that is already open.
+    pub activate: bool,
+}
+
+impl OpenRequest {
+    pub fn new(path: Utf8PathBuf) -> Self {
+        Self {
+            path,
+            select: None,
+            activate: true,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct PlaceholderFileDialog(Utf8PathBuf);
 
-impl ProjectFileDialog for PlaceholderFileDialog {
-    fn create(path: Utf8PathBuf, _frame: &mut eframe::Frame) -> Self {
+impl PlaceholderFileDialog {
+    pub fn create(path: Utf8PathBuf) -> Self {
         Self(path)
     }
+}
 
+impl ProjectFileDialog for PlaceholderFileDialog {
     fn title(&self) -> String {
         self.0.file_name().unwrap().into()
     }
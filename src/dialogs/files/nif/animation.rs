@@ -0,0 +1,719 @@
+//! Playback of NiKeyframeController and NiTransformController transform animations, and of
+//! controller sequences.
+
+use std::ops::{Add, Mul, Sub};
+
+use eframe::egui;
+use nif::{
+    blocks::{
+        Block, Key, KeyGroup, KeyType, NiAvObject, NiControllerSequence, NiKeyframeData,
+        NiTimeController, Tbc,
+    },
+    common::BlockRef,
+    Nif,
+};
+
+use crate::widgets::nif::Pose;
+
+use super::inspector::block_label;
+
+/// How timeline time maps to key time, from a controller or a sequence.
+#[derive(Debug, Clone, Copy)]
+struct Timing {
+    frequency: f32,
+    phase: f32,
+    start_time: f32,
+    stop_time: f32,
+    /// Loop, reverse or clamp, numbered as in the controller flags.
+    cycle_type: u32,
+}
+
+impl Timing {
+    fn from_controller(controller: &NiTimeController) -> Self {
+        Self {
+            frequency: controller.frequency,
+            phase: controller.phase,
+            start_time: controller.start_time,
+            stop_time: controller.stop_time,
+            cycle_type: u32::from((controller.flags >> 1) & 0b11),
+        }
+    }
+
+    fn from_sequence(sequence: &NiControllerSequence) -> Self {
+        Self {
+            frequency: sequence.frequency,
+            phase: 0.0,
+            start_time: sequence.start_time,
+            stop_time: sequence.stop_time,
+            cycle_type: sequence.cycle_type,
+        }
+    }
+
+    /// Maps timeline time to key time, following the frequency, phase and cycle type.
+    fn key_time(&self, time: f32) -> f32 {
+        let time = time * self.frequency + self.phase;
+        let (start, stop) = (self.start_time, self.stop_time);
+        let length = stop - start;
+        if length <= 0.0 {
+            return start;
+        }
+        match self.cycle_type {
+            1 => {
+                let time = (time - start).rem_euclid(2.0 * length);
+                start
+                    + if time > length {
+                        2.0 * length - time
+                    } else {
+                        time
+                    }
+            }
+            2 => time.clamp(start, stop),
+            _ => start + (time - start).rem_euclid(length),
+        }
+    }
+}
+
+/// Keyframes animating the transform of one object.
+#[derive(Debug, Clone)]
+struct AnimationTrack {
+    /// Block index of the animated object.
+    target: usize,
+    data: BlockRef,
+    timing: Timing,
+    label: String,
+    enabled: bool,
+}
+
+/// Tracks played together, the objects' own controllers or one controller sequence.
+#[derive(Debug)]
+struct TrackGroup {
+    name: String,
+    tracks: Vec<AnimationTrack>,
+}
+
+#[derive(Debug, Default)]
+pub struct AnimationPlayer {
+    groups: Vec<TrackGroup>,
+    selected_group: usize,
+    /// Seconds since the start of the timeline.
+    time: f32,
+    playing: bool,
+    /// The stored pose is shown until playback starts or the timeline is moved.
+    active: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(nif: &Nif) -> Self {
+        let blocks = &nif.blocks;
+        let mut groups = Vec::new();
+
+        let object_tracks = object_controller_tracks(blocks);
+        if !object_tracks.is_empty() {
+            groups.push(TrackGroup {
+                name: "Object controllers".into(),
+                tracks: object_tracks,
+            });
+        }
+        for (idx, block) in blocks.iter().enumerate() {
+            if let Block::NiControllerSequence(sequence) = block {
+                let tracks = sequence_tracks(blocks, sequence);
+                if !tracks.is_empty() {
+                    groups.push(TrackGroup {
+                        name: block_label(block, BlockRef(idx as i32)),
+                        tracks,
+                    });
+                }
+            }
+        }
+
+        Self {
+            groups,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    fn tracks(&self) -> &[AnimationTrack] {
+        self.groups
+            .get(self.selected_group)
+            .map(|group| group.tracks.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Length of the timeline, up to the last track's stop time.
+    fn duration(&self) -> f32 {
+        self.tracks()
+            .iter()
+            .map(|track| track.timing.stop_time)
+            .fold(0.0, f32::max)
+    }
+
+    /// Transforms of the objects the enabled tracks of the selected group move at the
+    /// current time, empty before playback started.
+    pub fn pose(&self, nif: &Nif) -> Pose {
+        let mut pose = Pose::new();
+        if !self.active {
+            return pose;
+        }
+        for track in self.tracks().iter().filter(|track| track.enabled) {
+            let data = match keyframe_data(&nif.blocks, track.data) {
+                Some(data) => data,
+                None => continue,
+            };
+            let posed = pose.entry(track.target).or_default();
+
+            // channels without keys keep the object's own transform
+            let time = track.timing.key_time(self.time);
+            if let Some(translation) =
+                sample(&data.translations, time, |v| glam::vec3(v.x, v.y, v.z))
+            {
+                posed.translation = Some(translation);
+            }
+            if let Some(rotation) = sample_rotation(data, time) {
+                posed.rotation = Some(rotation);
+            }
+            if let Some(scale) = sample(&data.scales, time, |v| *v) {
+                posed.scale = Some(scale);
+            }
+        }
+        pose
+    }
+
+    /// Sequence picker, track list and playback controls. Returns true when the pose changed.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        ui.label("Animation");
+        if self.groups.len() > 1 {
+            let selected_name = self
+                .groups
+                .get(self.selected_group)
+                .map(|group| group.name.clone())
+                .unwrap_or_default();
+            egui::ComboBox::from_id_source("nif_animation_group")
+                .selected_text(selected_name)
+                .show_ui(ui, |ui| {
+                    for (idx, group) in self.groups.iter().enumerate() {
+                        changed |= ui
+                            .selectable_value(&mut self.selected_group, idx, &group.name)
+                            .changed();
+                    }
+                });
+        }
+        if let Some(group) = self.groups.get_mut(self.selected_group) {
+            for track in group.tracks.iter_mut() {
+                changed |= ui.checkbox(&mut track.enabled, &track.label).changed();
+            }
+        }
+
+        let duration = self.duration();
+        ui.horizontal(|ui| {
+            if ui
+                .button(if self.playing { "Pause" } else { "Play" })
+                .clicked()
+            {
+                self.playing = !self.playing;
+                self.active = true;
+                changed = true;
+            }
+            if ui
+                .button("Stop")
+                .on_hover_text("Back to the stored pose")
+                .clicked()
+            {
+                self.playing = false;
+                self.active = false;
+                self.time = 0.0;
+                changed = true;
+            }
+        });
+        if ui
+            .add(egui::Slider::new(&mut self.time, 0.0..=duration).suffix(" s"))
+            .changed()
+        {
+            self.active = true;
+            changed = true;
+        }
+
+        if self.playing {
+            self.time += ui.input().stable_dt;
+            if self.time > duration {
+                self.time = if duration > 0.0 {
+                    self.time % duration
+                } else {
+                    0.0
+                };
+            }
+            changed = true;
+            ui.ctx().request_repaint();
+        }
+
+        changed
+    }
+}
+
+/// Tracks of the transform controllers attached to each object.
+fn object_controller_tracks(blocks: &[Block]) -> Vec<AnimationTrack> {
+    let mut tracks = Vec::new();
+    for (idx, block) in blocks.iter().enumerate() {
+        let mut controller_ref = match av_object(block) {
+            Some(object) => object.base.controller_ref,
+            None => continue,
+        };
+        // an object's controllers are chained, the bound guards against cycles
+        for _ in 0..blocks.len() {
+            let controller = match controller_ref.get(blocks) {
+                Some(controller) => controller,
+                None => break,
+            };
+            let (time_controller, data) = match controller_parts(blocks, controller) {
+                Some(parts) => parts,
+                None => break,
+            };
+            if keyframe_data(blocks, data).is_some() {
+                tracks.push(AnimationTrack {
+                    target: idx,
+                    data,
+                    timing: Timing::from_controller(time_controller),
+                    label: format!(
+                        "{} on {}",
+                        controller.name(),
+                        block_label(block, BlockRef(idx as i32))
+                    ),
+                    enabled: true,
+                });
+            }
+            controller_ref = time_controller.next_controller_ref;
+        }
+    }
+    tracks
+}
+
+/// Tracks of a sequence's transform interpolators, matched to objects by name.
+fn sequence_tracks(blocks: &[Block], sequence: &NiControllerSequence) -> Vec<AnimationTrack> {
+    let timing = Timing::from_sequence(sequence);
+    sequence
+        .controlled_blocks
+        .iter()
+        .filter_map(|controlled| {
+            let data = match controlled.interpolator_ref.get(blocks)? {
+                Block::NiTransformInterpolator(interpolator) => interpolator.data_ref,
+                _ => return None,
+            };
+            keyframe_data(blocks, data)?;
+            let target = blocks.iter().position(|block| {
+                av_object(block).map(|object| &object.base.name) == Some(&controlled.node_name)
+            })?;
+            Some(AnimationTrack {
+                target,
+                data,
+                timing,
+                label: controlled.node_name.clone(),
+                enabled: true,
+            })
+        })
+        .collect()
+}
+
+fn av_object(block: &Block) -> Option<&NiAvObject> {
+    match block {
+        Block::NiNode(block) => Some(&block.base),
+        Block::NiLODNode(block) => Some(&block.base.base.base),
+        Block::NiTriShape(block) => Some(&block.base),
+        _ => None,
+    }
+}
+
+/// Timing of a transform controller and the ref of its keyframe data.
+fn controller_parts<'a>(
+    blocks: &'a [Block],
+    controller: &'a Block,
+) -> Option<(&'a NiTimeController, BlockRef)> {
+    match controller {
+        Block::NiKeyframeController(controller) => Some((&controller.base, controller.data_ref)),
+        Block::NiTransformController(controller) => {
+            let data = match controller.interpolator_ref.get(blocks) {
+                Some(Block::NiTransformInterpolator(interpolator)) => interpolator.data_ref,
+                _ => BlockRef(-1),
+            };
+            Some((&controller.base, data))
+        }
+        _ => None,
+    }
+}
+
+fn keyframe_data(blocks: &[Block], data: BlockRef) -> Option<&NiKeyframeData> {
+    match data.get(blocks) {
+        Some(Block::NiKeyframeData(data)) => Some(data),
+        Some(Block::NiTransformData(data)) => Some(&data.base),
+        _ => None,
+    }
+}
+
+trait Interpolate: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self> {}
+
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>> Interpolate for T {}
+
+/// Position of `time` among the keys, as (index of the key before it, fraction towards the
+/// next key). None before the first or after the last key.
+fn key_span(count: usize, key_time: impl Fn(usize) -> f32, time: f32) -> Option<(usize, f32)> {
+    if count == 0 || time <= key_time(0) || time >= key_time(count - 1) {
+        return None;
+    }
+    let idx = (0..count).rev().find(|idx| key_time(*idx) <= time)?;
+    let (start, end) = (key_time(idx), key_time(idx + 1));
+    let fraction = if end > start {
+        (time - start) / (end - start)
+    } else {
+        0.0
+    };
+    Some((idx, fraction))
+}
+
+fn sample<T, V: Interpolate>(group: &KeyGroup<T>, time: f32, value: impl Fn(&T) -> V) -> Option<V> {
+    let keys = &group.keys;
+    let (first, last) = (keys.first()?, keys.last()?);
+    let (idx, s) = match key_span(keys.len(), |idx| keys[idx].time, time) {
+        Some(span) => span,
+        None if time <= first.time => return Some(value(&first.value)),
+        None => return Some(value(&last.value)),
+    };
+
+    let (a, b) = (&keys[idx], &keys[idx + 1]);
+    let (pa, pb) = (value(&a.value), value(&b.value));
+    Some(match group.interpolation {
+        KeyType::Constant => pa,
+        KeyType::Quadratic => hermite(pa, pb, value(&a.forward), value(&b.backward), s),
+        KeyType::Tbc => {
+            let (_, out_a) = tbc_tangents(keys, idx, &value);
+            let (in_b, _) = tbc_tangents(keys, idx + 1, &value);
+            hermite(pa, pb, out_a, in_b, s)
+        }
+        _ => pa + (pb - pa) * s,
+    })
+}
+
+fn hermite<V: Interpolate>(p0: V, p1: V, out0: V, in1: V, s: f32) -> V {
+    let (s2, s3) = (s * s, s * s * s);
+    p0 * (2.0 * s3 - 3.0 * s2 + 1.0)
+        + p1 * (-2.0 * s3 + 3.0 * s2)
+        + out0 * (s3 - 2.0 * s2 + s)
+        + in1 * (s3 - s2)
+}
+
+/// Kochanek-Bartels weights of the differences to the previous and next key, for the
+/// incoming and the outgoing tangent.
+fn tbc_weights(tbc: &Tbc) -> ([f32; 2], [f32; 2]) {
+    let (t, b, c) = (tbc.t, tbc.b, tbc.c);
+    let incoming = [
+        (1.0 - t) * (1.0 - c) * (1.0 + b) / 2.0,
+        (1.0 - t) * (1.0 + c) * (1.0 - b) / 2.0,
+    ];
+    let outgoing = [
+        (1.0 - t) * (1.0 + c) * (1.0 + b) / 2.0,
+        (1.0 - t) * (1.0 - c) * (1.0 - b) / 2.0,
+    ];
+    (incoming, outgoing)
+}
+
+/// Incoming and outgoing tangents of a TBC key, end keys repeat themselves as neighbours.
+fn tbc_tangents<T, V: Interpolate>(
+    keys: &[Key<T>],
+    idx: usize,
+    value: &impl Fn(&T) -> V,
+) -> (V, V) {
+    let p = value(&keys[idx].value);
+    let prev = idx
+        .checked_sub(1)
+        .map(|prev| value(&keys[prev].value))
+        .unwrap_or(p);
+    let next = keys.get(idx + 1).map(|key| value(&key.value)).unwrap_or(p);
+    let (d_in, d_out) = (p - prev, next - p);
+    let (incoming, outgoing) = tbc_weights(&keys[idx].tbc);
+    (
+        d_in * incoming[0] + d_out * incoming[1],
+        d_in * outgoing[0] + d_out * outgoing[1],
+    )
+}
+
+fn sample_rotation(data: &NiKeyframeData, time: f32) -> Option<glam::Quat> {
+    if data.rotation_type == KeyType::XyzRotation {
+        let angles = data
+            .xyz_rotations
+            .iter()
+            .map(|group| sample(group, time, |angle| *angle))
+            .collect::<Vec<_>>();
+        if angles.iter().all(Option::is_none) {
+            return None;
+        }
+        let angle = |axis: usize| angles[axis].unwrap_or(0.0);
+        return Some(
+            glam::Quat::from_rotation_z(angle(2))
+                * glam::Quat::from_rotation_y(angle(1))
+                * glam::Quat::from_rotation_x(angle(0)),
+        );
+    }
+
+    let keys = &data.quaternion_keys;
+    let quat = |idx: usize| {
+        let value = &keys[idx].value;
+        glam::Quat::from_xyzw(value.x, value.y, value.z, value.w).normalize()
+    };
+    let (idx, s) = match key_span(keys.len(), |idx| keys[idx].time, time) {
+        Some(span) => span,
+        None if keys.is_empty() => return None,
+        None if time <= keys[0].time => return Some(quat(0)),
+        None => return Some(quat(keys.len() - 1)),
+    };
+
+    let qa = quat(idx);
+    let qb = same_hemisphere(qa, quat(idx + 1));
+    Some(match data.rotation_type {
+        KeyType::Constant => qa,
+        // quaternion keys carry no tangents, both go through squad with derived control points
+        KeyType::Quadratic | KeyType::Tbc => {
+            let with_tbc = data.rotation_type == KeyType::Tbc;
+            let (_, control_a) = squad_controls(keys.len(), idx, &quat, |idx| {
+                with_tbc.then(|| keys[idx].tbc.clone())
+            });
+            let (control_b, _) = squad_controls(keys.len(), idx + 1, &quat, |idx| {
+                with_tbc.then(|| keys[idx].tbc.clone())
+            });
+            let control_b = same_hemisphere(qb, control_b);
+            qa.slerp(qb, s)
+                .slerp(control_a.slerp(control_b, s), 2.0 * s * (1.0 - s))
+        }
+        _ => qa.slerp(qb, s),
+    })
+}
+
+fn same_hemisphere(reference: glam::Quat, quat: glam::Quat) -> glam::Quat {
+    if reference.dot(quat) < 0.0 {
+        -quat
+    } else {
+        quat
+    }
+}
+
+/// Incoming and outgoing squad control points of a key, with TBC weights when given.
+fn squad_controls(
+    count: usize,
+    idx: usize,
+    quat: &impl Fn(usize) -> glam::Quat,
+    tbc: impl Fn(usize) -> Option<Tbc>,
+) -> (glam::Quat, glam::Quat) {
+    let q = quat(idx);
+    let prev = same_hemisphere(q, quat(idx.saturating_sub(1)));
+    let next = same_hemisphere(q, quat((idx + 1).min(count - 1)));
+    let d_in = quat_log(prev.inverse() * q);
+    let d_out = quat_log(q.inverse() * next);
+    let (incoming, outgoing) = tbc_weights(&tbc(idx).unwrap_or(Tbc {
+        t: 0.0,
+        b: 0.0,
+        c: 0.0,
+    }));
+    let tangent_in = d_in * incoming[0] + d_out * incoming[1];
+    let tangent_out = d_in * outgoing[0] + d_out * outgoing[1];
+    (
+        q * quat_exp((d_in - tangent_in) * 0.5),
+        q * quat_exp((tangent_out - d_out) * 0.5),
+    )
+}
+
+fn quat_log(quat: glam::Quat) -> glam::Vec3 {
+    let v = glam::vec3(quat.x, quat.y, quat.z);
+    let length = v.length();
+    if length < 1e-6 {
+        glam::Vec3::ZERO
+    } else {
+        v / length * length.atan2(quat.w)
+    }
+}
+
+fn quat_exp(v: glam::Vec3) -> glam::Quat {
+    let angle = v.length();
+    if angle < 1e-6 {
+        glam::Quat::IDENTITY
+    } else {
+        let axis = v / angle * angle.sin();
+        glam::Quat::from_xyzw(axis.x, axis.y, axis.z, angle.cos())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nif::{blocks::QuatKey, common::Quaternion};
+
+    use super::*;
+
+    const NO_TBC: Tbc = Tbc {
+        t: 0.0,
+        b: 0.0,
+        c: 0.0,
+    };
+
+    fn group<T: Clone>(interpolation: KeyType, keys: &[(f32, T)]) -> KeyGroup<T> {
+        KeyGroup {
+            interpolation,
+            keys: keys
+                .iter()
+                .map(|(time, value)| Key {
+                    time: *time,
+                    value: value.clone(),
+                    forward: value.clone(),
+                    backward: value.clone(),
+                    tbc: NO_TBC,
+                })
+                .collect(),
+        }
+    }
+
+    fn timing(cycle_type: u32) -> Timing {
+        Timing {
+            frequency: 1.0,
+            phase: 0.0,
+            start_time: 1.0,
+            stop_time: 3.0,
+            cycle_type,
+        }
+    }
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-5, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn key_time_wraps_reverses_and_clamps() {
+        assert_near(timing(0).key_time(3.5), 1.5);
+        assert_near(timing(0).key_time(0.5), 2.5);
+        assert_near(timing(1).key_time(3.5), 2.5);
+        assert_near(timing(1).key_time(5.5), 1.5);
+        assert_near(timing(2).key_time(7.0), 3.0);
+        assert_near(timing(2).key_time(-1.0), 1.0);
+    }
+
+    #[test]
+    fn key_time_follows_frequency_and_phase() {
+        let timing = Timing {
+            frequency: 2.0,
+            phase: 0.5,
+            ..timing(2)
+        };
+        assert_near(timing.key_time(1.0), 2.5);
+    }
+
+    #[test]
+    fn keys_hold_their_value_and_ends_clamp() {
+        let keys = group(KeyType::Linear, &[(0.0, 1.0), (1.0, 3.0), (2.0, 2.0)]);
+        assert_eq!(sample(&keys, 1.0, |v| *v), Some(3.0));
+        assert_eq!(sample(&keys, -1.0, |v| *v), Some(1.0));
+        assert_eq!(sample(&keys, 5.0, |v| *v), Some(2.0));
+        assert_eq!(
+            sample(&group::<f32>(KeyType::Linear, &[]), 0.0, |v| *v),
+            None
+        );
+    }
+
+    #[test]
+    fn linear_keys_meet_halfway() {
+        let keys = group(KeyType::Linear, &[(0.0, 1.0), (2.0, 3.0)]);
+        assert_near(sample(&keys, 1.0, |v| *v).unwrap(), 2.0);
+        let keys = group(KeyType::Constant, &[(0.0, 1.0), (2.0, 3.0)]);
+        assert_near(sample(&keys, 1.0, |v| *v).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn quadratic_keys_follow_their_tangents() {
+        let mut keys = group(KeyType::Quadratic, &[(0.0, 0.0), (1.0, 1.0)]);
+        keys.keys[0].forward = 2.0;
+        keys.keys[1].backward = 0.0;
+        // p0 / 2 + p1 / 2 + out0 / 8 - in1 / 8 at the midpoint
+        assert_near(sample(&keys, 0.5, |v| *v).unwrap(), 0.75);
+    }
+
+    #[test]
+    fn tbc_without_tension_continuity_or_bias_is_catmull_rom() {
+        let points = [0.0, 1.0, 4.0, 9.0];
+        let keys = group(
+            KeyType::Tbc,
+            &points
+                .iter()
+                .enumerate()
+                .map(|(idx, p)| (idx as f32, *p))
+                .collect::<Vec<_>>(),
+        );
+        let [p0, p1, p2, p3] = points;
+        let s = 0.5_f32;
+        let catmull_rom = 0.5
+            * (2.0 * p1
+                + (p2 - p0) * s
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * s * s
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * s * s * s);
+        assert_near(sample(&keys, 1.5, |v| *v).unwrap(), catmull_rom);
+    }
+
+    fn keyframe_data(rotation_type: KeyType, quaternion_keys: Vec<QuatKey>) -> NiKeyframeData {
+        NiKeyframeData {
+            rotation_type,
+            quaternion_keys,
+            xyz_rotations: [
+                group(KeyType::Linear, &[]),
+                group(KeyType::Linear, &[]),
+                group(KeyType::Linear, &[]),
+            ],
+            translations: group(KeyType::Linear, &[]),
+            scales: group(KeyType::Linear, &[]),
+        }
+    }
+
+    fn assert_same_rotation(quat: glam::Quat, expected: glam::Quat) {
+        assert!(
+            quat.dot(expected).abs() > 1.0 - 1e-5,
+            "{} != {}",
+            quat,
+            expected
+        );
+    }
+
+    #[test]
+    fn squad_between_evenly_turning_keys_turns_evenly() {
+        let quaternion_keys = (0..4)
+            .map(|idx| {
+                let quat = glam::Quat::from_rotation_z(idx as f32 * std::f32::consts::FRAC_PI_2);
+                QuatKey {
+                    time: idx as f32,
+                    value: Quaternion {
+                        w: quat.w,
+                        x: quat.x,
+                        y: quat.y,
+                        z: quat.z,
+                    },
+                    tbc: NO_TBC,
+                }
+            })
+            .collect();
+        let data = keyframe_data(KeyType::Quadratic, quaternion_keys);
+        assert_same_rotation(
+            sample_rotation(&data, 1.0).unwrap(),
+            glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        );
+        assert_same_rotation(
+            sample_rotation(&data, 1.5).unwrap(),
+            glam::Quat::from_rotation_z(3.0 * std::f32::consts::FRAC_PI_4),
+        );
+    }
+
+    #[test]
+    fn xyz_rotation_applies_x_then_y_then_z() {
+        let mut data = keyframe_data(KeyType::XyzRotation, Vec::new());
+        data.xyz_rotations[0] = group(KeyType::Linear, &[(0.0, 0.0), (2.0, 1.0)]);
+        data.xyz_rotations[2] = group(KeyType::Linear, &[(0.0, 0.0), (2.0, 0.4)]);
+        assert_same_rotation(
+            sample_rotation(&data, 1.0).unwrap(),
+            glam::Quat::from_rotation_z(0.2) * glam::Quat::from_rotation_x(0.5),
+        );
+    }
+}
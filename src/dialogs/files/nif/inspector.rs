@@ -0,0 +1,370 @@
+use eframe::egui;
+use nif::{
+    blocks::{Block, NiAvObject, NiKeyframeData, NiLight, NiObjectNET, NiTimeController},
+    common::{BlockRef, Color3, Matrix33, Vector3},
+};
+
+#[derive(Default)]
+pub struct InspectorResponse {
+    /// A field was edited and the block data should be re-rendered.
+    pub changed: bool,
+    /// A block reference was clicked.
+    pub select: Option<BlockRef>,
+}
+
+/// Tree label for a block, including its object name when it has one.
+pub fn block_label(block: &Block, block_ref: BlockRef) -> String {
+    let object_name = match block {
+        Block::NiNode(block) => Some(&block.base.base.name),
+        Block::NiLODNode(block) => Some(&block.base.base.base.base.name),
+        Block::NiTriShape(block) => Some(&block.base.base.name),
+        Block::NiTextureEffect(block) => Some(&block.base.base.name),
+        Block::NiDirectionalLight(block) => Some(&block.base.base.base.name),
+        Block::NiAmbientLight(block) => Some(&block.base.base.base.name),
+        Block::NiPointLight(block) => Some(&block.base.base.base.name),
+        Block::NiControllerSequence(block) => Some(&block.name),
+        _ => None,
+    };
+    match object_name {
+        Some(object_name) if !object_name.is_empty() => {
+            format!("{} \"{}\" (id {})", block.name(), object_name, block_ref.0)
+        }
+        _ => format!("{} (id {})", block.name(), block_ref.0),
+    }
+}
+
+/// Shows every known field of the block at `block_ref` in a property grid, read-only unless
+/// `editable`.
+pub fn show_block_inspector(
+    ui: &mut egui::Ui,
+    blocks: &mut [Block],
+    block_ref: BlockRef,
+    editable: bool,
+) -> InspectorResponse {
+    let block_names = blocks
+        .iter()
+        .map(|block| block.name().to_string())
+        .collect::<Vec<_>>();
+
+    let block = match usize::try_from(block_ref.0)
+        .ok()
+        .and_then(|idx| blocks.get_mut(idx))
+    {
+        Some(block) => block,
+        None => {
+            ui.label("No block selected");
+            return Default::default();
+        }
+    };
+
+    ui.heading(block_label(block, block_ref));
+    if !editable {
+        ui.label(egui::RichText::new("Read-only, this file cannot be saved").weak());
+    }
+
+    let mut grid = PropertyGrid {
+        block_names: &block_names,
+        editable,
+        response: Default::default(),
+    };
+    egui::Grid::new("nif_block_properties")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| match block {
+            Block::NiNode(block) => {
+                grid.av_object(ui, &mut block.base);
+                grid.block_refs(ui, "Children", &block.child_refs);
+                grid.block_refs(ui, "Effects", &block.effect_refs);
+            }
+            Block::NiTriShape(block) => {
+                grid.av_object(ui, &mut block.base);
+                grid.block_ref(ui, "Data", block.data_ref);
+                grid.block_ref(ui, "Skin instance", block.skin_instance_ref);
+            }
+            Block::NiTextureEffect(block) => {
+                grid.av_object(ui, &mut block.base);
+                grid.block_ref(ui, "Source texture", block.source_texture_ref);
+            }
+            Block::NiTriShapeData(block) => {
+                let data = &mut block.base;
+                grid.readonly(ui, "Vertices", data.vertices.len());
+                grid.readonly(ui, "Normals", data.normals.len());
+                grid.readonly(ui, "Vertex colors", data.vertex_colors.len());
+                grid.readonly(ui, "UV sets", data.uv_sets.len());
+                grid.readonly(ui, "Triangles", block.triangles.len());
+                grid.vector3(ui, "Center", &mut data.center);
+                grid.float(ui, "Radius", &mut data.radius);
+            }
+            Block::NiMaterialProperty(block) => {
+                grid.object_net(ui, &mut block.base);
+                grid.flags(ui, "Flags", &mut block.flags);
+                grid.color3(ui, "Ambient", &mut block.ambient_color);
+                grid.color3(ui, "Diffuse", &mut block.diffuse_color);
+                grid.color3(ui, "Specular", &mut block.specular_color);
+                grid.color3(ui, "Emissive", &mut block.emissive_color);
+                grid.float(ui, "Glossiness", &mut block.glossiness);
+                grid.float(ui, "Alpha", &mut block.alpha);
+            }
+            Block::NiAlphaProperty(block) => {
+                grid.object_net(ui, &mut block.base);
+                grid.flags(ui, "Flags", &mut block.flags);
+                grid.value(ui, "Threshold", &mut block.threshold);
+            }
+            Block::NiTexturingProperty(block) => {
+                grid.object_net(ui, &mut block.base);
+                grid.value(ui, "Apply mode", &mut block.apply_mode);
+                if let Some(base_texture) = &block.base_texture {
+                    grid.block_ref(ui, "Base texture", base_texture.source_ref);
+                }
+            }
+            Block::NiSourceTexture(block) => {
+                grid.object_net(ui, &mut block.base);
+                grid.string(ui, "Texture path", &mut block.file_name);
+            }
+            Block::NiTransformController(block) => {
+                grid.time_controller(ui, &mut block.base);
+                grid.block_ref(ui, "Interpolator", block.interpolator_ref);
+            }
+            Block::NiKeyframeController(block) => {
+                grid.time_controller(ui, &mut block.base);
+                grid.block_ref(ui, "Data", block.data_ref);
+            }
+            Block::NiTransformInterpolator(block) => {
+                grid.vector3(ui, "Translation", &mut block.translation);
+                grid.float(ui, "Scale", &mut block.scale);
+                grid.block_ref(ui, "Data", block.data_ref);
+            }
+            Block::NiTransformData(block) => grid.keyframe_data(ui, &block.base),
+            Block::NiKeyframeData(block) => grid.keyframe_data(ui, block),
+            Block::NiDirectionalLight(block) => grid.light(ui, &mut block.base),
+            Block::NiAmbientLight(block) => grid.light(ui, &mut block.base),
+            Block::NiPointLight(block) => {
+                grid.light(ui, &mut block.base);
+                grid.float(ui, "Constant attenuation", &mut block.constant_attenuation);
+                grid.float(ui, "Linear attenuation", &mut block.linear_attenuation);
+                grid.float(
+                    ui,
+                    "Quadratic attenuation",
+                    &mut block.quadratic_attenuation,
+                );
+            }
+            Block::NiSkinInstance(block) => {
+                grid.block_ref(ui, "Data", block.data_ref);
+                grid.block_ref(ui, "Partition", block.skin_partition_ref);
+                grid.block_ref(ui, "Skeleton root", block.skeleton_root_ref);
+                grid.block_refs(ui, "Bones", &block.bone_refs);
+            }
+            Block::NiSkinData(block) => {
+                grid.readonly(ui, "Bones", block.bone_list.len());
+            }
+            // blocks without a dedicated view still show their parsed fields
+            block => grid.debug(ui, block),
+        });
+
+    grid.response
+}
+
+struct PropertyGrid<'a> {
+    block_names: &'a [String],
+    editable: bool,
+    response: InspectorResponse,
+}
+
+impl<'a> PropertyGrid<'a> {
+    fn object_net(&mut self, ui: &mut egui::Ui, object: &mut NiObjectNET) {
+        self.string(ui, "Name", &mut object.name);
+        self.block_refs(ui, "Extra data", &object.extra_data_refs);
+        self.block_ref(ui, "Controller", object.controller_ref);
+    }
+
+    fn av_object(&mut self, ui: &mut egui::Ui, object: &mut NiAvObject) {
+        self.object_net(ui, &mut object.base);
+        self.flags(ui, "Flags", &mut object.flags);
+        self.vector3(ui, "Translation", &mut object.translation);
+        self.rotation(ui, "Rotation", &mut object.rotation);
+        self.float(ui, "Scale", &mut object.scale);
+        self.block_refs(ui, "Properties", &object.property_refs);
+    }
+
+    fn time_controller(&mut self, ui: &mut egui::Ui, controller: &mut NiTimeController) {
+        self.flags(ui, "Flags", &mut controller.flags);
+        self.float(ui, "Frequency", &mut controller.frequency);
+        self.float(ui, "Phase", &mut controller.phase);
+        self.float(ui, "Start time", &mut controller.start_time);
+        self.float(ui, "Stop time", &mut controller.stop_time);
+        self.block_ref(ui, "Target", controller.target_ref);
+        self.block_ref(ui, "Next controller", controller.next_controller_ref);
+    }
+
+    fn light(&mut self, ui: &mut egui::Ui, light: &mut NiLight) {
+        self.av_object(ui, &mut light.base);
+        self.float(ui, "Dimmer", &mut light.dimmer);
+        self.color3(ui, "Ambient", &mut light.ambient_color);
+        self.color3(ui, "Diffuse", &mut light.diffuse_color);
+        self.color3(ui, "Specular", &mut light.specular_color);
+    }
+
+    fn keyframe_data(&mut self, ui: &mut egui::Ui, data: &NiKeyframeData) {
+        self.readonly(ui, "Rotation keys", data.quaternion_keys.len());
+        self.readonly(ui, "Translation keys", data.translations.keys.len());
+        self.readonly(ui, "Scale keys", data.scales.keys.len());
+    }
+
+    /// Read-only dump of a block's fields.
+    fn debug(&mut self, ui: &mut egui::Ui, block: &Block) {
+        ui.label("Fields");
+        ui.label(egui::RichText::new(format!("{:#?}", block)).monospace());
+        ui.end_row();
+    }
+
+    fn readonly(&mut self, ui: &mut egui::Ui, label: &str, value: impl ToString) {
+        ui.label(label);
+        ui.label(value.to_string());
+        ui.end_row();
+    }
+
+    fn string(&mut self, ui: &mut egui::Ui, label: &str, value: &mut String) {
+        ui.label(label);
+        self.response.changed |= ui
+            .add_enabled(self.editable, egui::TextEdit::singleline(value))
+            .changed();
+        ui.end_row();
+    }
+
+    fn float(&mut self, ui: &mut egui::Ui, label: &str, value: &mut f32) {
+        ui.label(label);
+        self.response.changed |= ui
+            .add_enabled(self.editable, egui::DragValue::new(value).speed(0.01))
+            .changed();
+        ui.end_row();
+    }
+
+    fn value<N: egui::emath::Numeric>(&mut self, ui: &mut egui::Ui, label: &str, value: &mut N) {
+        ui.label(label);
+        self.response.changed |= ui
+            .add_enabled(self.editable, egui::DragValue::new(value))
+            .changed();
+        ui.end_row();
+    }
+
+    fn flags(&mut self, ui: &mut egui::Ui, label: &str, value: &mut u16) {
+        ui.label(label);
+        ui.horizontal(|ui| {
+            self.response.changed |= ui
+                .add_enabled(self.editable, egui::DragValue::new(value))
+                .changed();
+            ui.label(egui::RichText::new(format!("{:#06x}", value)).weak());
+        });
+        ui.end_row();
+    }
+
+    fn vector3(&mut self, ui: &mut egui::Ui, label: &str, value: &mut Vector3) {
+        ui.label(label);
+        ui.horizontal(|ui| {
+            for component in [&mut value.x, &mut value.y, &mut value.z] {
+                self.response.changed |= ui
+                    .add_enabled(self.editable, egui::DragValue::new(component).speed(0.1))
+                    .changed();
+            }
+        });
+        ui.end_row();
+    }
+
+    /// Edits a rotation matrix as XYZ euler angles in degrees.
+    fn rotation(&mut self, ui: &mut egui::Ui, label: &str, value: &mut Matrix33) {
+        let matrix = glam::Mat3::from_cols_array_2d(&[
+            [value.m11, value.m12, value.m13],
+            [value.m21, value.m22, value.m23],
+            [value.m31, value.m32, value.m33],
+        ])
+        .transpose();
+        let (x, y, z) = glam::Quat::from_mat3(&matrix).to_euler(glam::EulerRot::XYZ);
+        let mut angles = [x.to_degrees(), y.to_degrees(), z.to_degrees()];
+
+        ui.label(label);
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            for angle in angles.iter_mut() {
+                changed |= ui
+                    .add_enabled(
+                        self.editable,
+                        egui::DragValue::new(angle).speed(0.5).suffix("°"),
+                    )
+                    .changed();
+            }
+        });
+        ui.end_row();
+
+        if changed {
+            let rotation = glam::Quat::from_euler(
+                glam::EulerRot::XYZ,
+                angles[0].to_radians(),
+                angles[1].to_radians(),
+                angles[2].to_radians(),
+            );
+            let cols = glam::Mat3::from_quat(rotation)
+                .transpose()
+                .to_cols_array_2d();
+            *value = Matrix33 {
+                m11: cols[0][0],
+                m12: cols[0][1],
+                m13: cols[0][2],
+                m21: cols[1][0],
+                m22: cols[1][1],
+                m23: cols[1][2],
+                m31: cols[2][0],
+                m32: cols[2][1],
+                m33: cols[2][2],
+            };
+            self.response.changed = true;
+        }
+    }
+
+    fn color3(&mut self, ui: &mut egui::Ui, label: &str, value: &mut Color3) {
+        let mut rgb = [value.r, value.g, value.b];
+        ui.label(label);
+        let response = ui.add_enabled_ui(self.editable, |ui| ui.color_edit_button_rgb(&mut rgb));
+        if response.inner.changed() {
+            value.r = rgb[0];
+            value.g = rgb[1];
+            value.b = rgb[2];
+            self.response.changed = true;
+        }
+        ui.end_row();
+    }
+
+    fn block_ref(&mut self, ui: &mut egui::Ui, label: &str, block_ref: BlockRef) {
+        ui.label(label);
+        self.block_ref_link(ui, block_ref);
+        ui.end_row();
+    }
+
+    fn block_refs(&mut self, ui: &mut egui::Ui, label: &str, block_refs: &[BlockRef]) {
+        if block_refs.is_empty() {
+            return;
+        }
+        ui.label(label);
+        ui.vertical(|ui| {
+            for block_ref in block_refs {
+                self.block_ref_link(ui, *block_ref);
+            }
+        });
+        ui.end_row();
+    }
+
+    fn block_ref_link(&mut self, ui: &mut egui::Ui, block_ref: BlockRef) {
+        let name = usize::try_from(block_ref.0)
+            .ok()
+            .and_then(|idx| self.block_names.get(idx));
+        match name {
+            Some(name) => {
+                if ui.link(format!("{} (id {})", name, block_ref.0)).clicked() {
+                    self.response.select = Some(block_ref);
+                }
+            }
+            None => {
+                ui.label(egui::RichText::new("none").weak());
+            }
+        }
+    }
+}
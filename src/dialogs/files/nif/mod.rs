@@ -0,0 +1,327 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use eframe::egui;
+use nif::{blocks::Block, common::BlockRef, Nif};
+
+use crate::{
+    containers, nif_writer,
+    storage::prompt_save_nif_file,
+    widgets::nif::{object_bounds, CameraMode, CameraState, NifWidget},
+};
+
+use super::ProjectFileDialog;
+
+mod animation;
+mod inspector;
+
+use animation::AnimationPlayer;
+use inspector::{block_label, show_block_inspector};
+
+#[derive(Debug)]
+pub struct NifFileDialog {
+    path: Utf8PathBuf,
+    data: Nif,
+    nif_widget: NifWidget,
+    lod_distance: f32,
+    /// Switch LOD levels by camera distance instead of the fixed `lod_distance`.
+    auto_lod: bool,
+    animation: AnimationPlayer,
+    selected_block: Option<BlockRef>,
+    /// Why saving would lose data, saving is disabled while set.
+    save_blocker: Option<String>,
+    modified: bool,
+    save_status: Option<Result<String, String>>,
+}
+
+impl NifFileDialog {
+    pub fn create(
+        path: Utf8PathBuf,
+        data_buf: Vec<u8>,
+        frame: &mut eframe::Frame,
+    ) -> anyhow::Result<Self> {
+        let data = Nif::parse(&mut std::io::Cursor::new(&data_buf))?;
+
+        let render_state = frame.wgpu_render_state().unwrap();
+        let mut nif_widget = NifWidget::new(render_state);
+        let animation = AnimationPlayer::new(&data);
+        show_pose(&mut nif_widget, &data, &animation, render_state, Some(0.0));
+
+        // single models are easier to inspect by orbiting them
+        nif_widget.set_camera_mode(CameraMode::Orbit);
+        match nif_widget.group_bounds(render_state, "") {
+            Some((min, max)) => nif_widget.focus_on_model_bounds(min, max),
+            None => nif_widget.reset_camera_from_bounds(),
+        }
+
+        let save_blocker = nif_writer::check_roundtrip(&data_buf, &data)
+            .err()
+            .map(|err| err.to_string());
+
+        Ok(Self {
+            path,
+            data,
+            nif_widget,
+            lod_distance: 0.0,
+            auto_lod: false,
+            animation,
+            selected_block: None,
+            save_blocker,
+            modified: false,
+            save_status: None,
+        })
+    }
+
+    fn save_to(&mut self, path: &Utf8Path) {
+        let result = nif_writer::write_nif_to_vec(&self.data)
+            .and_then(|buf| containers::write_with_backup(path, &buf));
+        self.save_status = Some(match result {
+            Ok(()) => {
+                self.modified = false;
+                Ok(format!("Saved to {}", path))
+            }
+            Err(err) => Err(format!("Failed to save: {}", err)),
+        });
+    }
+
+    fn show_save_controls(&mut self, ui: &mut egui::Ui) {
+        let can_write = self.save_blocker.is_none();
+        // files inside archives or containers can only be saved elsewhere
+        let can_overwrite = self.path.is_file();
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(can_write && can_overwrite, egui::Button::new("Save"))
+                .clicked()
+            {
+                let path = self.path.clone();
+                self.save_to(&path);
+            }
+            if ui
+                .add_enabled(can_write, egui::Button::new("Save As…"))
+                .clicked()
+            {
+                if let Some(path) = prompt_save_nif_file(&self.path) {
+                    self.save_to(&path);
+                }
+            }
+        });
+
+        if let Some(save_blocker) = &self.save_blocker {
+            ui.label(
+                egui::RichText::new(format!("Cannot save without losing data, {}", save_blocker))
+                    .color(egui::Color32::YELLOW),
+            );
+        }
+        match &self.save_status {
+            Some(Ok(status)) => {
+                ui.label(egui::RichText::new(status).color(egui::Color32::GREEN));
+            }
+            Some(Err(status)) => {
+                ui.label(egui::RichText::new(status).color(egui::Color32::RED));
+            }
+            None => {}
+        }
+    }
+}
+
+impl ProjectFileDialog for NifFileDialog {
+    fn title(&self) -> String {
+        let title = self.path.file_name().unwrap();
+        if self.modified {
+            format!("{}*", title)
+        } else {
+            title.into()
+        }
+    }
+
+    fn camera_state(&mut self) -> Option<CameraState> {
+        Some(self.nif_widget.camera_state())
+    }
+
+    fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    fn set_camera_state(&mut self, state: CameraState) {
+        self.nif_widget.set_camera_state(state);
+    }
+
+    fn show(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
+        self.show_save_controls(ui);
+        ui.separator();
+
+        let Self {
+            data,
+            nif_widget,
+            lod_distance,
+            auto_lod,
+            animation,
+            selected_block,
+            modified,
+            save_blocker,
+            ..
+        } = self;
+        // edits to a file that cannot be saved would only be lost
+        let editable = save_blocker.is_none();
+
+        ui.horizontal_top(|ui| {
+            nif_widget.show(ui, frame, Some(egui::vec2(320.0, 0.0)));
+            let render_state = frame.wgpu_render_state().unwrap();
+            ui.separator();
+            ui.vertical(|ui| {
+                ui.label("Simulated distance (LOD)");
+                let lod_changed = ui.checkbox(auto_lod, "Use camera distance").changed()
+                    | ui.add_enabled(!*auto_lod, egui::Slider::new(lod_distance, 0.0..=500.0))
+                        .changed();
+                let lod = (!*auto_lod).then_some(*lod_distance);
+                if lod_changed {
+                    show_pose(nif_widget, data, animation, render_state, lod);
+                }
+                if !animation.is_empty() {
+                    ui.separator();
+                    if animation.show(ui) {
+                        nif_widget.set_pose(data, render_state, None, &animation.pose(data));
+                    }
+                }
+                ui.separator();
+                let frame_label = match selected_block {
+                    Some(_) => "Frame selection",
+                    None => "Frame model",
+                };
+                if ui.button(frame_label).clicked() {
+                    frame_selection(nif_widget, data, animation, render_state, *selected_block);
+                    // the viewport was already drawn this frame
+                    ui.ctx().request_repaint();
+                }
+                egui::ScrollArea::vertical()
+                    .id_source("nif_block_tree")
+                    .max_height(ui.available_height() / 2.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        let root_block = data.blocks.get(0).unwrap();
+                        add_node(ui, BlockRef(0), root_block, &data.blocks, selected_block);
+                    });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_source("nif_block_inspector")
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| match *selected_block {
+                        Some(block_ref) => {
+                            let response =
+                                show_block_inspector(ui, &mut data.blocks, block_ref, editable);
+                            if response.changed {
+                                *modified = true;
+                                show_pose(nif_widget, data, animation, render_state, lod);
+                            }
+                            if let Some(select) = response.select {
+                                *selected_block = Some(select);
+                            }
+                        }
+                        None => {
+                            ui.label("Select a block to inspect its properties");
+                        }
+                    });
+            });
+        });
+    }
+}
+
+/// Collects and shows the NIF, in the animation's current pose.
+fn show_pose(
+    nif_widget: &mut NifWidget,
+    data: &Nif,
+    animation: &AnimationPlayer,
+    render_state: &eframe::egui_wgpu::RenderState,
+    lod_distance: Option<f32>,
+) {
+    nif_widget.set_nif(data, render_state, lod_distance, None, None);
+    nif_widget.set_pose(data, render_state, None, &animation.pose(data));
+}
+
+/// Fits the selected object to the view, or the whole model when it has no geometry.
+fn frame_selection(
+    nif_widget: &mut NifWidget,
+    data: &Nif,
+    animation: &AnimationPlayer,
+    render_state: &eframe::egui_wgpu::RenderState,
+    selected_block: Option<BlockRef>,
+) {
+    let pose = animation.pose(data);
+    let bounds = selected_block
+        .and_then(|block_ref| usize::try_from(block_ref.0).ok())
+        .and_then(|idx| object_bounds(data, &pose, idx))
+        .or_else(|| nif_widget.group_bounds(render_state, ""));
+    if let Some((min, max)) = bounds {
+        nif_widget.focus_on_model_bounds(min, max);
+    }
+}
+
+fn add_node(
+    ui: &mut egui::Ui,
+    block_ref: BlockRef,
+    block: &Block,
+    blocks: &[Block],
+    selected_block: &mut Option<BlockRef>,
+) {
+    let properties = block.properties(blocks).unwrap_or_default();
+    let children = block.children(blocks).unwrap_or_default();
+    let extra_data = block.extra_data(blocks).unwrap_or_default();
+
+    let mut specialized_data_blocks = Vec::new();
+    match block {
+        Block::NiTextureEffect(block) => {
+            if let Some(source_texture) = block.source_texture_ref.get(blocks) {
+                specialized_data_blocks.push((block.source_texture_ref, source_texture));
+            }
+        }
+        Block::NiTriShape(block) => {
+            if let Some(tri_shape_data) = block.data_ref.get(blocks) {
+                specialized_data_blocks.push((block.data_ref, tri_shape_data));
+            }
+        }
+        _ => {}
+    }
+
+    let is_selected = *selected_block == Some(block_ref);
+    let label = block_label(block, block_ref);
+
+    if !properties.is_empty()
+        || !children.is_empty()
+        || !extra_data.is_empty()
+        || !specialized_data_blocks.is_empty()
+    {
+        let id = ui.make_persistent_id(("nif_block", block_ref.0));
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+            .show_header(ui, |ui| {
+                if ui.selectable_label(is_selected, label).clicked() {
+                    *selected_block = Some(block_ref);
+                }
+            })
+            .body(|ui| {
+                ui.style_mut().wrap = Some(true);
+
+                for (property_ref, property_block) in properties {
+                    add_node(ui, property_ref, property_block, blocks, selected_block);
+                }
+
+                for (child_ref, child_block) in children {
+                    add_node(ui, child_ref, child_block, blocks, selected_block);
+                }
+
+                for (extra_data_ref, extra_data_block) in extra_data {
+                    add_node(ui, extra_data_ref, extra_data_block, blocks, selected_block);
+                }
+
+                for (specialized_data_ref, specialized_data_block) in specialized_data_blocks {
+                    add_node(
+                        ui,
+                        specialized_data_ref,
+                        specialized_data_block,
+                        blocks,
+                        selected_block,
+                    );
+                }
+            });
+    } else if ui.selectable_label(is_selected, label).clicked() {
+        *selected_block = Some(block_ref);
+    }
+}
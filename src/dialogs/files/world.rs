@@ -1,202 +1,816 @@
 use std::{
     collections::{HashMap, HashSet},
-    fs::File,
-    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    io::Cursor,
 };
 
 use camino::Utf8PathBuf;
 use eframe::egui;
 use nif::Nif;
-use slidetown::parsers::{lbf::Lbf, lf::Lf, lif::Lif, lof::Lof, loi::Loi};
+use slidetown::parsers::{
+    lbf::Lbf,
+    lf::Lf,
+    lof::Lof,
+    loi::{Loi, LoiObject, LoiObjectExtra},
+};
 
-use crate::widgets::nif::{untextured_mesh::UntexturedMeshInstance, NifWidget};
+use crate::{
+    containers::{self, ContainerKind},
+    project::{FileLister, Project},
+    widgets::nif::{untextured_mesh::UntexturedMeshInstance, CameraState, NifWidget, OverlayShape},
+    world_data::{self, ObjectKey, TrackDiff, WorldObjectRef},
+};
 
 use super::ProjectFileDialog;
 
+const BLOCK_ONLY_A_TINT: glam::Vec3 = glam::Vec3::new(1.0, 0.6, 0.6);
+const BLOCK_ONLY_B_TINT: glam::Vec3 = glam::Vec3::new(0.6, 1.0, 0.6);
+const REMOVED_TINT: glam::Vec3 = glam::Vec3::new(1.0, 0.25, 0.25);
+const ADDED_TINT: glam::Vec3 = glam::Vec3::new(0.25, 1.0, 0.25);
+const MOVED_TINT: glam::Vec3 = glam::Vec3::new(0.3, 0.6, 1.0);
+
+fn tinted_instance(tint: glam::Vec3) -> UntexturedMeshInstance {
+    UntexturedMeshInstance {
+        tint,
+        ..Default::default()
+    }
+}
+
+fn tint_color(tint: glam::Vec3) -> egui::Color32 {
+    let [r, g, b] = (tint * 255.0).to_array();
+    egui::Color32::from_rgb(r as u8, g as u8, b as u8)
+}
+
+fn loi_object(loi: &Loi, (block_index, object_index): ObjectKey) -> Option<&LoiObject> {
+    loi.blocks
+        .iter()
+        .find(|block| block.block_index == block_index)?
+        .objects
+        .get(object_index)
+}
+
+/// An object of the current track that references an entry of `Loi::object_extras`.
+#[derive(Debug)]
+struct ObjectExtraLink {
+    object_ref: WorldObjectRef,
+    object_position: glam::Vec3,
+    extra_index: usize,
+    extra_position: glam::Vec3,
+}
+
 #[derive(Debug)]
 pub struct WorldDirDialog {
     dir_path: Utf8PathBuf,
+    files: FileLister,
     nif_widget: NifWidget,
     available_tracks: Vec<String>,
+    current_track: String,
+    loi: Option<Loi>,
+    /// Track shown as changes on top of the current one.
+    compare_track: Option<String>,
+    compare_loi: Option<Loi>,
+    diff: Option<TrackDiff>,
+    /// Old and new position of every moved object.
+    moved_lines: Vec<(glam::Vec3, glam::Vec3)>,
+    model_names: HashMap<u32, String>,
+    /// Mesh group of every model placed by the current track.
+    model_groups: HashMap<u32, String>,
+    selected_object: Option<WorldObjectRef>,
+    selection_bounds: Option<(glam::Vec3, glam::Vec3)>,
+    extra_links: Vec<ObjectExtraLink>,
+    show_extras: bool,
+    layer_filter: String,
+    /// Layer shown on its own, every other layer is hidden while set.
+    solo_layer: Option<String>,
 }
 
 impl WorldDirDialog {
-    fn load_track(&mut self, name: &str, frame: &mut eframe::Frame) {
+    /// Shows `name`, compared against `compare_track`. Everything is read before the shown
+    /// track is replaced, so a failed load leaves the previous one in place.
+    fn load_track(
+        &mut self,
+        name: &str,
+        compare_track: Option<String>,
+        frame: &mut eframe::Frame,
+    ) -> anyhow::Result<()> {
         let render_state = frame.wgpu_render_state().unwrap();
+        // comparing a track with itself shows nothing
+        let compare_track = compare_track.filter(|track| track != name);
         let Self {
-            dir_path,
-            nif_widget,
-            ..
+            dir_path, files, ..
         } = self;
 
-        nif_widget.clear_nifs(render_state);
+        let enabled_blocks = world_data::enabled_blocks(files, dir_path, name)?;
 
-        let mut enabled_blocks = HashSet::new();
+        let lf_data = files.read_file(&dir_path.join("terrain0.lf"))?;
+        let lf = Lf::read(&mut Cursor::new(&lf_data))?;
 
-        let lif_path = dir_path.join(name).join("terrain0.LIF");
-        let file = File::open(&lif_path).unwrap();
-        let mut reader = BufReader::new(file);
-        let lif = Lif::read(&mut reader).unwrap();
-        for block in lif.blocks.iter() {
-            if block.unk > 0 {
-                enabled_blocks.insert(block.index);
+        let loi = world_data::read_loi(files, dir_path, name, lf.block_count)?;
+        let compare = compare_track
+            .as_ref()
+            .map(|track| -> anyhow::Result<_> {
+                let enabled_blocks = world_data::enabled_blocks(files, dir_path, track)?;
+                let loi = world_data::read_loi(files, dir_path, track, lf.block_count)?;
+                Ok((track.clone(), enabled_blocks, loi))
+            })
+            .transpose()?;
+        let diff = compare.as_ref().map(|(_, compare_blocks, compare_loi)| {
+            TrackDiff::new(&enabled_blocks, &loi, compare_blocks, compare_loi)
+        });
+
+        let mut shown_blocks = enabled_blocks.clone();
+        if let Some((_, compare_blocks, _)) = &compare {
+            shown_blocks.extend(compare_blocks.iter().copied());
+        }
+        // blocks used by only one of the compared tracks get their own tinted group
+        let block_group = |base: &str, block_index: u32| match (&diff, &compare) {
+            (Some(diff), _) if diff.blocks_only_a.contains(&block_index) => (
+                format!("{}_only_{}", base, name),
+                Some(vec![tinted_instance(BLOCK_ONLY_A_TINT)]),
+            ),
+            (Some(diff), Some((compare_track, _, _)))
+                if diff.blocks_only_b.contains(&block_index) =>
+            {
+                (
+                    format!("{}_only_{}", base, compare_track),
+                    Some(vec![tinted_instance(BLOCK_ONLY_B_TINT)]),
+                )
+            }
+            _ => (base.to_string(), None),
+        };
+
+        // parsed meshes with their group and instances, added once everything has loaded
+        let mut nifs = Vec::new();
+
+        let lf_entries = containers::embedded_nifs(ContainerKind::Lf, &lf_data)?;
+        for (block, entry) in lf.blocks.iter().zip(lf_entries.iter()) {
+            if !shown_blocks.contains(&block.index) {
+                continue;
             }
+            let nif_data = containers::embedded_nif_data(&lf_data, entry)?;
+            let nif = Nif::parse(&mut Cursor::new(nif_data))?;
+            let (group, instances) = block_group("terrain", block.index);
+            nifs.push((nif, group, instances));
         }
 
-        let lf_path = dir_path.join("terrain0.lf");
-        let file = File::open(&lf_path).unwrap();
-        let mut reader = BufReader::new(file);
-        let lf = Lf::read(&mut reader).unwrap();
-        for block in lf
-            .blocks
-            .iter()
-            .filter(|b| enabled_blocks.contains(&b.index))
-        {
-            reader
-                .seek(SeekFrom::Start(block.file_offset as _))
-                .unwrap();
-            let mut nif_data = vec![0u8; block.file_length as _];
-            reader.read_exact(&mut nif_data).unwrap();
-            let nif = Nif::parse(&mut Cursor::new(nif_data)).unwrap();
-            nif_widget.add_nif(&nif, render_state, 0.0, Some("terrain".into()), None);
-        }
-
-        let lbf_path = dir_path.join("blockObj0.LBF");
-        let file = File::open(&lbf_path).unwrap();
-        let mut reader = BufReader::new(file);
-        let lbf = Lbf::parse(&mut reader).unwrap();
-        for (_, block) in lbf
-            .blocks
-            .iter()
-            .enumerate()
-            .filter(|b| enabled_blocks.contains(&(b.0 as _)))
-        {
-            for object in block.objects.iter() {
-                reader
-                    .seek(SeekFrom::Start(object.file_offset as _))
-                    .unwrap();
-                let mut nif_data = vec![0u8; object.file_length as _];
-                reader.read_exact(&mut nif_data).unwrap();
-                let nif = Nif::parse(&mut Cursor::new(nif_data)).unwrap();
-                nif_widget.add_nif(&nif, render_state, 0.0, Some("blockObj".into()), None);
+        let lbf_data = files.read_file(&dir_path.join("blockObj0.LBF"))?;
+        let lbf = Lbf::parse(&mut Cursor::new(&lbf_data))?;
+        // entries list every block's objects in turn
+        let mut lbf_entries = containers::embedded_nifs(ContainerKind::Lbf, &lbf_data)?.into_iter();
+        for (block_index, block) in lbf.blocks.iter().enumerate() {
+            let shown = shown_blocks.contains(&(block_index as _));
+            for entry in lbf_entries
+                .by_ref()
+                .take(block.objects.len())
+                .filter(|_| shown)
+            {
+                let nif_data = containers::embedded_nif_data(&lbf_data, &entry)?;
+                let nif = Nif::parse(&mut Cursor::new(nif_data))?;
+                let (group, instances) = block_group("blockObj", block_index as _);
+                nifs.push((nif, group, instances));
             }
         }
 
         let mut instances_by_model_index: HashMap<u32, Vec<UntexturedMeshInstance>> =
             HashMap::new();
+        let mut extra_links = Vec::new();
+        let mut moved_lines = Vec::new();
+
+        let removed = diff
+            .iter()
+            .flat_map(|diff| diff.removed.iter().copied())
+            .collect::<HashSet<ObjectKey>>();
+        let moved = diff
+            .iter()
+            .flat_map(|diff| diff.moved.iter().copied())
+            .collect::<HashMap<ObjectKey, ObjectKey>>();
 
-        let loi_path = dir_path.join(name).join("object0.loI");
-        let file = File::open(&loi_path).unwrap();
-        let mut reader = BufReader::new(file);
-        let loi = Loi::read(&mut reader, lf.block_count as _).unwrap();
         for block in loi
             .blocks
             .iter()
             .filter(|b| enabled_blocks.contains(&b.block_index))
         {
-            for object in block.objects.iter() {
-                let position = glam::vec3(object.position.0, object.position.1, object.position.2);
-                let rm = object.rotation;
-                let rotation_mat = glam::Mat3::from_cols_array_2d(&[
-                    [rm.0 .0, rm.0 .1, rm.0 .2],
-                    [rm.1 .0, rm.1 .1, rm.1 .2],
-                    [rm.2 .0, rm.2 .1, rm.2 .2],
-                ])
-                .transpose();
-                let rotation = glam::Quat::from_mat3(&rotation_mat);
+            for (object_index, object) in block.objects.iter().enumerate() {
+                let position = world_data::object_position(object);
+                let rotation = world_data::object_rotation(object);
                 let scale = object.scale;
-                let entry = instances_by_model_index.entry(object.model_table_index);
-                entry.or_default().push(UntexturedMeshInstance {
-                    position,
-                    rotation,
-                    scale,
-                });
-                // if object.object_extra_index >= 0 {
-                //     let extra = loi
-                //         .object_extras
-                //         .get(object.object_extra_index as usize)
-                //         .unwrap();
-                //     dbg!(&object.position, &extra.position);
-                // }
+                let key = (block.block_index, object_index);
+                // moved objects are drawn where the compared track places them
+                if !moved.contains_key(&key) {
+                    let tint = if removed.contains(&key) {
+                        REMOVED_TINT
+                    } else {
+                        glam::Vec3::ONE
+                    };
+                    let entry = instances_by_model_index.entry(object.model_table_index);
+                    entry.or_default().push(UntexturedMeshInstance {
+                        position,
+                        rotation,
+                        scale,
+                        tint,
+                    });
+                }
+                let extra_index = object.object_extra_index;
+                if let Some(extra) = usize::try_from(extra_index)
+                    .ok()
+                    .and_then(|idx| loi.object_extras.get(idx))
+                {
+                    extra_links.push(ObjectExtraLink {
+                        object_ref: WorldObjectRef {
+                            track: name.to_string(),
+                            block_index: block.block_index,
+                            object_index,
+                        },
+                        object_position: position,
+                        extra_index: extra_index as usize,
+                        extra_position: glam::vec3(
+                            extra.position.0,
+                            extra.position.1,
+                            extra.position.2,
+                        ),
+                    });
+                }
             }
         }
 
-        let lof_path = dir_path.join("modeltable0.LOF");
-        let file = File::open(&lof_path).unwrap();
-        let mut reader = BufReader::new(file);
-        let lof = Lof::read_without_data(&mut reader).unwrap();
-        for model in lof.models.iter() {
-            if !instances_by_model_index.contains_key(&model.index) {
-                continue;
+        if let (Some(diff), Some((_, _, compare_loi))) = (&diff, &compare) {
+            let added = diff.added.iter().copied().collect::<HashSet<ObjectKey>>();
+            let moved_to = moved
+                .iter()
+                .map(|(key_a, key_b)| (*key_b, *key_a))
+                .collect::<HashMap<ObjectKey, ObjectKey>>();
+            for block in compare_loi.blocks.iter() {
+                for (object_index, object) in block.objects.iter().enumerate() {
+                    let key = (block.block_index, object_index);
+                    let position = world_data::object_position(object);
+                    let tint = if added.contains(&key) {
+                        ADDED_TINT
+                    } else if let Some(key_a) = moved_to.get(&key) {
+                        if let Some(object_a) = loi_object(&loi, *key_a) {
+                            moved_lines.push((world_data::object_position(object_a), position));
+                        }
+                        MOVED_TINT
+                    } else {
+                        continue;
+                    };
+                    let entry = instances_by_model_index.entry(object.model_table_index);
+                    entry.or_default().push(UntexturedMeshInstance {
+                        position,
+                        rotation: world_data::object_rotation(object),
+                        scale: object.scale,
+                        tint,
+                    });
+                }
+            }
+        }
+
+        let lof_data = files.read_file(&dir_path.join("modeltable0.LOF"))?;
+        let lof = Lof::read_without_data(&mut Cursor::new(&lof_data))?;
+        let lof_entries = containers::embedded_nifs(ContainerKind::Lof, &lof_data)?;
+        let model_names = lof
+            .models
+            .iter()
+            .map(|model| (model.index, model.file_name.clone()))
+            .collect();
+        let mut model_groups = HashMap::new();
+        for (model, entry) in lof.models.iter().zip(lof_entries.iter()) {
+            let instances = match instances_by_model_index.remove(&model.index) {
+                Some(instances) => instances,
+                None => continue,
+            };
+            let nif_data = containers::embedded_nif_data(&lof_data, entry)?;
+            let nif = Nif::parse(&mut Cursor::new(nif_data))?;
+            let group = format!("modeltable_{}_{}", model.index, model.file_name);
+            nifs.push((nif, group.clone(), Some(instances)));
+            model_groups.insert(model.index, group);
+        }
+
+        self.nif_widget.clear_nifs(render_state);
+        for (nif, group, instances) in nifs {
+            self.nif_widget
+                .add_nif(&nif, render_state, None, Some(group), instances);
+        }
+        self.current_track = name.to_string();
+        self.compare_track = compare_track;
+        self.model_names = model_names;
+        self.model_groups = model_groups;
+        self.extra_links = extra_links;
+        self.moved_lines = moved_lines;
+        self.loi = Some(loi);
+        self.compare_loi = compare.map(|(_, _, loi)| loi);
+        self.diff = diff;
+        self.refresh_overlay();
+        Ok(())
+    }
+
+    /// Rebuilds the viewport overlay from the selection and the extras toggle.
+    fn refresh_overlay(&mut self) {
+        let mut overlay = Vec::new();
+        if self.show_extras {
+            for link in self.extra_links.iter() {
+                overlay.push(OverlayShape::Line {
+                    from: link.object_position,
+                    to: link.extra_position,
+                    color: egui::Color32::LIGHT_BLUE,
+                });
+                overlay.push(OverlayShape::Point {
+                    position: link.object_position,
+                    radius: 3.0,
+                    color: egui::Color32::LIGHT_BLUE,
+                });
+                overlay.push(OverlayShape::Point {
+                    position: link.extra_position,
+                    radius: 4.0,
+                    color: egui::Color32::from_rgb(255, 128, 0),
+                });
             }
-            reader
-                .seek(SeekFrom::Start(model.file_offset as _))
-                .unwrap();
-            let mut nif_data = vec![0u8; model.file_length as _];
-            reader.read_exact(&mut nif_data).unwrap();
-            let nif = Nif::parse(&mut Cursor::new(nif_data)).unwrap();
-            let instances = instances_by_model_index.remove(&model.index);
-            nif_widget.add_nif(
-                &nif,
-                render_state,
-                0.0,
-                Some(format!("modeltable_{}_{}", model.index, model.file_name)),
-                instances,
+        }
+        for &(from, to) in self.moved_lines.iter() {
+            overlay.push(OverlayShape::Line {
+                from,
+                to,
+                color: tint_color(MOVED_TINT),
+            });
+        }
+        if let Some((min, max)) = self.selection_bounds {
+            overlay.push(OverlayShape::Box {
+                min,
+                max,
+                color: egui::Color32::YELLOW,
+            });
+        }
+        self.nif_widget.set_overlay(overlay);
+    }
+
+    fn object_extra(&self, object: &LoiObject) -> Option<&LoiObjectExtra> {
+        let idx = usize::try_from(object.object_extra_index).ok()?;
+        self.loi.as_ref()?.object_extras.get(idx)
+    }
+
+    fn object(&self, object_ref: &WorldObjectRef) -> Option<&LoiObject> {
+        let loi = if object_ref.track == self.current_track {
+            self.loi.as_ref()?
+        } else if self.compare_track.as_ref() == Some(&object_ref.track) {
+            self.compare_loi.as_ref()?
+        } else {
+            return None;
+        };
+        loi_object(loi, (object_ref.block_index, object_ref.object_index))
+    }
+
+    /// Changed objects as (label, reference, tint), in the track that places them.
+    fn diff_entries(&self) -> Vec<(String, WorldObjectRef, glam::Vec3)> {
+        let (diff, compare_track) = match (self.diff.as_ref(), self.compare_track.as_ref()) {
+            (Some(diff), Some(compare_track)) => (diff, compare_track),
+            _ => return Vec::new(),
+        };
+        let entry = |prefix: &str, track: &str, (block_index, object_index): ObjectKey, tint| {
+            let object_ref = WorldObjectRef {
+                track: track.to_string(),
+                block_index,
+                object_index,
+            };
+            let model_name = self
+                .object(&object_ref)
+                .and_then(|object| self.model_names.get(&object.model_table_index))
+                .cloned()
+                .unwrap_or_default();
+            let label = format!("{} {}/{} {}", prefix, block_index, object_index, model_name);
+            (label, object_ref, tint)
+        };
+
+        let added = diff
+            .added
+            .iter()
+            .map(|&key| entry("+", compare_track, key, ADDED_TINT));
+        let removed = diff
+            .removed
+            .iter()
+            .map(|&key| entry("-", &self.current_track, key, REMOVED_TINT));
+        let moved = diff
+            .moved
+            .iter()
+            .map(|&(_, key_b)| entry("~", compare_track, key_b, MOVED_TINT));
+        added.chain(removed).chain(moved).collect()
+    }
+
+    /// Plain text description of the differences to the compared track.
+    fn diff_summary(&self) -> Option<String> {
+        let (diff, compare_track) = (self.diff.as_ref()?, self.compare_track.as_ref()?);
+        let join = |blocks: &[u32]| {
+            blocks
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let describe = |loi: Option<&Loi>, key: ObjectKey| {
+            let model_index = loi
+                .and_then(|loi| loi_object(loi, key))
+                .map(|object| object.model_table_index);
+            let model_name = model_index.and_then(|idx| self.model_names.get(&idx));
+            format!(
+                "block {} object {}: model {} {}",
+                key.0,
+                key.1,
+                model_index.map(|idx| idx.to_string()).unwrap_or_default(),
+                model_name.map(String::as_str).unwrap_or_default()
+            )
+        };
+
+        let mut summary = format!("{} -> {}\n", self.current_track, compare_track);
+        if diff.is_empty() {
+            summary.push_str("No differences\n");
+            return Some(summary);
+        }
+        summary.push_str(&format!(
+            "Blocks only in {}: {}\n",
+            self.current_track,
+            join(&diff.blocks_only_a)
+        ));
+        summary.push_str(&format!(
+            "Blocks only in {}: {}\n",
+            compare_track,
+            join(&diff.blocks_only_b)
+        ));
+        summary.push_str(&format!(
+            "{} added, {} removed, {} moved objects\n",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.moved.len()
+        ));
+        for &key in diff.added.iter() {
+            summary.push_str(&format!("+ {}\n", describe(self.compare_loi.as_ref(), key)));
+        }
+        for &key in diff.removed.iter() {
+            summary.push_str(&format!("- {}\n", describe(self.loi.as_ref(), key)));
+        }
+        for &(key_a, key_b) in diff.moved.iter() {
+            summary.push_str(&format!(
+                "~ {} -> block {} object {}\n",
+                describe(self.loi.as_ref(), key_a),
+                key_b.0,
+                key_b.1
+            ));
+        }
+        Some(summary)
+    }
+
+    /// World space bounds of a placed object, from its model's bounds.
+    fn object_bounds(
+        &self,
+        object: &LoiObject,
+        frame: &mut eframe::Frame,
+    ) -> (glam::Vec3, glam::Vec3) {
+        let position = world_data::object_position(object);
+        let model_bounds = self
+            .model_groups
+            .get(&object.model_table_index)
+            .and_then(|group| {
+                self.nif_widget
+                    .group_bounds(frame.wgpu_render_state().unwrap(), group)
+            });
+        let (min, max) = match model_bounds {
+            Some(bounds) => bounds,
+            None => return (position - glam::Vec3::ONE, position + glam::Vec3::ONE),
+        };
+
+        let transform = glam::Mat4::from_scale_rotation_translation(
+            glam::Vec3::splat(object.scale),
+            world_data::object_rotation(object),
+            position,
+        );
+        let mut world_min = glam::Vec3::splat(f32::MAX);
+        let mut world_max = glam::Vec3::splat(f32::MIN);
+        for corner in 0..8 {
+            let corner = glam::vec3(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
             );
+            let corner = transform.transform_point3(corner);
+            world_min = world_min.min(corner);
+            world_max = world_max.max(corner);
         }
+        (world_min, world_max)
     }
 }
 
-impl ProjectFileDialog for WorldDirDialog {
-    fn create(dir_path: Utf8PathBuf, frame: &mut eframe::Frame) -> Self {
+impl WorldDirDialog {
+    pub fn create(
+        project: &Project,
+        dir_path: Utf8PathBuf,
+        frame: &mut eframe::Frame,
+    ) -> anyhow::Result<Self> {
         let render_state = frame.wgpu_render_state().unwrap();
 
-        let mut available_tracks = vec!["Main".to_string()];
-        for i in 1.. {
-            let name = format!("Track{}", i);
-            let path = dir_path.join(&name);
-            if let Ok(metadata) = std::fs::metadata(path) {
-                if metadata.is_dir() {
-                    available_tracks.push(name);
-                }
-            } else {
-                break;
-            }
-        }
+        let files = project.file_lister();
+        let available_tracks = world_data::available_tracks(&files, &dir_path);
 
         let mut me = Self {
             dir_path,
+            files,
             nif_widget: NifWidget::new(render_state),
             available_tracks,
+            current_track: String::new(),
+            loi: None,
+            compare_track: None,
+            compare_loi: None,
+            diff: None,
+            moved_lines: Vec::new(),
+            model_names: Default::default(),
+            model_groups: Default::default(),
+            selected_object: None,
+            selection_bounds: None,
+            extra_links: Vec::new(),
+            show_extras: true,
+            layer_filter: String::new(),
+            solo_layer: None,
         };
-        me.load_track("Main", frame);
+        me.load_track("Main", None, frame)?;
         me.nif_widget.reset_camera_from_bounds();
-        me
+        Ok(me)
     }
+}
 
+impl ProjectFileDialog for WorldDirDialog {
     fn title(&self) -> String {
         self.dir_path.file_name().unwrap().into()
     }
 
-    fn show(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
-        let Self { nif_widget, .. } = self;
+    fn camera_state(&mut self) -> Option<CameraState> {
+        Some(self.nif_widget.camera_state())
+    }
 
+    fn set_camera_state(&mut self, state: CameraState) {
+        self.nif_widget.set_camera_state(state);
+    }
+
+    fn select_world_object(&mut self, object_ref: &WorldObjectRef, frame: &mut eframe::Frame) {
+        if self.current_track != object_ref.track
+            && self.compare_track.as_ref() != Some(&object_ref.track)
+        {
+            let compare_track = self.compare_track.clone();
+            if let Err(err) = self.load_track(&object_ref.track, compare_track, frame) {
+                eprintln!("Failed to load track {}: {:?}", object_ref.track, err);
+            }
+        }
+        self.selection_bounds = self
+            .object(object_ref)
+            .map(|object| self.object_bounds(object, frame));
+        if let Some((min, max)) = self.selection_bounds {
+            self.nif_widget.focus_on_bounds(min, max);
+        }
+        self.selected_object = Some(object_ref.clone());
+        self.refresh_overlay();
+    }
+
+    fn show(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
         let available_tracks = self.available_tracks.clone();
+        let selected_object = self.selected_object.as_ref().map(|object_ref| {
+            (
+                object_ref.clone(),
+                self.object(object_ref).map(|object| {
+                    (
+                        object.model_table_index,
+                        world_data::object_position(object),
+                        self.object_extra(object)
+                            .map(|extra| (object.object_extra_index, format!("{:#?}", extra))),
+                    )
+                }),
+            )
+        });
+
+        let diff_summary = self.diff_summary();
+        let diff_entries = self.diff_entries();
+        let layer_stats = self
+            .nif_widget
+            .group_stats(frame.wgpu_render_state().unwrap());
+
+        let Self {
+            nif_widget,
+            current_track,
+            compare_track,
+            diff,
+            extra_links,
+            show_extras,
+            layer_filter,
+            solo_layer,
+            ..
+        } = self;
 
         let mut selected_track = None;
+        let mut selected_compare_track = compare_track.clone();
+        let mut clear_selection = false;
+        let mut select_object = None;
+        let mut extras_toggled = false;
         ui.horizontal_top(|ui| {
-            nif_widget.show(ui, frame, Some(egui::vec2(100.0, 0.0)));
+            nif_widget.show(ui, frame, Some(egui::vec2(160.0, 0.0)));
             ui.separator();
             ui.vertical(|ui| {
-                for track in available_tracks {
-                    if ui.button(&track).clicked() {
-                        selected_track = Some(track);
+                for track in available_tracks.iter() {
+                    if ui.selectable_label(current_track == track, track).clicked() {
+                        selected_track = Some(track.clone());
+                    }
+                }
+
+                ui.separator();
+                egui::ComboBox::from_id_source("compare_track")
+                    .selected_text(match &selected_compare_track {
+                        Some(track) => format!("Compare: {}", track),
+                        None => "Compare: off".to_string(),
+                    })
+                    .width(150.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut selected_compare_track, None, "Off");
+                        for track in available_tracks.iter().filter(|t| *t != current_track) {
+                            ui.selectable_value(
+                                &mut selected_compare_track,
+                                Some(track.clone()),
+                                track,
+                            );
+                        }
+                    });
+                if let (Some(diff), Some(compare_track)) = (diff.as_ref(), compare_track.as_ref()) {
+                    let legend = |ui: &mut egui::Ui, tint: glam::Vec3, text: String| {
+                        ui.label(
+                            egui::RichText::new(format!("■ {}", text)).color(tint_color(tint)),
+                        );
+                    };
+                    legend(
+                        ui,
+                        BLOCK_ONLY_A_TINT,
+                        format!(
+                            "{} blocks only in {}",
+                            diff.blocks_only_a.len(),
+                            current_track
+                        ),
+                    );
+                    legend(
+                        ui,
+                        BLOCK_ONLY_B_TINT,
+                        format!(
+                            "{} blocks only in {}",
+                            diff.blocks_only_b.len(),
+                            compare_track
+                        ),
+                    );
+                    legend(ui, ADDED_TINT, format!("{} added", diff.added.len()));
+                    legend(ui, REMOVED_TINT, format!("{} removed", diff.removed.len()));
+                    legend(ui, MOVED_TINT, format!("{} moved", diff.moved.len()));
+                    egui::CollapsingHeader::new("Differences")
+                        .id_source("diff_entries")
+                        .show(ui, |ui| {
+                            egui::ScrollArea::vertical()
+                                .id_source("diff_entries_scroll")
+                                .max_height(300.0)
+                                .show(ui, |ui| {
+                                    for (label, object_ref, tint) in diff_entries.iter() {
+                                        let selected = selected_object
+                                            .as_ref()
+                                            .map(|(selected, _)| selected == object_ref)
+                                            .unwrap_or(false);
+                                        let label =
+                                            egui::RichText::new(label).color(tint_color(*tint));
+                                        if ui.selectable_label(selected, label).clicked() {
+                                            select_object = Some(object_ref.clone());
+                                        }
+                                    }
+                                });
+                        });
+                    if let Some(diff_summary) = &diff_summary {
+                        if ui.button("Copy summary").clicked() {
+                            ui.output().copied_text = diff_summary.clone();
+                        }
+                    }
+                }
+
+                if let Some((object_ref, object)) = &selected_object {
+                    ui.separator();
+                    ui.label("Selected object");
+                    ui.label(format!(
+                        "Block {}, object {}",
+                        object_ref.block_index, object_ref.object_index
+                    ));
+                    if let Some((model_index, position, extra)) = object {
+                        ui.label(format!("Model {}", model_index));
+                        ui.label(format!(
+                            "{:.1} {:.1} {:.1}",
+                            position.x, position.y, position.z
+                        ));
+                        if let Some((extra_index, extra)) = extra {
+                            ui.label(format!("Extra {}", extra_index));
+                            ui.label(egui::RichText::new(extra).monospace());
+                        }
+                    }
+                    if ui.small_button("Clear").clicked() {
+                        clear_selection = true;
                     }
                 }
+
+                ui.separator();
+                extras_toggled = ui.checkbox(show_extras, "Show extras").changed();
+                egui::CollapsingHeader::new(format!("Objects with extras ({})", extra_links.len()))
+                    .id_source("extra_links")
+                    .show(ui, |ui| {
+                        egui::ScrollArea::vertical()
+                            .id_source("extra_links_scroll")
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                for link in extra_links.iter() {
+                                    let label = format!(
+                                        "{}/{} → extra {}",
+                                        link.object_ref.block_index,
+                                        link.object_ref.object_index,
+                                        link.extra_index
+                                    );
+                                    let selected = selected_object
+                                        .as_ref()
+                                        .map(|(object_ref, _)| *object_ref == link.object_ref)
+                                        .unwrap_or(false);
+                                    if ui.selectable_label(selected, label).clicked() {
+                                        select_object = Some(link.object_ref.clone());
+                                    }
+                                }
+                            });
+                    });
+
+                ui.separator();
+                egui::CollapsingHeader::new(format!("Layers ({})", layer_stats.len()))
+                    .id_source("layers")
+                    .show(ui, |ui| {
+                        ui.add(egui::TextEdit::singleline(layer_filter).hint_text("Filter"));
+                        ui.horizontal(|ui| {
+                            for (label, visible) in [("Show all", true), ("Hide all", false)] {
+                                if ui.small_button(label).clicked() {
+                                    for layer in layer_stats.iter() {
+                                        nif_widget.set_group_visible(&layer.name, visible);
+                                    }
+                                    *solo_layer = None;
+                                }
+                            }
+                        });
+                        let filter = layer_filter.to_lowercase();
+                        egui::ScrollArea::vertical()
+                            .id_source("layers_scroll")
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                for layer in layer_stats
+                                    .iter()
+                                    .filter(|layer| layer.name.to_lowercase().contains(&filter))
+                                {
+                                    let mut visible = nif_widget.is_group_visible(&layer.name);
+                                    if ui.checkbox(&mut visible, &layer.name).changed() {
+                                        nif_widget.set_group_visible(&layer.name, visible);
+                                    }
+                                    ui.horizontal(|ui| {
+                                        let mut tint =
+                                            nif_widget.group_tint(&layer.name).to_array();
+                                        if ui.color_edit_button_rgb(&mut tint).changed() {
+                                            nif_widget.set_group_tint(&layer.name, tint.into());
+                                        }
+                                        let is_solo = solo_layer.as_ref() == Some(&layer.name);
+                                        if ui
+                                            .selectable_label(is_solo, "Solo")
+                                            .on_hover_text("Hide every other layer")
+                                            .clicked()
+                                        {
+                                            for other in layer_stats.iter() {
+                                                nif_widget.set_group_visible(
+                                                    &other.name,
+                                                    is_solo || other.name == layer.name,
+                                                );
+                                            }
+                                            *solo_layer = (!is_solo).then(|| layer.name.clone());
+                                        }
+                                        ui.label(format!(
+                                            "{} tris, {} inst",
+                                            layer.triangle_count, layer.instance_count
+                                        ));
+                                    });
+                                }
+                            });
+                    });
             });
         });
+        if clear_selection || selected_track.is_some() {
+            self.selected_object = None;
+            self.selection_bounds = None;
+            self.refresh_overlay();
+        }
+        if selected_compare_track != self.compare_track {
+            let current_track = self.current_track.clone();
+            selected_track.get_or_insert(current_track);
+        }
         if let Some(track) = selected_track {
-            self.load_track(&track, frame);
+            if let Err(err) = self.load_track(&track, selected_compare_track, frame) {
+                eprintln!("Failed to load track {}: {:?}", track, err);
+            }
+        }
+        if let Some(object_ref) = select_object {
+            self.select_world_object(&object_ref, frame);
+        }
+        if extras_toggled {
+            self.refresh_overlay();
         }
     }
 }
@@ -1,3 +1,7 @@
+pub mod embedded_nifs;
 pub mod files;
 pub mod new_project;
+pub mod patch_archive;
 pub mod project;
+pub mod project_tree;
+pub mod quick_open;
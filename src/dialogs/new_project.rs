@@ -1,8 +1,14 @@
+use std::sync::mpsc;
+
 use camino::Utf8PathBuf;
 use eframe::egui;
 use serde::{Deserialize, Serialize};
 
-use crate::{project::Project, storage::prompt_game_directory};
+use crate::{
+    archive,
+    project::{EncryptionKey, Project},
+    storage::{prompt_game_directory, prompt_key_file},
+};
 
 pub enum NewProjectDialogResult {
     Created(Project),
@@ -13,7 +19,13 @@ pub enum NewProjectDialogResult {
 #[derive(Default, Serialize, Deserialize, Debug)]
 pub struct NewProjectDialog {
     game_dir: Option<Utf8PathBuf>,
-    key_dir: Option<Utf8PathBuf>,
+    key_path: Option<Utf8PathBuf>,
+    key: Option<EncryptionKey>,
+    #[serde(skip)]
+    key_status: Option<Result<String, String>>,
+    /// Result of checking the key against the game archives, which runs on its own thread.
+    #[serde(skip)]
+    key_check: Option<mpsc::Receiver<Result<String, String>>>,
 }
 
 impl NewProjectDialog {
@@ -21,8 +33,68 @@ impl NewProjectDialog {
         Default::default()
     }
 
+    fn validate_key(&mut self) {
+        // a check still running for the previous key or directory no longer matters
+        self.key_check = None;
+        let key_path = match &self.key_path {
+            Some(key_path) => key_path,
+            None => {
+                self.key = None;
+                self.key_status = None;
+                return;
+            }
+        };
+
+        let key = match EncryptionKey::from_file(key_path) {
+            Ok(key) => key,
+            Err(err) => {
+                self.key = None;
+                self.key_status = Some(Err(format!("Invalid key: {}", err)));
+                return;
+            }
+        };
+
+        self.key_status = Some(match &self.game_dir {
+            Some(game_dir) => {
+                // finding an archive can walk much of the game directory
+                let (sender, receiver) = mpsc::channel();
+                let (game_dir, key) = (game_dir.clone(), key.clone());
+                std::thread::spawn(move || {
+                    let status = match archive::verify_key(&game_dir, &key) {
+                        Ok(true) => Ok("Key decrypts the game archives.".into()),
+                        Ok(false) => {
+                            Ok("Key loaded, no archives found to verify it against.".into())
+                        }
+                        Err(err) => Err(format!("Key does not decrypt the game archives: {}", err)),
+                    };
+                    sender.send(status).ok();
+                });
+                self.key_check = Some(receiver);
+                Ok("Checking the key against the game archives…".into())
+            }
+            None => Ok("Key loaded.".into()),
+        });
+        self.key = Some(key);
+    }
+
+    fn poll_key_check(&mut self, ctx: &egui::Context) {
+        let receiver = match &self.key_check {
+            Some(receiver) => receiver,
+            None => return,
+        };
+        match receiver.try_recv() {
+            Ok(status) => {
+                self.key_status = Some(status);
+                self.key_check = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => ctx.request_repaint(),
+            Err(mpsc::TryRecvError::Disconnected) => self.key_check = None,
+        }
+    }
+
     pub fn show(&mut self, ctx: &egui::Context) -> NewProjectDialogResult {
         let mut result = NewProjectDialogResult::Idle;
+        self.poll_key_check(ctx);
 
         let mut open = true;
         egui::Window::new("New Project")
@@ -37,24 +109,50 @@ impl NewProjectDialog {
                     }
                     if ui.button("Choose").clicked() {
                         self.game_dir = prompt_game_directory();
+                        self.validate_key();
                     }
 
                     ui.add_space(8.0);
                     ui.label(egui::RichText::new("Custom encryption key (optional):").weak());
-                    if let Some(key_dir) = &self.key_dir {
-                        ui.add_enabled(false, egui::Label::new(key_dir.as_str()));
+                    if let Some(key_path) = &self.key_path {
+                        ui.add_enabled(false, egui::Label::new(key_path.as_str()));
                     }
-                    if ui.button("Choose").clicked() {
-                        dbg!(&self.key_dir);
+                    match &self.key_status {
+                        Some(Ok(status)) => {
+                            ui.label(egui::RichText::new(status).color(egui::Color32::GREEN));
+                        }
+                        Some(Err(status)) => {
+                            ui.label(egui::RichText::new(status).color(egui::Color32::RED));
+                        }
+                        None => {}
                     }
+                    ui.horizontal(|ui| {
+                        if ui.button("Choose").clicked() {
+                            if let Some(key_path) = prompt_key_file() {
+                                self.key_path = Some(key_path);
+                                self.validate_key();
+                            }
+                        }
+                        if self.key_path.is_some() && ui.button("Clear").clicked() {
+                            self.key_path = None;
+                            self.validate_key();
+                        }
+                    });
 
                     ui.add_space(12.0);
 
+                    // a key is only used once the archive check has passed
+                    let key_valid = self.key_path.is_none()
+                        || (self.key.is_some()
+                            && self.key_check.is_none()
+                            && !matches!(self.key_status, Some(Err(_))));
                     ui.vertical_centered_justified(|ui| {
-                        ui.add_enabled_ui(self.game_dir.is_some(), |ui| {
+                        ui.add_enabled_ui(self.game_dir.is_some() && key_valid, |ui| {
                             if ui.button("Create Project").clicked() {
                                 let game_dir = self.game_dir.as_ref().unwrap().clone();
-                                result = NewProjectDialogResult::Created(Project::new(game_dir));
+                                let mut project = Project::new(game_dir);
+                                project.set_encryption_key(self.key.clone());
+                                result = NewProjectDialogResult::Created(project);
                             }
                         });
                     });
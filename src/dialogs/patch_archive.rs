@@ -0,0 +1,120 @@
+use std::time::SystemTime;
+
+use camino::Utf8PathBuf;
+use eframe::egui;
+
+use crate::{
+    archive,
+    project::Project,
+    storage::{prompt_open_files, prompt_save_archive_file},
+};
+
+#[derive(Debug)]
+pub struct PatchArchiveDialog {
+    files: Vec<(Utf8PathBuf, bool)>,
+    status: Option<Result<String, String>>,
+}
+
+impl PatchArchiveDialog {
+    pub fn new(project: &Project, session_start: SystemTime) -> Self {
+        let mut modified_files = project.modified_files_since(session_start);
+        modified_files.sort();
+        Self {
+            files: modified_files
+                .into_iter()
+                .map(|path| (path, true))
+                .collect(),
+            status: None,
+        }
+    }
+
+    /// Returns false once the dialog has been closed.
+    pub fn show(&mut self, ctx: &egui::Context, project: &Project) -> bool {
+        let mut open = true;
+        egui::Window::new("Build Patch Archive")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("Files modified in this session:");
+                ui.label(
+                    egui::RichText::new(
+                        "Only loose files are listed, extract archived files to edit them.",
+                    )
+                    .weak(),
+                );
+                if self.files.is_empty() {
+                    ui.label(egui::RichText::new("No modified files, add some manually.").weak());
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        let game_dir = project.game_dir();
+                        for (path, included) in self.files.iter_mut() {
+                            let name = path.strip_prefix(&game_dir).unwrap_or(path);
+                            ui.checkbox(included, name.as_str());
+                        }
+                    });
+
+                if ui.button("Add files…").clicked() {
+                    for path in prompt_open_files() {
+                        if !self.files.iter().any(|(existing, _)| *existing == path) {
+                            self.files.push((path, true));
+                        }
+                    }
+                }
+
+                match &self.status {
+                    Some(Ok(status)) => {
+                        ui.label(egui::RichText::new(status).color(egui::Color32::GREEN));
+                    }
+                    Some(Err(status)) => {
+                        ui.label(egui::RichText::new(status).color(egui::Color32::RED));
+                    }
+                    None => {}
+                }
+
+                ui.add_space(8.0);
+
+                let any_included = self.files.iter().any(|(_, included)| *included);
+                ui.vertical_centered_justified(|ui| {
+                    ui.add_enabled_ui(any_included, |ui| {
+                        if ui.button("Build").clicked() {
+                            if let Some(path) = prompt_save_archive_file() {
+                                self.status = Some(
+                                    self.build(project, &path)
+                                        .map(|count| {
+                                            format!(
+                                                "Wrote and verified {} files in {}",
+                                                count, path
+                                            )
+                                        })
+                                        .map_err(|err| format!("Failed to build archive: {}", err)),
+                                );
+                            }
+                        }
+                    });
+                });
+            });
+        open
+    }
+
+    fn build(&self, project: &Project, path: &Utf8PathBuf) -> anyhow::Result<usize> {
+        let game_dir = project.game_dir();
+        let mut archive_files = Vec::new();
+        for (file_path, _) in self.files.iter().filter(|(_, included)| *included) {
+            let name = match file_path.strip_prefix(&game_dir) {
+                // archives always use `/`, whatever the platform's separator is
+                Ok(relative_path) => relative_path
+                    .components()
+                    .map(|component| component.as_str())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                Err(_) => file_path.file_name().unwrap().to_string(),
+            };
+            archive_files.push((name, std::fs::read(file_path)?));
+        }
+
+        archive::write_archive(path, &archive_files, &project.encryption_key())?;
+        Ok(archive_files.len())
+    }
+}
@@ -1,12 +1,25 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
 
 use camino::Utf8PathBuf;
 use eframe::egui;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    dialogs::files::{create_dialog_for_file, get_dir_dialog, ProjectFileDialog},
+    dialogs::{
+        embedded_nifs::EmbeddedNifsDialog,
+        files::{get_dir_dialog, open_file_dialog, OpenRequest, ProjectFileDialog},
+        patch_archive::PatchArchiveDialog,
+        project_tree::{ProjectTree, TreeAction},
+        quick_open::{QuickOpenPalette, QuickOpenResult, QuickOpenTarget},
+    },
+    file_watcher::{FileWatcher, WatchEvent, POLL_INTERVAL},
     project::{Project, ProjectFilesEntry},
+    storage::prompt_output_directory,
 };
 
 #[derive(Default, Serialize, Deserialize, Debug)]
@@ -14,12 +27,88 @@ pub struct ProjectDialog {
     project: Project,
     #[serde(skip)]
     files: Option<Vec<ProjectFilesEntry>>,
+    /// Listing of the project files being built in the background.
+    #[serde(skip)]
+    files_loading: Option<mpsc::Receiver<Vec<ProjectFilesEntry>>>,
     #[serde(skip)]
     open_files: HashMap<Utf8PathBuf, Box<dyn ProjectFileDialog>>,
     #[serde(skip)]
     active_file: Option<Utf8PathBuf>,
     #[serde(skip)]
-    request_open_file: Option<Utf8PathBuf>,
+    request_open: Option<OpenRequest>,
+    #[serde(skip)]
+    session_start: Option<SystemTime>,
+    #[serde(skip)]
+    patch_archive_dialog: Option<PatchArchiveDialog>,
+    #[serde(skip)]
+    embedded_nifs_dialog: Option<EmbeddedNifsDialog>,
+    #[serde(skip)]
+    quick_open: Option<QuickOpenPalette>,
+    #[serde(skip)]
+    tree: ProjectTree,
+    #[serde(skip)]
+    watcher: FileWatcher,
+    #[serde(skip)]
+    deleted_files: HashSet<Utf8PathBuf>,
+    /// Open files with unsaved edits that changed on disk, they are not reloaded until asked.
+    #[serde(skip)]
+    changed_on_disk: HashSet<Utf8PathBuf>,
+    /// Open files that could not be reloaded after changing on disk.
+    #[serde(skip)]
+    reload_errors: HashMap<Utf8PathBuf, String>,
+    /// Tab with unsaved edits waiting for the user to confirm closing it.
+    #[serde(skip)]
+    confirm_close: Option<Utf8PathBuf>,
+}
+
+/// Creates the viewer for a file, or for a directory with a dedicated view such as a world.
+fn create_dialog(
+    project: &Project,
+    entries: &[ProjectFilesEntry],
+    path: &Utf8PathBuf,
+    frame: &mut eframe::Frame,
+) -> anyhow::Result<Box<dyn ProjectFileDialog>> {
+    // directories inside archives only exist in the listing
+    match ProjectFilesEntry::find(entries, path) {
+        Some(ProjectFilesEntry::Directory((dir, inner_entries))) => {
+            match get_dir_dialog(dir, inner_entries) {
+                Some(kind) => kind.create_dialog(project, dir, frame),
+                None => anyhow::bail!("there is no viewer for {}", dir),
+            }
+        }
+        _ => open_file_dialog(project, path, frame),
+    }
+}
+
+/// Re-creates an open dialog from the current contents on disk, keeping its camera.
+/// The open dialog stays as it is when that fails.
+fn reload_dialog(
+    project: &Project,
+    entries: &[ProjectFilesEntry],
+    open_files: &mut HashMap<Utf8PathBuf, Box<dyn ProjectFileDialog>>,
+    path: &Utf8PathBuf,
+    frame: &mut eframe::Frame,
+) -> anyhow::Result<()> {
+    let mut dialog = create_dialog(project, entries, path, frame)?;
+    let camera_state = open_files
+        .get_mut(path)
+        .and_then(|dialog| dialog.camera_state());
+    if let Some(camera_state) = camera_state {
+        dialog.set_camera_state(camera_state);
+    }
+    open_files.insert(path.clone(), dialog);
+    Ok(())
+}
+
+/// A full width notice above the active file.
+fn show_banner(ui: &mut egui::Ui, fill: egui::Color32, add_contents: impl FnOnce(&mut egui::Ui)) {
+    egui::Frame::none()
+        .fill(fill)
+        .inner_margin(egui::style::Margin::same(6.0))
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            add_contents(ui);
+        });
 }
 
 impl ProjectDialog {
@@ -27,18 +116,30 @@ impl ProjectDialog {
         Self {
             project,
             files: None,
+            files_loading: None,
             open_files: Default::default(),
             active_file: None,
-            request_open_file: None,
+            request_open: None,
+            session_start: Some(SystemTime::now()),
+            patch_archive_dialog: None,
+            embedded_nifs_dialog: None,
+            quick_open: None,
+            tree: Default::default(),
+            watcher: Default::default(),
+            deleted_files: Default::default(),
+            changed_on_disk: Default::default(),
+            reload_errors: Default::default(),
+            confirm_close: None,
         }
     }
 
+    pub fn open_patch_archive_dialog(&mut self) {
+        let session_start = *self.session_start.get_or_insert_with(SystemTime::now);
+        self.patch_archive_dialog = Some(PatchArchiveDialog::new(&self.project, session_start));
+    }
+
     pub fn request_open_file(&mut self, path: Utf8PathBuf) {
-        let Self { files, project, .. } = self;
-        if files.is_none() {
-            *files = Some(project.enumerate_files());
-        }
-        self.request_open_file = Some(path);
+        self.request_open = Some(OpenRequest::new(path));
     }
 
     pub fn project(&self) -> &Project {
@@ -53,88 +154,256 @@ impl ProjectDialog {
         let Self {
             project,
             files,
+            files_loading,
             open_files,
             active_file,
-            request_open_file,
+            request_open,
+            session_start,
+            patch_archive_dialog,
+            embedded_nifs_dialog,
+            quick_open,
+            tree,
+            watcher,
+            deleted_files,
+            changed_on_disk,
+            reload_errors,
+            confirm_close,
         } = self;
 
-        if let Some(path) = request_open_file.take() {
-            open_files.insert(path.clone(), create_dialog_for_file(&path, frame));
-            *active_file = Some(path);
+        session_start.get_or_insert_with(SystemTime::now);
+
+        if let Some(dialog) = patch_archive_dialog {
+            if !dialog.show(ctx, project) {
+                *patch_archive_dialog = None;
+            }
+        }
+
+        if let Some(dialog) = embedded_nifs_dialog {
+            if !dialog.show(ctx) {
+                *embedded_nifs_dialog = None;
+            }
+        }
+
+        if files.is_none() && files_loading.is_none() {
+            *files_loading = Some(project.enumerate_files_in_background());
+        }
+        if let Some(receiver) = files_loading {
+            match receiver.try_recv() {
+                Ok(entries) => {
+                    *files = Some(entries);
+                    *files_loading = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    ctx.request_repaint_after(Duration::from_millis(100))
+                }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    eprintln!("Listing the project files failed");
+                    files.get_or_insert_with(Vec::new);
+                    *files_loading = None;
+                }
+            }
+        }
+
+        // directory views are picked from the file listing, so wait for it
+        let wait_for_files = matches!(
+            request_open,
+            Some(OpenRequest { path, activate: true, .. }) if !path.is_file() && files.is_none()
+        );
+        if let Some(OpenRequest {
+            path,
+            select,
+            activate,
+        }) = request_open.take().filter(|_| !wait_for_files)
+        {
+            if activate && !open_files.contains_key(&path) {
+                let entries = files.as_deref().unwrap_or_default();
+                match create_dialog(project, entries, &path, frame) {
+                    Ok(dialog) => {
+                        open_files.insert(path.clone(), dialog);
+                    }
+                    Err(err) => eprintln!("Failed to open {}: {:?}", path, err),
+                }
+            }
+            if let Some(dialog) = open_files.get_mut(&path) {
+                if let Some(object) = select {
+                    dialog.select_world_object(&object, frame);
+                }
+                if activate {
+                    *active_file = Some(path);
+                }
+            }
+        }
+
+        watcher.start(project.game_dir(), project.file_lister());
+        watcher.set_watched(open_files.keys());
+        for event in watcher.events() {
+            match event {
+                WatchEvent::Changed(path) => {
+                    deleted_files.remove(&path);
+                    // never throw away unsaved edits without asking
+                    if matches!(open_files.get(&path), Some(dialog) if dialog.is_modified()) {
+                        changed_on_disk.insert(path);
+                        continue;
+                    }
+                    let entries = files.as_deref().unwrap_or_default();
+                    match reload_dialog(project, entries, open_files, &path, frame) {
+                        Ok(()) => {
+                            reload_errors.remove(&path);
+                        }
+                        Err(err) => {
+                            reload_errors.insert(path, format!("{:#}", err));
+                        }
+                    }
+                }
+                WatchEvent::Deleted(path) => {
+                    deleted_files.insert(path);
+                }
+                WatchEvent::DirChanged(dir, dir_entries) => {
+                    let listed = match files {
+                        Some(entries) if dir == project.game_dir() => {
+                            *entries = dir_entries;
+                            true
+                        }
+                        Some(entries) => match ProjectFilesEntry::find_dir_mut(entries, &dir) {
+                            Some(entries) => {
+                                *entries = dir_entries;
+                                true
+                            }
+                            None => false,
+                        },
+                        None => false,
+                    };
+                    if !listed {
+                        // the current listing stays up until the new one is ready
+                        *files_loading = Some(project.enumerate_files_in_background());
+                    }
+                }
+            }
+        }
+        deleted_files.retain(|path| open_files.contains_key(path));
+        changed_on_disk.retain(|path| open_files.contains_key(path));
+        reload_errors.retain(|path, _| open_files.contains_key(path));
+        ctx.request_repaint_after(POLL_INTERVAL);
+
+        let toggle_quick_open = {
+            let input = ctx.input();
+            input.modifiers.command && input.key_pressed(egui::Key::P)
+        };
+        if toggle_quick_open {
+            if quick_open.is_some() {
+                *quick_open = None;
+            } else if let Some(entries) = files {
+                *quick_open = Some(QuickOpenPalette::new(&project.game_dir(), entries));
+            }
         }
 
+        if let Some(palette) = quick_open {
+            match palette.show(ctx) {
+                QuickOpenResult::Chosen(target) => {
+                    *quick_open = None;
+                    let (path, dialog) = match target {
+                        QuickOpenTarget::File(path) => {
+                            let dialog = match open_files.remove(&path) {
+                                Some(dialog) => Ok(dialog),
+                                None => open_file_dialog(project, &path, frame),
+                            };
+                            (path, dialog)
+                        }
+                        QuickOpenTarget::Dir(path, kind) => {
+                            let dialog = match open_files.remove(&path) {
+                                Some(dialog) => Ok(dialog),
+                                None => kind.create_dialog(project, &path, frame),
+                            };
+                            (path, dialog)
+                        }
+                    };
+                    match dialog {
+                        Ok(dialog) => {
+                            open_files.insert(path.clone(), dialog);
+                            *active_file = Some(path);
+                        }
+                        Err(err) => eprintln!("Failed to open {}: {:?}", path, err),
+                    }
+                }
+                QuickOpenResult::Dismissed => *quick_open = None,
+                QuickOpenResult::Idle => {}
+            }
+        }
+
+        let mut tree_actions = Vec::new();
+
         egui::SidePanel::new(egui::panel::Side::Left, "project_panel")
             .min_width(200.0)
             .show(ctx, |ui| {
                 ui.add_space(4.0);
                 ui.label("Project");
                 ui.heading(project.game_dir().file_name().unwrap_or("untitled"));
+                if project.has_custom_encryption_key() {
+                    ui.label(egui::RichText::new("Custom encryption key").weak());
+                }
+                ui.separator();
+                tree.show_filter(ui);
                 ui.separator();
                 egui::containers::ScrollArea::vertical()
                     .auto_shrink([false, true])
-                    .show(ui, |ui| {
-                        if files.is_none() {
-                            *files = Some(project.enumerate_files());
+                    .show(ui, |ui| match files {
+                        Some(entries) => {
+                            tree_actions = tree.show(ui, frame, project, entries, open_files);
                         }
-
-                        let entries = files.as_ref().unwrap();
-                        fn render_entries(
-                            ui: &mut egui::Ui,
-                            frame: &mut eframe::Frame,
-                            entries: &[ProjectFilesEntry],
-                            open_entries: &mut HashMap<Utf8PathBuf, Box<dyn ProjectFileDialog>>,
-                        ) {
-                            for entry in entries {
-                                match entry {
-                                    ProjectFilesEntry::File(file) => {
-                                        let mut is_open = open_entries.contains_key(file);
-                                        let was_open = is_open;
-                                        ui.checkbox(&mut is_open, file.file_name().unwrap());
-                                        if was_open && !is_open {
-                                            open_entries.remove(file);
-                                        } else if !was_open && is_open {
-                                            open_entries.insert(
-                                                file.clone(),
-                                                create_dialog_for_file(file, frame),
-                                            );
-                                        }
-                                    }
-                                    ProjectFilesEntry::Directory((dir, inner_entries)) => {
-                                        ui.collapsing(dir.file_name().unwrap(), |ui| {
-                                            match get_dir_dialog(dir, inner_entries) {
-                                                Some(kind) => {
-                                                    let mut is_open =
-                                                        open_entries.contains_key(dir);
-                                                    let was_open = is_open;
-                                                    ui.checkbox(
-                                                        &mut is_open,
-                                                        format!(
-                                                            "({}) {}",
-                                                            kind,
-                                                            dir.file_name().unwrap()
-                                                        ),
-                                                    );
-                                                    if was_open && !is_open {
-                                                        open_entries.remove(dir);
-                                                    } else if !was_open && is_open {
-                                                        open_entries.insert(
-                                                            dir.clone(),
-                                                            kind.create_dialog(dir, frame),
-                                                        );
-                                                    }
-                                                }
-                                                None => {}
-                                            }
-                                            render_entries(ui, frame, inner_entries, open_entries);
-                                        });
-                                    }
-                                }
-                            }
+                        None => {
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label("Listing files…");
+                            });
                         }
-                        render_entries(ui, frame, entries, open_files);
                     });
             });
 
+        for action in tree_actions {
+            match action {
+                TreeAction::Extract(paths, root) => {
+                    if let Some(target_dir) = prompt_output_directory() {
+                        if let Err(err) = project.extract_archived_files(&paths, &root, &target_dir)
+                        {
+                            eprintln!("Failed to extract files: {:?}", err);
+                        }
+                    }
+                }
+                TreeAction::EmbeddedNifs(path) => match EmbeddedNifsDialog::new(project, path) {
+                    Ok(dialog) => *embedded_nifs_dialog = Some(dialog),
+                    Err(err) => eprintln!("Failed to read embedded NIFs: {:?}", err),
+                },
+                TreeAction::ConfirmClose(path) => *confirm_close = Some(path),
+            }
+        }
+
+        if let Some(path) = confirm_close.clone() {
+            let mut close = None;
+            egui::Window::new("Unsaved changes")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "{} has unsaved changes.",
+                        path.file_name().unwrap_or(path.as_str())
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("Discard and close").clicked() {
+                            close = Some(true);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            close = Some(false);
+                        }
+                    });
+                });
+            if let Some(close) = close {
+                if close {
+                    open_files.remove(&path);
+                }
+                *confirm_close = None;
+            }
+        }
+
         if !open_files.is_empty() {
             // reset active_file in case the first file was just opened or the currently active file was closed
             if active_file.is_none() || !open_files.contains_key(active_file.as_ref().unwrap()) {
@@ -168,11 +437,12 @@ impl ProjectDialog {
                         ui.set_width(ui.available_size_before_wrap().x);
                         ui.horizontal_wrapped(|ui| {
                             for (key, dialog) in open_files.iter_mut() {
+                                let mut title = dialog.title();
+                                if deleted_files.contains(key) {
+                                    title.push_str(" (deleted)");
+                                }
                                 if ui
-                                    .selectable_label(
-                                        active_file.as_ref().unwrap() == key,
-                                        dialog.title(),
-                                    )
+                                    .selectable_label(active_file.as_ref().unwrap() == key, title)
                                     .clicked()
                                 {
                                     *active_file = Some(key.clone());
@@ -192,10 +462,61 @@ impl ProjectDialog {
                     }
                     .show(ui, |ui| {
                         if let Some(active_file) = active_file {
-                            open_files
-                                .get_mut(active_file)
-                                .unwrap()
-                                .show(ctx, ui, frame);
+                            if deleted_files.contains(active_file) {
+                                show_banner(ui, egui::Color32::from_rgb(120, 40, 40), |ui| {
+                                    ui.colored_label(
+                                        egui::Color32::WHITE,
+                                        "⚠ This file was deleted from disk. \
+                                        The view shows its last loaded contents.",
+                                    );
+                                });
+                            }
+                            if let Some(err) = reload_errors.get(active_file) {
+                                show_banner(ui, egui::Color32::from_rgb(120, 40, 40), |ui| {
+                                    ui.colored_label(
+                                        egui::Color32::WHITE,
+                                        format!(
+                                            "⚠ This file changed on disk but could not be \
+                                            reloaded: {}. The view shows its last loaded contents.",
+                                            err
+                                        ),
+                                    );
+                                });
+                            }
+                            if changed_on_disk.contains(active_file) {
+                                let mut reload = false;
+                                show_banner(ui, egui::Color32::from_rgb(120, 90, 30), |ui| {
+                                    ui.colored_label(
+                                        egui::Color32::WHITE,
+                                        "⚠ This file changed on disk while it has unsaved edits.",
+                                    );
+                                    ui.horizontal(|ui| {
+                                        reload = ui.button("Reload and discard changes").clicked();
+                                        if ui.button("Keep my version").clicked() {
+                                            changed_on_disk.remove(active_file);
+                                        }
+                                    });
+                                });
+                                if reload {
+                                    changed_on_disk.remove(active_file);
+                                    let entries = files.as_deref().unwrap_or_default();
+                                    if let Err(err) = reload_dialog(
+                                        project,
+                                        entries,
+                                        open_files,
+                                        active_file,
+                                        frame,
+                                    ) {
+                                        reload_errors
+                                            .insert(active_file.clone(), format!("{:#}", err));
+                                    }
+                                }
+                            }
+                            let dialog = open_files.get_mut(active_file).unwrap();
+                            dialog.show(ctx, ui, frame);
+                            if let Some(request) = dialog.take_open_request() {
+                                *request_open = Some(request);
+                            }
                         }
                     });
                     // let mut closed_dialogs = Vec::new();
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use eframe::egui;
+
+use crate::{
+    containers::ContainerKind,
+    dialogs::files::{get_dir_dialog, open_file_dialog, FileKind, ProjectFileDialog},
+    project::{Project, ProjectFilesEntry},
+};
+
+pub enum TreeAction {
+    /// Extract archived files, keeping their paths relative to the given root.
+    Extract(Vec<Utf8PathBuf>, Utf8PathBuf),
+    /// List the NIFs embedded in an LF, LBF or LOF container.
+    EmbeddedNifs(Utf8PathBuf),
+    /// Close a tab with unsaved edits once the user confirms.
+    ConfirmClose(Utf8PathBuf),
+}
+
+#[derive(Debug, Default)]
+pub struct ProjectTree {
+    filter_text: String,
+    hide_unsupported: bool,
+}
+
+impl ProjectTree {
+    pub fn show_filter(&mut self, ui: &mut egui::Ui) {
+        ui.add(
+            egui::TextEdit::singleline(&mut self.filter_text)
+                .hint_text("Filter (e.g. *.nif .lf terrain)")
+                .desired_width(f32::INFINITY),
+        );
+        ui.checkbox(&mut self.hide_unsupported, "Hide unsupported files");
+    }
+
+    pub fn show(
+        &self,
+        ui: &mut egui::Ui,
+        frame: &mut eframe::Frame,
+        project: &Project,
+        entries: &[ProjectFilesEntry],
+        open_entries: &mut HashMap<Utf8PathBuf, Box<dyn ProjectFileDialog>>,
+    ) -> Vec<TreeAction> {
+        let mut tree_ctx = TreeContext {
+            frame,
+            project,
+            open_entries,
+            filter: TreeFilter::new(&self.filter_text, self.hide_unsupported),
+            actions: Vec::new(),
+        };
+        render_entries(ui, &mut tree_ctx, entries, false);
+        tree_ctx.actions
+    }
+}
+
+struct TreeContext<'a> {
+    frame: &'a mut eframe::Frame,
+    project: &'a Project,
+    open_entries: &'a mut HashMap<Utf8PathBuf, Box<dyn ProjectFileDialog>>,
+    filter: TreeFilter,
+    actions: Vec<TreeAction>,
+}
+
+/// Closes a tab, asking first when it has unsaved edits.
+fn close_entry(tree_ctx: &mut TreeContext, path: &Utf8PathBuf) {
+    match tree_ctx.open_entries.get(path) {
+        Some(dialog) if dialog.is_modified() => tree_ctx
+            .actions
+            .push(TreeAction::ConfirmClose(path.clone())),
+        _ => {
+            tree_ctx.open_entries.remove(path);
+        }
+    }
+}
+
+fn render_entries(
+    ui: &mut egui::Ui,
+    tree_ctx: &mut TreeContext,
+    entries: &[ProjectFilesEntry],
+    in_archive: bool,
+) {
+    for entry in entries {
+        if !tree_ctx.filter.is_visible(entry) {
+            continue;
+        }
+
+        match entry {
+            ProjectFilesEntry::File((file, size)) => {
+                let kind = FileKind::from_path(file);
+                let mut is_open = tree_ctx.open_entries.contains_key(file);
+                let was_open = is_open;
+                let response = ui
+                    .horizontal(|ui| {
+                        let response = ui.checkbox(
+                            &mut is_open,
+                            format!("{} {}", kind.icon(), file.file_name().unwrap()),
+                        );
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.label(egui::RichText::new(format_size(*size)).weak());
+                        });
+                        response
+                    })
+                    .inner;
+                let is_container = ContainerKind::from_path(file).is_some();
+                if in_archive || is_container {
+                    response.context_menu(|ui| {
+                        if in_archive && ui.button("Extract to…").clicked() {
+                            tree_ctx.actions.push(TreeAction::Extract(
+                                vec![file.clone()],
+                                file.parent().unwrap().to_owned(),
+                            ));
+                            ui.close_menu();
+                        }
+                        if is_container && ui.button("Embedded NIFs…").clicked() {
+                            tree_ctx
+                                .actions
+                                .push(TreeAction::EmbeddedNifs(file.clone()));
+                            ui.close_menu();
+                        }
+                    });
+                }
+                if was_open && !is_open {
+                    close_entry(tree_ctx, file);
+                } else if !was_open && is_open {
+                    match open_file_dialog(tree_ctx.project, file, tree_ctx.frame) {
+                        Ok(dialog) => {
+                            tree_ctx.open_entries.insert(file.clone(), dialog);
+                        }
+                        Err(err) => eprintln!("Failed to open {}: {:?}", file, err),
+                    }
+                }
+            }
+            ProjectFilesEntry::Directory((dir, inner_entries)) => {
+                let dir_kind = get_dir_dialog(dir, inner_entries);
+                let header_text = match dir_kind {
+                    Some(kind) => format!("🌐 {} ({})", dir.file_name().unwrap(), kind),
+                    None => format!("🗀 {}", dir.file_name().unwrap()),
+                };
+                let response =
+                    directory_header(header_text, dir, &tree_ctx.filter).show(ui, |ui| {
+                        if let Some(kind) = dir_kind {
+                            let mut is_open = tree_ctx.open_entries.contains_key(dir);
+                            let was_open = is_open;
+                            ui.checkbox(
+                                &mut is_open,
+                                format!("({}) {}", kind, dir.file_name().unwrap()),
+                            );
+                            if was_open && !is_open {
+                                close_entry(tree_ctx, dir);
+                            } else if !was_open && is_open {
+                                match kind.create_dialog(tree_ctx.project, dir, tree_ctx.frame) {
+                                    Ok(dialog) => {
+                                        tree_ctx.open_entries.insert(dir.clone(), dialog);
+                                    }
+                                    Err(err) => eprintln!("Failed to open {}: {:?}", dir, err),
+                                }
+                            }
+                        }
+                        render_entries(ui, tree_ctx, inner_entries, in_archive);
+                    });
+                if in_archive {
+                    response.header_response.context_menu(|ui| {
+                        if ui.button("Extract to…").clicked() {
+                            tree_ctx.actions.push(TreeAction::Extract(
+                                entry.file_paths(),
+                                dir.parent().unwrap().to_owned(),
+                            ));
+                            ui.close_menu();
+                        }
+                    });
+                }
+            }
+            ProjectFilesEntry::Archive((archive, inner_entries)) => {
+                let header_text = format!(
+                    "{} {}",
+                    FileKind::Archive.icon(),
+                    archive.file_name().unwrap()
+                );
+                let response =
+                    directory_header(header_text, archive, &tree_ctx.filter).show(ui, |ui| {
+                        render_entries(ui, tree_ctx, inner_entries, true);
+                    });
+                response.header_response.context_menu(|ui| {
+                    if ui.button("Extract all to…").clicked() {
+                        tree_ctx
+                            .actions
+                            .push(TreeAction::Extract(entry.file_paths(), archive.clone()));
+                        ui.close_menu();
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Header for a directory or archive. While searching, headers keep a separate open state
+/// that starts expanded so matches are not hidden in collapsed directories, and the state from
+/// before the search is back once the filter is cleared.
+fn directory_header(text: String, path: &Utf8Path, filter: &TreeFilter) -> egui::CollapsingHeader {
+    let header = egui::CollapsingHeader::new(text);
+    if filter.terms.is_empty() {
+        header.id_source(path)
+    } else {
+        header.id_source((path, "filtered")).default_open(true)
+    }
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+enum FilterTerm {
+    /// `.nif`, matches by extension
+    Extension(String),
+    /// `*.nif`, `terrain?.lf`, matches the whole file name
+    Glob(String),
+    /// anything else, matches part of the file name
+    Substring(String),
+}
+
+struct TreeFilter {
+    terms: Vec<FilterTerm>,
+    hide_unsupported: bool,
+}
+
+impl TreeFilter {
+    fn new(text: &str, hide_unsupported: bool) -> Self {
+        let terms = text
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|term| !term.is_empty())
+            .map(|term| {
+                let term = term.to_lowercase();
+                if term.contains('*') || term.contains('?') {
+                    FilterTerm::Glob(term)
+                } else if let Some(extension) = term.strip_prefix('.') {
+                    FilterTerm::Extension(extension.to_string())
+                } else {
+                    FilterTerm::Substring(term)
+                }
+            })
+            .collect();
+        Self {
+            terms,
+            hide_unsupported,
+        }
+    }
+
+    fn matches_file(&self, path: &Utf8Path) -> bool {
+        if self.hide_unsupported && !FileKind::from_path(path).is_supported() {
+            return false;
+        }
+        if self.terms.is_empty() {
+            return true;
+        }
+
+        let file_name = path.file_name().unwrap_or_default().to_lowercase();
+        let extension = path.extension().unwrap_or_default().to_lowercase();
+        self.terms.iter().any(|term| match term {
+            FilterTerm::Extension(ext) => extension == *ext,
+            FilterTerm::Glob(pattern) => glob_match(pattern, &file_name),
+            FilterTerm::Substring(text) => file_name.contains(text.as_str()),
+        })
+    }
+
+    fn is_visible(&self, entry: &ProjectFilesEntry) -> bool {
+        if self.terms.is_empty() && !self.hide_unsupported {
+            return true;
+        }
+        match entry {
+            ProjectFilesEntry::File((path, _)) => self.matches_file(path),
+            ProjectFilesEntry::Directory((dir, entries)) => {
+                let is_world = get_dir_dialog(dir, entries).is_some();
+                (is_world && self.terms.is_empty()) || entries.iter().any(|e| self.is_visible(e))
+            }
+            ProjectFilesEntry::Archive((_, entries)) => entries.iter().any(|e| self.is_visible(e)),
+        }
+    }
+}
+
+/// Matches `text` against a pattern where `*` matches any run of characters and `?` any one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_matches(filter: &str, path: &str) -> bool {
+        TreeFilter::new(filter, false).matches_file(Utf8Path::new(path))
+    }
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(glob_match("*.nif", "model.nif"));
+        assert!(glob_match("*.nif", ".nif"));
+        assert!(!glob_match("*.nif", "model.nif.bak"));
+        assert!(glob_match("a*b*c", "axbybzc"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(glob_match("block_?.nif", "block_1.nif"));
+        assert!(!glob_match("block_?.nif", "block_12.nif"));
+        assert!(!glob_match("block_?.nif", "block_.nif"));
+    }
+
+    #[test]
+    fn extension_terms_match_the_whole_extension() {
+        assert!(filter_matches(".lf", "data/terrain0.lf"));
+        assert!(!filter_matches(".lf", "data/terrain0.lif"));
+        assert!(!filter_matches(".lf", "data/lf"));
+    }
+
+    #[test]
+    fn terms_ignore_case() {
+        assert!(filter_matches("TERRAIN*.LF", "Data/terrain0.lf"));
+        assert!(filter_matches(".LOF", "Data/modeltable0.LOF"));
+        assert!(filter_matches("Block", "data/BLOCKOBJ0.LBF"));
+    }
+}
@@ -0,0 +1,313 @@
+use camino::Utf8PathBuf;
+use eframe::egui;
+
+use crate::{
+    dialogs::files::{get_dir_dialog, DirDialogKind},
+    project::ProjectFilesEntry,
+};
+
+const MAX_RESULTS: usize = 50;
+
+pub enum QuickOpenResult {
+    Chosen(QuickOpenTarget),
+    Dismissed,
+    Idle,
+}
+
+#[derive(Debug, Clone)]
+pub enum QuickOpenTarget {
+    File(Utf8PathBuf),
+    Dir(Utf8PathBuf, DirDialogKind),
+}
+
+#[derive(Debug)]
+struct Candidate {
+    label: String,
+    target: QuickOpenTarget,
+}
+
+#[derive(Debug)]
+pub struct QuickOpenPalette {
+    query: String,
+    candidates: Vec<Candidate>,
+    results: Vec<(usize, Vec<usize>)>,
+    selected: usize,
+}
+
+impl QuickOpenPalette {
+    pub fn new(game_dir: &Utf8PathBuf, entries: &[ProjectFilesEntry]) -> Self {
+        fn collect(
+            game_dir: &Utf8PathBuf,
+            entries: &[ProjectFilesEntry],
+            candidates: &mut Vec<Candidate>,
+        ) {
+            for entry in entries {
+                match entry {
+                    ProjectFilesEntry::File((file, _)) => candidates.push(Candidate {
+                        label: relative_label(game_dir, file),
+                        target: QuickOpenTarget::File(file.clone()),
+                    }),
+                    ProjectFilesEntry::Directory((dir, inner_entries)) => {
+                        if let Some(kind) = get_dir_dialog(dir, inner_entries) {
+                            candidates.push(Candidate {
+                                label: format!("{} ({})", relative_label(game_dir, dir), kind),
+                                target: QuickOpenTarget::Dir(dir.clone(), kind),
+                            });
+                        }
+                        collect(game_dir, inner_entries, candidates);
+                    }
+                    ProjectFilesEntry::Archive((_, inner_entries)) => {
+                        collect(game_dir, inner_entries, candidates);
+                    }
+                }
+            }
+        }
+
+        let mut candidates = Vec::new();
+        collect(game_dir, entries, &mut candidates);
+
+        let mut palette = Self {
+            query: String::new(),
+            candidates,
+            results: Vec::new(),
+            selected: 0,
+        };
+        palette.update_results();
+        palette
+    }
+
+    fn update_results(&mut self) {
+        let mut scored = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, candidate)| {
+                fuzzy_match(&self.query, &candidate.label)
+                    .map(|(score, matched)| (score, idx, matched))
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| {
+            b.0.cmp(&a.0).then_with(|| {
+                let a_len = self.candidates[a.1].label.len();
+                let b_len = self.candidates[b.1].label.len();
+                a_len.cmp(&b_len)
+            })
+        });
+        self.results = scored
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, idx, matched)| (idx, matched))
+            .collect();
+        self.selected = 0;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> QuickOpenResult {
+        let (escape, enter, up, down) = {
+            let input = ctx.input();
+            (
+                input.key_pressed(egui::Key::Escape),
+                input.key_pressed(egui::Key::Enter),
+                input.key_pressed(egui::Key::ArrowUp),
+                input.key_pressed(egui::Key::ArrowDown),
+            )
+        };
+        if escape {
+            return QuickOpenResult::Dismissed;
+        }
+        if down && self.selected + 1 < self.results.len() {
+            self.selected += 1;
+        }
+        if up && self.selected > 0 {
+            self.selected -= 1;
+        }
+
+        let mut chosen = None;
+        if enter {
+            chosen = self.results.get(self.selected).map(|r| r.0);
+        }
+
+        egui::Window::new("Quick Open")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 48.0))
+            .fixed_size(egui::vec2(480.0, 0.0))
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("Search files by name…")
+                        .desired_width(f32::INFINITY),
+                );
+                response.request_focus();
+                if response.changed() {
+                    self.update_results();
+                }
+
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        if self.results.is_empty() {
+                            ui.label(egui::RichText::new("No matching files").weak());
+                        }
+                        for (result_idx, (candidate_idx, matched)) in
+                            self.results.iter().enumerate()
+                        {
+                            let candidate = &self.candidates[*candidate_idx];
+                            let is_selected = result_idx == self.selected;
+                            let job = highlighted_label(ui, &candidate.label, matched);
+                            let response = ui.selectable_label(is_selected, job);
+                            if is_selected && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                chosen = Some(*candidate_idx);
+                            }
+                        }
+                    });
+            });
+
+        match chosen {
+            Some(idx) => QuickOpenResult::Chosen(self.candidates[idx].target.clone()),
+            None => QuickOpenResult::Idle,
+        }
+    }
+}
+
+fn relative_label(game_dir: &Utf8PathBuf, path: &Utf8PathBuf) -> String {
+    path.strip_prefix(game_dir)
+        .unwrap_or(path)
+        .as_str()
+        .replace('\\', "/")
+}
+
+fn highlighted_label(ui: &egui::Ui, label: &str, matched: &[usize]) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Button.resolve(ui.style());
+    let normal_color = ui.visuals().text_color();
+    let matched_color = ui.visuals().strong_text_color();
+
+    let mut job = egui::text::LayoutJob::default();
+    for (idx, c) in label.char_indices() {
+        let color = if matched.contains(&idx) {
+            matched_color
+        } else {
+            normal_color
+        };
+        let format = egui::TextFormat {
+            font_id: font_id.clone(),
+            color,
+            underline: if matched.contains(&idx) {
+                egui::Stroke::new(1.0, matched_color)
+            } else {
+                egui::Stroke::none()
+            },
+            ..Default::default()
+        };
+        job.append(&label[idx..idx + c.len_utf8()], 0.0, format);
+    }
+    job
+}
+
+/// Case-insensitive subsequence match of `query` against `text`.
+///
+/// Returns a score (higher is better) and the byte indices of the matched characters.
+/// Consecutive matches, matches at the start of a path segment or word, and matches
+/// in the file name score higher.
+fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let query = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let file_name_start = text.rfind('/').map(|idx| idx + 1).unwrap_or(0);
+
+    let mut score = 0i64;
+    let mut matched = Vec::with_capacity(query.len());
+    let mut query_idx = 0;
+    let mut prev_matched_idx: Option<usize> = None;
+    let mut prev_char: Option<char> = None;
+
+    for (idx, c) in text.char_indices() {
+        if query_idx == query.len() {
+            break;
+        }
+        if c.to_lowercase().eq(std::iter::once(query[query_idx])) {
+            let mut char_score = 1;
+            if let Some(prev_idx) = prev_matched_idx {
+                let prev_len = text[prev_idx..].chars().next().unwrap().len_utf8();
+                if prev_idx + prev_len == idx {
+                    char_score += 5;
+                }
+            }
+            match prev_char {
+                None | Some('/') | Some('\\') => char_score += 8,
+                Some('_') | Some('-') | Some('.') | Some(' ') => char_score += 4,
+                Some(prev) if prev.is_lowercase() && c.is_uppercase() => char_score += 4,
+                _ => {}
+            }
+            if idx >= file_name_start {
+                char_score += 2;
+            }
+            score += char_score;
+            matched.push(idx);
+            prev_matched_idx = Some(idx);
+            query_idx += 1;
+        }
+        prev_char = Some(c);
+    }
+
+    if query_idx < query.len() {
+        return None;
+    }
+
+    // prefer shorter, tighter matches
+    let span = matched.last().unwrap() - matched.first().unwrap();
+    score -= (span / 4) as i64;
+
+    Some((score, matched))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(query: &str, text: &str) -> i64 {
+        fuzzy_match(query, text).unwrap().0
+    }
+
+    #[test]
+    fn query_matches_as_a_subsequence() {
+        let text = "data/modeltable0.LOF";
+        let (_, matched) = fuzzy_match("ml lof", text).unwrap();
+        let chars = matched
+            .iter()
+            .map(|&idx| text[idx..].chars().next().unwrap().to_ascii_lowercase())
+            .collect::<String>();
+        assert_eq!(chars, "mllof");
+        assert_eq!(fuzzy_match("", text), Some((0, Vec::new())));
+    }
+
+    #[test]
+    fn missing_or_reordered_characters_do_not_match() {
+        assert_eq!(fuzzy_match("xyz", "terrain0.lf"), None);
+        assert_eq!(fuzzy_match("fl", "terrain0.lf"), None);
+    }
+
+    #[test]
+    fn consecutive_characters_rank_higher() {
+        assert!(score("ter", "terrain0.lf") > score("ter", "txexr.lf"));
+    }
+
+    #[test]
+    fn word_boundaries_and_file_names_rank_higher() {
+        assert!(score("m", "dir/model.nif") > score("m", "dir/amodel.nif"));
+        assert!(score("ob", "a_obj.nif") > score("ob", "aobj.nif"));
+        assert!(score("ab", "x/ab.nif") > score("ab", "ab/x.nif"));
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{
+    archive,
+    containers::ContainerKind,
+    project::{FileLister, ProjectFilesEntry},
+};
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const TREE_SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum WatchEvent {
+    Changed(Utf8PathBuf),
+    Deleted(Utf8PathBuf),
+    /// Files were added to or removed from a directory, with its new listing.
+    DirChanged(Utf8PathBuf, Vec<ProjectFilesEntry>),
+}
+
+/// Polls modification times of open files and the project directory tree on a background
+/// thread, which stops once the watcher is dropped.
+#[derive(Debug, Default)]
+pub struct FileWatcher {
+    watched: Arc<Mutex<Vec<Utf8PathBuf>>>,
+    events: Option<mpsc::Receiver<WatchEvent>>,
+}
+
+impl FileWatcher {
+    /// Starts watching below `game_dir`, unless already started.
+    pub fn start(&mut self, game_dir: Utf8PathBuf, lister: FileLister) {
+        if self.events.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let watched = self.watched.clone();
+        std::thread::spawn(move || watch(game_dir, lister, watched, sender));
+        self.events = Some(receiver);
+    }
+
+    /// Sets the files to report changes for. Paths seen for the first time are only recorded.
+    pub fn set_watched<'a>(&self, paths: impl Iterator<Item = &'a Utf8PathBuf>) {
+        *self.watched.lock().unwrap() = paths.cloned().collect();
+    }
+
+    /// Changes found since the last call.
+    pub fn events(&self) -> Vec<WatchEvent> {
+        match &self.events {
+            Some(events) => events.try_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn watch(
+    game_dir: Utf8PathBuf,
+    lister: FileLister,
+    watched: Arc<Mutex<Vec<Utf8PathBuf>>>,
+    sender: mpsc::Sender<WatchEvent>,
+) {
+    let mut stamps: HashMap<Utf8PathBuf, Option<SystemTime>> = HashMap::new();
+    let mut signatures = HashMap::new();
+    dir_signatures(&game_dir, &mut signatures);
+    let mut last_tree_scan = Instant::now();
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        // the watcher holds the only other reference
+        if Arc::strong_count(&watched) == 1 {
+            return;
+        }
+
+        let paths = watched.lock().unwrap().clone();
+        let mut events = Vec::new();
+        let mut new_stamps = HashMap::new();
+        for path in paths {
+            let stamp = modified_stamp(&path);
+            match stamps.get(&path) {
+                Some(previous) if *previous != stamp => match stamp {
+                    Some(_) => events.push(WatchEvent::Changed(path.clone())),
+                    None => events.push(WatchEvent::Deleted(path.clone())),
+                },
+                _ => {}
+            }
+            new_stamps.insert(path, stamp);
+        }
+        stamps = new_stamps;
+
+        if last_tree_scan.elapsed() >= TREE_SCAN_INTERVAL {
+            last_tree_scan = Instant::now();
+            let mut new_signatures = HashMap::new();
+            dir_signatures(&game_dir, &mut new_signatures);
+            for dir in changed_dirs(&signatures, &new_signatures) {
+                let entries = lister.list(&dir);
+                events.push(WatchEvent::DirChanged(dir, entries));
+            }
+            signatures = new_signatures;
+        }
+
+        for event in events {
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Hashes the names in every directory below `dir`, and the modification times of archives
+/// since their contents show up in the tree.
+fn dir_signatures(dir: &Utf8Path, signatures: &mut HashMap<Utf8PathBuf, u64>) {
+    let mut entries = match dir.read_dir_utf8() {
+        Ok(entries) => entries.flatten().collect::<Vec<_>>(),
+        Err(_) => return,
+    };
+    entries.sort_by(|a, b| a.path().cmp(b.path()));
+
+    let mut hasher = DefaultHasher::new();
+    for entry in entries {
+        let path = entry.path();
+        path.hash(&mut hasher);
+        if path.is_dir() {
+            dir_signatures(path, signatures);
+        } else if archive::is_archive_path(path) {
+            modified_stamp(path).hash(&mut hasher);
+        }
+    }
+    signatures.insert(dir.to_owned(), hasher.finish());
+}
+
+/// Directories whose entries differ, leaving out those below another changed directory since
+/// that one is listed again as a whole.
+fn changed_dirs(
+    old: &HashMap<Utf8PathBuf, u64>,
+    new: &HashMap<Utf8PathBuf, u64>,
+) -> Vec<Utf8PathBuf> {
+    let changed = new
+        .iter()
+        .filter(|(dir, signature)| old.get(*dir) != Some(signature))
+        .map(|(dir, _)| dir)
+        .collect::<Vec<_>>();
+    let mut result = changed
+        .iter()
+        .filter(|dir| {
+            !changed
+                .iter()
+                .any(|other| other != *dir && dir.starts_with(other))
+        })
+        .map(|dir| (*dir).clone())
+        .collect::<Vec<_>>();
+    result.sort();
+    result
+}
+
+/// Modification time of a file, of the archive or container holding a virtual path,
+/// or the newest modification time of the files in a directory.
+fn modified_stamp(path: &Utf8Path) -> Option<SystemTime> {
+    if path.is_dir() {
+        let mut newest = None;
+        for entry in path.read_dir_utf8().ok()?.flatten() {
+            newest = newest.max(modified_stamp(entry.path()));
+        }
+        return newest.or(Some(SystemTime::UNIX_EPOCH));
+    }
+    if let Ok(metadata) = std::fs::metadata(path) {
+        return metadata.modified().ok();
+    }
+    if let Some(container_path) = path
+        .parent()
+        .filter(|parent| ContainerKind::from_path(parent).is_some())
+    {
+        return modified_stamp(container_path);
+    }
+    archive::split_archive_path(path).and_then(|(archive_path, _)| modified_stamp(archive_path))
+}
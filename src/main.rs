@@ -3,21 +3,37 @@ use std::str::FromStr;
 use camino::Utf8PathBuf;
 
 mod app;
+mod archive;
+mod containers;
 mod dialogs;
+mod file_watcher;
+mod nif_writer;
 mod project;
 mod storage;
 mod widgets;
+mod world_data;
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let [flag, dir] = args.as_slice() {
+        if flag == "--verify-nif-roundtrip" {
+            if let Err(err) = nif_writer::verify_roundtrip_dir(dir.as_str().into()) {
+                eprintln!("Failed to verify NIF round-trip: {:?}", err);
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+
     let native_options = eframe::NativeOptions {
         initial_window_size: Some(eframe::egui::vec2(800.0, 600.0)),
         renderer: eframe::Renderer::Wgpu,
         depth_buffer: 32,
         ..Default::default()
     };
-    let quick_open_path = std::env::args()
-        .skip(1)
-        .flat_map(|s| Utf8PathBuf::from_str(&s).ok())
+    let quick_open_path = args
+        .iter()
+        .flat_map(|s| Utf8PathBuf::from_str(s).ok())
         .next();
     eframe::run_native(
         "slidetown",
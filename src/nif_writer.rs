@@ -0,0 +1,640 @@
+//! Serializer for the subset of NIF blocks the viewer can edit.
+//!
+//! The `nif` crate only reads files, so this writes the NetImmerse 4.0.0.2 layout used by the
+//! game's models: a text header, the block count, every block prefixed by its type name and a
+//! footer listing the root block. Booleans are 32 bits wide in this version.
+//!
+//! Fields the parsed blocks do not keep, such as velocities, bounding boxes and match groups,
+//! are written empty. A file is only safe to save once `check_roundtrip` shows that writing its
+//! unedited blocks reproduces it byte for byte.
+
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Write},
+};
+
+use camino::Utf8Path;
+use nif::{
+    blocks::{
+        Block, KeyGroup, KeyType, NiAvObject, NiLight, NiNode, NiObjectNET, NiTimeController,
+        TexDesc,
+    },
+    common::{BlockRef, Color3, Color4, Matrix22, Matrix33, Quaternion, Vector3},
+    Nif,
+};
+
+const HEADER: &[u8] = b"NetImmerse File Format, Version 4.0.0.2\n";
+const VERSION: u32 = 0x04000002;
+
+/// Whether there is a serializer for the block's type.
+pub fn is_writable(block: &Block) -> bool {
+    matches!(
+        block,
+        Block::NiNode(_)
+            | Block::NiLODNode(_)
+            | Block::NiTriShape(_)
+            | Block::NiTriShapeData(_)
+            | Block::NiMaterialProperty(_)
+            | Block::NiAlphaProperty(_)
+            | Block::NiTexturingProperty(_)
+            | Block::NiSourceTexture(_)
+            | Block::NiKeyframeController(_)
+            | Block::NiKeyframeData(_)
+            | Block::NiDirectionalLight(_)
+            | Block::NiAmbientLight(_)
+            | Block::NiPointLight(_)
+            | Block::NiSkinInstance(_)
+    )
+}
+
+/// Blocks that `write_nif` cannot serialize yet.
+pub fn unwritable_blocks(nif: &Nif) -> Vec<(BlockRef, &'static str)> {
+    nif.blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| !is_writable(block))
+        .map(|(idx, block)| (BlockRef(idx as i32), block.name()))
+        .collect()
+}
+
+pub fn write_nif<W: Write>(writer: &mut W, nif: &Nif) -> anyhow::Result<()> {
+    if let Some((block_ref, name)) = unwritable_blocks(nif).first() {
+        anyhow::bail!("block {} ({}) cannot be written yet", block_ref.0, name);
+    }
+
+    let mut writer = NifWriter { writer };
+    writer.bytes(HEADER)?;
+    writer.u32(VERSION)?;
+    writer.u32(nif.blocks.len() as u32)?;
+    for block in nif.blocks.iter() {
+        writer.string(block.name())?;
+        writer.block(block)?;
+    }
+
+    // footer: the root node
+    writer.u32(1)?;
+    writer.block_ref(BlockRef(0))?;
+    Ok(())
+}
+
+pub fn write_nif_to_vec(nif: &Nif) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    write_nif(&mut buf, nif)?;
+    Ok(buf)
+}
+
+/// Checks that writing `nif`, parsed from `original`, gives back `original` unchanged.
+pub fn check_roundtrip(original: &[u8], nif: &Nif) -> anyhow::Result<()> {
+    let mut unwritable_types = unwritable_blocks(nif)
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect::<Vec<_>>();
+    if !unwritable_types.is_empty() {
+        unwritable_types.sort_unstable();
+        unwritable_types.dedup();
+        anyhow::bail!("unsupported block types: {}", unwritable_types.join(", "));
+    }
+
+    let written = write_nif_to_vec(nif)?;
+    if written != original {
+        let offset = written
+            .iter()
+            .zip(original.iter())
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| written.len().min(original.len()));
+        anyhow::bail!(
+            "writing the file back changes it from {:#x} on ({} bytes written, {} original)",
+            offset,
+            written.len(),
+            original.len()
+        );
+    }
+    Ok(())
+}
+
+struct NifWriter<'a, W: Write> {
+    writer: &'a mut W,
+}
+
+impl<'a, W: Write> NifWriter<'a, W> {
+    fn bytes(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+
+    fn u8(&mut self, value: u8) -> anyhow::Result<()> {
+        self.bytes(&[value])
+    }
+
+    fn u16(&mut self, value: u16) -> anyhow::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn i16(&mut self, value: i16) -> anyhow::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn u32(&mut self, value: u32) -> anyhow::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn f32(&mut self, value: f32) -> anyhow::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn bool(&mut self, value: bool) -> anyhow::Result<()> {
+        self.u32(value as u32)
+    }
+
+    fn string(&mut self, value: &str) -> anyhow::Result<()> {
+        self.u32(value.len() as u32)?;
+        self.bytes(value.as_bytes())
+    }
+
+    fn block_ref(&mut self, value: BlockRef) -> anyhow::Result<()> {
+        self.bytes(&value.0.to_le_bytes())
+    }
+
+    fn block_refs(&mut self, values: &[BlockRef]) -> anyhow::Result<()> {
+        self.u32(values.len() as u32)?;
+        for value in values {
+            self.block_ref(*value)?;
+        }
+        Ok(())
+    }
+
+    fn vector3(&mut self, value: &Vector3) -> anyhow::Result<()> {
+        self.f32(value.x)?;
+        self.f32(value.y)?;
+        self.f32(value.z)
+    }
+
+    fn matrix33(&mut self, value: &Matrix33) -> anyhow::Result<()> {
+        for component in [
+            value.m11, value.m12, value.m13, value.m21, value.m22, value.m23, value.m31, value.m32,
+            value.m33,
+        ] {
+            self.f32(component)?;
+        }
+        Ok(())
+    }
+
+    fn matrix22(&mut self, value: &Matrix22) -> anyhow::Result<()> {
+        for component in [value.m11, value.m12, value.m21, value.m22] {
+            self.f32(component)?;
+        }
+        Ok(())
+    }
+
+    fn quaternion(&mut self, value: &Quaternion) -> anyhow::Result<()> {
+        self.f32(value.w)?;
+        self.f32(value.x)?;
+        self.f32(value.y)?;
+        self.f32(value.z)
+    }
+
+    fn color3(&mut self, value: &Color3) -> anyhow::Result<()> {
+        self.f32(value.r)?;
+        self.f32(value.g)?;
+        self.f32(value.b)
+    }
+
+    fn color4(&mut self, value: &Color4) -> anyhow::Result<()> {
+        self.f32(value.r)?;
+        self.f32(value.g)?;
+        self.f32(value.b)?;
+        self.f32(value.a)
+    }
+
+    fn key_type(&mut self, value: KeyType) -> anyhow::Result<()> {
+        self.u32(match value {
+            KeyType::Linear => 1,
+            KeyType::Quadratic => 2,
+            KeyType::Tbc => 3,
+            KeyType::XyzRotation => 4,
+            KeyType::Constant => 5,
+        })
+    }
+
+    fn object_net(&mut self, value: &NiObjectNET) -> anyhow::Result<()> {
+        self.string(&value.name)?;
+        // this version only has a single extra data slot
+        if value.extra_data_refs.len() > 1 {
+            anyhow::bail!("{} has more than one extra data block", value.name);
+        }
+        self.block_ref(
+            value
+                .extra_data_refs
+                .first()
+                .copied()
+                .unwrap_or(BlockRef(-1)),
+        )?;
+        self.block_ref(value.controller_ref)
+    }
+
+    fn av_object(&mut self, value: &NiAvObject) -> anyhow::Result<()> {
+        self.object_net(&value.base)?;
+        self.u16(value.flags)?;
+        self.vector3(&value.translation)?;
+        self.matrix33(&value.rotation)?;
+        self.f32(value.scale)?;
+        // velocity
+        self.vector3(&Vector3::default())?;
+        self.block_refs(&value.property_refs)?;
+        // has bounding box
+        self.bool(false)
+    }
+
+    fn node(&mut self, value: &NiNode) -> anyhow::Result<()> {
+        self.av_object(&value.base)?;
+        self.block_refs(&value.child_refs)?;
+        self.block_refs(&value.effect_refs)
+    }
+
+    /// A texture slot of NiTexturingProperty, a flag followed by the texture when it is set.
+    fn tex_desc(&mut self, value: Option<&TexDesc>) -> anyhow::Result<()> {
+        self.bool(value.is_some())?;
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        self.block_ref(value.source_ref)?;
+        self.u32(value.clamp_mode)?;
+        self.u32(value.filter_mode)?;
+        self.u32(value.uv_set)?;
+        self.i16(value.ps2_l)?;
+        self.i16(value.ps2_k)?;
+        self.u16(value.unknown1)
+    }
+
+    fn time_controller(&mut self, value: &NiTimeController) -> anyhow::Result<()> {
+        self.block_ref(value.next_controller_ref)?;
+        self.u16(value.flags)?;
+        self.f32(value.frequency)?;
+        self.f32(value.phase)?;
+        self.f32(value.start_time)?;
+        self.f32(value.stop_time)?;
+        self.block_ref(value.target_ref)
+    }
+
+    fn light(&mut self, value: &NiLight) -> anyhow::Result<()> {
+        self.av_object(&value.base)?;
+        // affected nodes
+        self.u32(0)?;
+        self.f32(value.dimmer)?;
+        self.color3(&value.ambient_color)?;
+        self.color3(&value.diffuse_color)?;
+        self.color3(&value.specular_color)
+    }
+
+    fn key_group<T>(
+        &mut self,
+        group: &KeyGroup<T>,
+        mut write_value: impl FnMut(&mut Self, &T) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        self.u32(group.keys.len() as u32)?;
+        if group.keys.is_empty() {
+            return Ok(());
+        }
+        self.key_type(group.interpolation)?;
+        for key in group.keys.iter() {
+            self.f32(key.time)?;
+            write_value(self, &key.value)?;
+            match group.interpolation {
+                KeyType::Quadratic => {
+                    write_value(self, &key.forward)?;
+                    write_value(self, &key.backward)?;
+                }
+                KeyType::Tbc => {
+                    self.f32(key.tbc.t)?;
+                    self.f32(key.tbc.b)?;
+                    self.f32(key.tbc.c)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, block: &Block) -> anyhow::Result<()> {
+        match block {
+            Block::NiNode(block) => self.node(block)?,
+            Block::NiLODNode(block) => {
+                self.node(&block.base.base)?;
+                self.u32(block.base.index)?;
+                self.vector3(&block.lod_center)?;
+                self.u32(block.lod_levels.len() as u32)?;
+                for level in block.lod_levels.iter() {
+                    self.f32(level.near_extent)?;
+                    self.f32(level.far_extent)?;
+                }
+            }
+            Block::NiTriShape(block) => {
+                self.av_object(&block.base)?;
+                self.block_ref(block.data_ref)?;
+                self.block_ref(block.skin_instance_ref)?;
+            }
+            Block::NiTriShapeData(block) => {
+                let data = &block.base;
+                self.u16(data.vertices.len() as u16)?;
+                self.bool(!data.vertices.is_empty())?;
+                for vertex in data.vertices.iter() {
+                    self.vector3(vertex)?;
+                }
+                self.bool(!data.normals.is_empty())?;
+                for normal in data.normals.iter() {
+                    self.vector3(normal)?;
+                }
+                self.vector3(&data.center)?;
+                self.f32(data.radius)?;
+                self.bool(!data.vertex_colors.is_empty())?;
+                for color in data.vertex_colors.iter() {
+                    self.color4(color)?;
+                }
+                self.u16(data.uv_sets.len() as u16)?;
+                self.bool(!data.uv_sets.is_empty())?;
+                for uv in data.uv_sets.iter().flatten() {
+                    self.f32(uv.x)?;
+                    self.f32(uv.y)?;
+                }
+                self.u16(block.triangles.len() as u16)?;
+                self.u32(block.triangles.len() as u32 * 3)?;
+                for triangle in block.triangles.iter() {
+                    self.u16(triangle.v1)?;
+                    self.u16(triangle.v2)?;
+                    self.u16(triangle.v3)?;
+                }
+                // match groups
+                self.u16(0)?;
+            }
+            Block::NiMaterialProperty(block) => {
+                self.object_net(&block.base)?;
+                self.u16(block.flags)?;
+                self.color3(&block.ambient_color)?;
+                self.color3(&block.diffuse_color)?;
+                self.color3(&block.specular_color)?;
+                self.color3(&block.emissive_color)?;
+                self.f32(block.glossiness)?;
+                self.f32(block.alpha)?;
+            }
+            Block::NiAlphaProperty(block) => {
+                self.object_net(&block.base)?;
+                self.u16(block.flags)?;
+                self.u8(block.threshold)?;
+            }
+            Block::NiTexturingProperty(block) => {
+                self.object_net(&block.base)?;
+                self.u16(block.flags)?;
+                self.u32(block.apply_mode)?;
+                self.u32(block.texture_count)?;
+                for texture in [
+                    &block.base_texture,
+                    &block.dark_texture,
+                    &block.detail_texture,
+                    &block.gloss_texture,
+                    &block.glow_texture,
+                    &block.bump_map_texture,
+                ] {
+                    self.tex_desc(texture.as_ref())?;
+                }
+                if block.bump_map_texture.is_some() {
+                    self.f32(block.bump_map_luma_scale)?;
+                    self.f32(block.bump_map_luma_offset)?;
+                    self.matrix22(&block.bump_map_matrix)?;
+                }
+                // every texture past the six fixed slots is a decal slot
+                let decal_slots = block.texture_count.saturating_sub(6).min(4) as usize;
+                if block.decal_textures.len() != decal_slots {
+                    anyhow::bail!(
+                        "{} has {} decal textures for a texture count of {}",
+                        block.base.name,
+                        block.decal_textures.len(),
+                        block.texture_count
+                    );
+                }
+                for decal in block.decal_textures.iter() {
+                    self.tex_desc(decal.as_ref())?;
+                }
+            }
+            Block::NiSourceTexture(block) => {
+                self.object_net(&block.base)?;
+                self.u8(block.use_external)?;
+                if block.use_external != 0 {
+                    self.string(&block.file_name)?;
+                } else {
+                    self.u8(block.unknown_byte)?;
+                    self.block_ref(block.pixel_data_ref)?;
+                }
+                self.u32(block.format_prefs.pixel_layout)?;
+                self.u32(block.format_prefs.use_mipmaps)?;
+                self.u32(block.format_prefs.alpha_format)?;
+                self.u8(block.is_static)?;
+            }
+            Block::NiKeyframeController(block) => {
+                self.time_controller(&block.base)?;
+                self.block_ref(block.data_ref)?;
+            }
+            Block::NiKeyframeData(block) => {
+                let num_rotation_keys = match block.rotation_type {
+                    KeyType::XyzRotation => 1,
+                    _ => block.quaternion_keys.len() as u32,
+                };
+                self.u32(num_rotation_keys)?;
+                if num_rotation_keys > 0 {
+                    self.key_type(block.rotation_type)?;
+                }
+                if block.rotation_type == KeyType::XyzRotation {
+                    // order
+                    self.f32(0.0)?;
+                    for group in block.xyz_rotations.iter() {
+                        self.key_group(group, |writer, value| writer.f32(*value))?;
+                    }
+                } else {
+                    for key in block.quaternion_keys.iter() {
+                        self.f32(key.time)?;
+                        self.quaternion(&key.value)?;
+                        if block.rotation_type == KeyType::Tbc {
+                            self.f32(key.tbc.t)?;
+                            self.f32(key.tbc.b)?;
+                            self.f32(key.tbc.c)?;
+                        }
+                    }
+                }
+                self.key_group(&block.translations, |writer, value| writer.vector3(value))?;
+                self.key_group(&block.scales, |writer, value| writer.f32(*value))?;
+            }
+            Block::NiDirectionalLight(block) => self.light(&block.base)?,
+            Block::NiAmbientLight(block) => self.light(&block.base)?,
+            Block::NiPointLight(block) => {
+                self.light(&block.base)?;
+                self.f32(block.constant_attenuation)?;
+                self.f32(block.linear_attenuation)?;
+                self.f32(block.quadratic_attenuation)?;
+            }
+            Block::NiSkinInstance(block) => {
+                // the partition reference only exists in later versions
+                if block.skin_partition_ref.0 >= 0 {
+                    anyhow::bail!("skin instances with a partition cannot be written");
+                }
+                self.block_ref(block.data_ref)?;
+                self.block_ref(block.skeleton_root_ref)?;
+                self.block_refs(&block.bone_refs)?;
+            }
+            _ => anyhow::bail!("{} cannot be written yet", block.name()),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct BlockTypeStats {
+    writable: bool,
+    files: usize,
+    lossless_files: usize,
+}
+
+/// Parses every .nif below `dir`, writes it back and byte-compares the result with the original.
+///
+/// Prints a line per file and a summary of which block types only appear in lossless files.
+pub fn verify_roundtrip_dir(dir: &Utf8Path) -> anyhow::Result<()> {
+    let mut paths = Vec::new();
+    collect_nif_paths(dir, &mut paths)?;
+    paths.sort();
+
+    let mut stats: BTreeMap<&'static str, BlockTypeStats> = BTreeMap::new();
+    let mut lossless = 0;
+
+    for path in paths.iter() {
+        let original = std::fs::read(path)?;
+        let nif = match Nif::parse(&mut Cursor::new(&original)) {
+            Ok(nif) => nif,
+            Err(err) => {
+                println!("PARSE FAIL  {}: {}", path, err);
+                continue;
+            }
+        };
+
+        let result = match check_roundtrip(&original, &nif) {
+            Ok(()) => {
+                lossless += 1;
+                println!("OK          {}", path);
+                true
+            }
+            Err(err) => {
+                println!("MISMATCH    {}: {}", path, err);
+                false
+            }
+        };
+
+        let mut block_types = nif
+            .blocks
+            .iter()
+            .map(|block| (block.name(), is_writable(block)))
+            .collect::<Vec<_>>();
+        block_types.sort_unstable();
+        block_types.dedup();
+        for (block_type, writable) in block_types {
+            let stats = stats.entry(block_type).or_default();
+            stats.writable = writable;
+            stats.files += 1;
+            if result {
+                stats.lossless_files += 1;
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "{}/{} files round-tripped losslessly",
+        lossless,
+        paths.len()
+    );
+    println!();
+    println!(
+        "{:<32} {:>8} {:>8}  writable",
+        "block type", "files", "lossless"
+    );
+    for (block_type, stats) in stats {
+        println!(
+            "{:<32} {:>8} {:>8}  {}",
+            block_type,
+            stats.files,
+            stats.lossless_files,
+            if stats.writable { "yes" } else { "no" }
+        );
+    }
+
+    Ok(())
+}
+
+fn collect_nif_paths(dir: &Utf8Path, paths: &mut Vec<camino::Utf8PathBuf>) -> anyhow::Result<()> {
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_nif_paths(path, paths)?;
+        } else if path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("nif"))
+            .unwrap_or(false)
+        {
+            paths.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file with a single NiNode, laid out by hand so the writer is not checked against itself.
+    fn single_node_file(velocity: [f32; 3]) -> Vec<u8> {
+        fn string(buf: &mut Vec<u8>, value: &str) {
+            buf.extend((value.len() as u32).to_le_bytes());
+            buf.extend(value.as_bytes());
+        }
+
+        let mut buf = HEADER.to_vec();
+        buf.extend(VERSION.to_le_bytes());
+        buf.extend(1u32.to_le_bytes());
+        string(&mut buf, "NiNode");
+        string(&mut buf, "root");
+        // extra data, controller
+        buf.extend((-1i32).to_le_bytes());
+        buf.extend((-1i32).to_le_bytes());
+        // flags
+        buf.extend(12u16.to_le_bytes());
+        let identity: [f32; 9] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        // translation, rotation, scale, velocity
+        for value in [&[1.0, 2.0, 3.0][..], &identity, &[1.5], &velocity].concat() {
+            buf.extend(value.to_le_bytes());
+        }
+        // properties, has bounding box, children, effects
+        for count in [0u32; 4] {
+            buf.extend(count.to_le_bytes());
+        }
+        // footer
+        buf.extend(1u32.to_le_bytes());
+        buf.extend(0i32.to_le_bytes());
+        buf
+    }
+
+    #[test]
+    fn unedited_node_roundtrips() {
+        let original = single_node_file([0.0; 3]);
+        let nif = Nif::parse(&mut Cursor::new(&original)).unwrap();
+        check_roundtrip(&original, &nif).unwrap();
+
+        let written = write_nif_to_vec(&nif).unwrap();
+        let reparsed = Nif::parse(&mut Cursor::new(&written)).unwrap();
+        assert_eq!(format!("{:?}", reparsed), format!("{:?}", nif));
+    }
+
+    #[test]
+    fn dropped_fields_fail_the_check() {
+        let original = single_node_file([0.0, 0.0, 4.0]);
+        let nif = Nif::parse(&mut Cursor::new(&original)).unwrap();
+        assert!(check_roundtrip(&original, &nif).is_err());
+    }
+}
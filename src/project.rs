@@ -1,7 +1,13 @@
-use std::io::BufWriter;
+use std::{io::BufWriter, sync::mpsc, time::SystemTime};
 
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use serde::{Deserialize, Serialize};
+use slidetown::parsers::agt::DEFAULT_KEY;
+
+use crate::{
+    archive::{self, ArchiveCache},
+    containers::{self, ContainerKind},
+};
 
 pub type ProjectFilePath = Utf8PathBuf;
 
@@ -9,6 +15,9 @@ pub type ProjectFilePath = Utf8PathBuf;
 pub struct Project {
     last_path: Option<Utf8PathBuf>,
     game_dir: Utf8PathBuf,
+    encryption_key: Option<EncryptionKey>,
+    #[serde(skip)]
+    archives: ArchiveCache,
 }
 
 impl Project {
@@ -54,44 +63,356 @@ impl Project {
         self.game_dir.clone()
     }
 
-    pub fn enumerate_files(&self) -> Vec<ProjectFilesEntry> {
-        fn visit_dir(dir: &std::path::Path) -> anyhow::Result<Vec<ProjectFilesEntry>> {
-            let mut result = Vec::new();
-            let mut files = Vec::new();
-            if dir.is_dir() {
-                for entry in std::fs::read_dir(dir)? {
-                    let entry = entry?;
-                    let path = entry.path();
-                    let utf8_path = match Utf8PathBuf::from_path_buf(path) {
-                        Ok(path) => path,
-                        Err(_) => {
-                            anyhow::bail!("dir entry path was not valid utf8: {:?}", entry.path())
+    pub fn set_encryption_key(&mut self, key: Option<EncryptionKey>) {
+        self.encryption_key = key;
+    }
+
+    pub fn has_custom_encryption_key(&self) -> bool {
+        self.encryption_key.is_some()
+    }
+
+    /// The key used to decrypt packed game data, falling back to the default client key.
+    pub fn encryption_key(&self) -> EncryptionKey {
+        self.encryption_key.clone().unwrap_or_default()
+    }
+
+    /// Reads a file from disk, from inside a packed archive if `path` points into one,
+    /// or an embedded NIF if `path` points into an LF/LBF/LOF container.
+    pub fn read_file(&self, path: &Utf8Path) -> anyhow::Result<Vec<u8>> {
+        self.file_lister().read_file(path)
+    }
+
+    /// Loose files below the game directory that were modified at or after `since`.
+    ///
+    /// Files inside archives are not included, archives are only ever rewritten as a whole so
+    /// there is no way to tell which of their files changed.
+    pub fn modified_files_since(&self, since: SystemTime) -> Vec<Utf8PathBuf> {
+        fn visit_dir(dir: &Utf8Path, since: SystemTime, result: &mut Vec<Utf8PathBuf>) {
+            let entries = match dir.read_dir_utf8() {
+                Ok(entries) => entries,
+                Err(_) => return,
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+                if metadata.is_dir() {
+                    visit_dir(path, since, result);
+                } else if !archive::is_archive_path(path)
+                    && metadata.modified().map(|t| t >= since).unwrap_or(false)
+                {
+                    result.push(path.to_owned());
+                }
+            }
+        }
+
+        let mut result = Vec::new();
+        visit_dir(&self.game_dir, since, &mut result);
+        result
+    }
+
+    /// Extracts archived files below `target_dir`, keeping their paths relative to `root`.
+    pub fn extract_archived_files(
+        &self,
+        paths: &[Utf8PathBuf],
+        root: &Utf8Path,
+        target_dir: &Utf8Path,
+    ) -> anyhow::Result<()> {
+        archive::extract_paths(
+            paths,
+            root,
+            target_dir,
+            &self.archives,
+            &self.encryption_key(),
+        )
+    }
+
+    /// Lists the project files on a background thread, archives are decrypted there too.
+    pub fn enumerate_files_in_background(&self) -> mpsc::Receiver<Vec<ProjectFilesEntry>> {
+        let (sender, receiver) = mpsc::channel();
+        let (game_dir, lister) = (self.game_dir(), self.file_lister());
+        std::thread::spawn(move || {
+            sender.send(lister.list(&game_dir)).ok();
+        });
+        receiver
+    }
+
+    /// A handle for listing and reading project files from other threads.
+    pub fn file_lister(&self) -> FileLister {
+        FileLister {
+            key: self.encryption_key(),
+            archives: self.archives.clone(),
+        }
+    }
+}
+
+/// Lists and reads project files from other threads, sharing the archive cache of its
+/// project.
+#[derive(Debug, Clone)]
+pub struct FileLister {
+    key: EncryptionKey,
+    archives: ArchiveCache,
+}
+
+impl FileLister {
+    /// Reads a file from disk, from inside a packed archive if `path` points into one,
+    /// or an embedded NIF if `path` points into an LF/LBF/LOF container.
+    pub fn read_file(&self, path: &Utf8Path) -> anyhow::Result<Vec<u8>> {
+        if path.is_file() {
+            return Ok(std::fs::read(path)?);
+        }
+        if let Some((container_path, kind)) = path
+            .parent()
+            .and_then(|parent| Some((parent, ContainerKind::from_path(parent)?)))
+        {
+            // NIFs embedded in LF/LBF/LOF containers are addressed as container_path/file_name
+            let data = self.read_file(container_path)?;
+            let entries = containers::embedded_nifs(kind, &data)?;
+            return match entries
+                .iter()
+                .find(|entry| Some(entry.file_name.as_str()) == path.file_name())
+            {
+                Some(entry) => containers::read_embedded_nif(&data, entry),
+                None => anyhow::bail!("{} does not contain {}", container_path, path),
+            };
+        }
+        match archive::split_archive_path(path) {
+            Some((archive_path, inner_path)) => self
+                .archives
+                .open(archive_path, &self.key)?
+                .read_path(inner_path),
+            None => anyhow::bail!("{} does not exist", path),
+        }
+    }
+
+    /// Whether `path` is a directory on disk or inside a packed archive.
+    pub fn is_dir(&self, path: &Utf8Path) -> bool {
+        if path.is_dir() {
+            return true;
+        }
+        match archive::split_archive_path(path) {
+            Some((archive_path, inner_path)) => self
+                .archives
+                .open(archive_path, &self.key)
+                .map(|archive| archive.has_dir(inner_path))
+                .unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Lists the entries below `dir`, an unreadable directory lists as empty.
+    pub fn list(&self, dir: &Utf8Path) -> Vec<ProjectFilesEntry> {
+        match self.visit_dir(dir.as_std_path()) {
+            Ok(entries) => entries,
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn visit_dir(&self, dir: &std::path::Path) -> anyhow::Result<Vec<ProjectFilesEntry>> {
+        let mut result = Vec::new();
+        let mut files = Vec::new();
+        if dir.is_dir() {
+            // a single unreadable entry should not empty the whole listing
+            for entry in std::fs::read_dir(dir)? {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        eprintln!("Skipping an entry of {:?}: {:?}", dir, err);
+                        continue;
+                    }
+                };
+                let path = entry.path();
+                let utf8_path = match Utf8PathBuf::from_path_buf(path) {
+                    Ok(path) => path,
+                    Err(path) => {
+                        eprintln!("Skipping {:?}, its path is not valid utf8", path);
+                        continue;
+                    }
+                };
+
+                if utf8_path.is_dir() {
+                    let inner_entries = match self.visit_dir(&entry.path()) {
+                        Ok(inner_entries) => inner_entries,
+                        Err(err) => {
+                            eprintln!("Failed to list {}: {:?}", utf8_path, err);
+                            Vec::new()
                         }
                     };
+                    result.push(ProjectFilesEntry::Directory((utf8_path, inner_entries)));
+                    continue;
+                }
 
-                    if utf8_path.is_dir() {
-                        result.push(ProjectFilesEntry::Directory((
-                            utf8_path,
-                            visit_dir(&entry.path())?,
-                        )));
-                    } else {
-                        files.push(ProjectFilesEntry::File(utf8_path));
+                let size = match entry.metadata() {
+                    Ok(metadata) => metadata.len(),
+                    Err(err) => {
+                        eprintln!("Skipping {}: {:?}", utf8_path, err);
+                        continue;
+                    }
+                };
+                if archive::is_archive_path(&utf8_path) {
+                    match self.archives.open(&utf8_path, &self.key) {
+                        Ok(archive) => {
+                            let inner_entries =
+                                build_entries(&utf8_path, &archive.file_paths_with_sizes());
+                            result.push(ProjectFilesEntry::Archive((utf8_path, inner_entries)));
+                        }
+                        Err(err) => {
+                            eprintln!("failed to open archive {}: {:?}", utf8_path, err);
+                            files.push(ProjectFilesEntry::File((utf8_path, size)));
+                        }
                     }
+                } else {
+                    files.push(ProjectFilesEntry::File((utf8_path, size)));
                 }
             }
-            result.extend(files.into_iter());
-            Ok(result)
         }
+        result.extend(files.into_iter());
+        Ok(result)
+    }
+}
 
-        match visit_dir(self.game_dir().as_path().as_std_path()) {
-            Ok(entries) => entries,
-            Err(_) => Vec::new(),
+/// Builds a directory tree below `base` out of relative file paths and their sizes.
+fn build_entries(base: &Utf8Path, paths: &[(Utf8PathBuf, u64)]) -> Vec<ProjectFilesEntry> {
+    let mut dirs: Vec<(&str, Vec<(Utf8PathBuf, u64)>)> = Vec::new();
+    let mut files = Vec::new();
+    for (path, size) in paths {
+        let mut components = path.components();
+        let first = match components.next() {
+            Some(first) => first.as_str(),
+            None => continue,
+        };
+        let rest = components.as_path();
+        if rest.as_str().is_empty() {
+            files.push(ProjectFilesEntry::File((base.join(first), *size)));
+        } else {
+            let rest = (rest.to_owned(), *size);
+            match dirs.iter_mut().find(|(name, _)| *name == first) {
+                Some((_, dir_paths)) => dir_paths.push(rest),
+                None => dirs.push((first, vec![rest])),
+            }
+        }
+    }
+
+    let mut result = dirs
+        .into_iter()
+        .map(|(name, dir_paths)| {
+            let dir = base.join(name);
+            let inner_entries = build_entries(&dir, &dir_paths);
+            ProjectFilesEntry::Directory((dir, inner_entries))
+        })
+        .collect::<Vec<_>>();
+    result.extend(files.into_iter());
+    result
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EncryptionKey(Vec<u8>);
+
+impl Default for EncryptionKey {
+    fn default() -> Self {
+        Self(DEFAULT_KEY.to_vec())
+    }
+}
+
+impl EncryptionKey {
+    /// Reads a key from a file containing either the raw key bytes or the key as hex text.
+    pub fn from_file(path: &Utf8Path) -> anyhow::Result<Self> {
+        let data = std::fs::read(path)?;
+        let hex_bytes = std::str::from_utf8(&data)
+            .ok()
+            .and_then(|text| parse_hex(text.trim()));
+        Self::from_bytes(hex_bytes.unwrap_or(data))
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        if bytes.len() != DEFAULT_KEY.len() {
+            anyhow::bail!(
+                "expected a {} byte key, got {} bytes",
+                DEFAULT_KEY.len(),
+                bytes.len()
+            );
         }
+        Ok(Self(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
     }
 }
 
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let hi = pair[0].to_digit(16)?;
+            let lo = pair[1].to_digit(16)?;
+            Some((hi * 16 + lo) as u8)
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 pub enum ProjectFilesEntry {
-    File(Utf8PathBuf),
+    /// A file and its size in bytes.
+    File((Utf8PathBuf, u64)),
     Directory((Utf8PathBuf, Vec<ProjectFilesEntry>)),
+    /// A packed archive, its contents listed as virtual paths below the archive path.
+    Archive((Utf8PathBuf, Vec<ProjectFilesEntry>)),
+}
+
+impl ProjectFilesEntry {
+    /// Finds the entry for `path` in a tree of entries.
+    pub fn find<'a>(entries: &'a [ProjectFilesEntry], path: &Utf8Path) -> Option<&'a Self> {
+        entries.iter().find_map(|entry| match entry {
+            ProjectFilesEntry::File((file, _)) => (file == path).then_some(entry),
+            ProjectFilesEntry::Directory((dir, inner_entries))
+            | ProjectFilesEntry::Archive((dir, inner_entries)) => {
+                if dir == path {
+                    Some(entry)
+                } else if path.starts_with(dir) {
+                    Self::find(inner_entries, path)
+                } else {
+                    None
+                }
+            }
+        })
+    }
+
+    /// Finds the directory `path` in a tree of entries and returns its entries.
+    pub fn find_dir_mut<'a>(
+        entries: &'a mut [ProjectFilesEntry],
+        path: &Utf8Path,
+    ) -> Option<&'a mut Vec<ProjectFilesEntry>> {
+        entries.iter_mut().find_map(|entry| match entry {
+            ProjectFilesEntry::Directory((dir, inner_entries)) if path.starts_with(&*dir) => {
+                if dir == path {
+                    Some(inner_entries)
+                } else {
+                    Self::find_dir_mut(inner_entries, path)
+                }
+            }
+            _ => None,
+        })
+    }
+
+    /// All file paths at or below this entry.
+    pub fn file_paths(&self) -> Vec<Utf8PathBuf> {
+        match self {
+            ProjectFilesEntry::File((path, _)) => vec![path.clone()],
+            ProjectFilesEntry::Directory((_, entries))
+            | ProjectFilesEntry::Archive((_, entries)) => entries
+                .iter()
+                .flat_map(ProjectFilesEntry::file_paths)
+                .collect(),
+        }
+    }
 }
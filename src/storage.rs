@@ -1,4 +1,4 @@
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 
 pub fn prompt_game_directory() -> Option<Utf8PathBuf> {
     native_dialog::FileDialog::new()
@@ -8,6 +8,96 @@ pub fn prompt_game_directory() -> Option<Utf8PathBuf> {
         .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
 }
 
+pub fn prompt_output_directory() -> Option<Utf8PathBuf> {
+    native_dialog::FileDialog::new()
+        .show_open_single_dir()
+        .ok()
+        .flatten()
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+}
+
+pub fn prompt_open_files() -> Vec<Utf8PathBuf> {
+    native_dialog::FileDialog::new()
+        .show_open_multiple_file()
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|path| Utf8PathBuf::from_path_buf(path).ok())
+        .collect()
+}
+
+pub fn prompt_save_archive_file() -> Option<Utf8PathBuf> {
+    native_dialog::FileDialog::new()
+        .add_filter("Game Archive", &["agt"])
+        .show_save_single_file()
+        .ok()
+        .flatten()
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+}
+
+pub fn prompt_save_nif_file(existing_path: &Utf8Path) -> Option<Utf8PathBuf> {
+    let mut dialog = native_dialog::FileDialog::new().add_filter("NIF Model", &["nif"]);
+
+    if let Some(file_name) = existing_path.file_name() {
+        dialog = dialog.set_filename(file_name);
+    }
+
+    dialog
+        .show_save_single_file()
+        .ok()
+        .flatten()
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+}
+
+pub fn prompt_open_nif_file() -> Option<Utf8PathBuf> {
+    native_dialog::FileDialog::new()
+        .add_filter("NIF Model", &["nif"])
+        .show_open_single_file()
+        .ok()
+        .flatten()
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+}
+
+pub fn prompt_open_manifest_file() -> Option<Utf8PathBuf> {
+    native_dialog::FileDialog::new()
+        .add_filter("Extraction Manifest", &["json"])
+        .show_open_single_file()
+        .ok()
+        .flatten()
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+}
+
+pub fn prompt_save_container_file(existing_path: &Utf8Path) -> Option<Utf8PathBuf> {
+    let mut dialog = native_dialog::FileDialog::new();
+
+    if let Some(file_name) = existing_path.file_name() {
+        dialog = dialog.set_filename(file_name);
+    }
+
+    dialog
+        .show_save_single_file()
+        .ok()
+        .flatten()
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+}
+
+pub fn prompt_save_table_file() -> Option<Utf8PathBuf> {
+    native_dialog::FileDialog::new()
+        .add_filter("CSV", &["csv"])
+        .add_filter("JSON", &["json"])
+        .show_save_single_file()
+        .ok()
+        .flatten()
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+}
+
+pub fn prompt_key_file() -> Option<Utf8PathBuf> {
+    native_dialog::FileDialog::new()
+        .show_open_single_file()
+        .ok()
+        .flatten()
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+}
+
 pub fn prompt_open_project_file() -> Option<Utf8PathBuf> {
     native_dialog::FileDialog::new()
         .add_filter("Slidetown Project", &["stproj"])
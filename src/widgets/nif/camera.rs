@@ -7,6 +7,33 @@ pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
 }
 
+/// Clipping planes of a camera's view, normals pointing inwards.
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    planes: [glam::Vec4; 6],
+}
+
+impl Frustum {
+    pub fn from_view_proj(view_proj: glam::Mat4) -> Self {
+        let (r0, r1, r2, r3) = (
+            view_proj.row(0),
+            view_proj.row(1),
+            view_proj.row(2),
+            view_proj.row(3),
+        );
+        // wgpu clip space depth is 0..1, so the near plane is just the z row
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2]
+            .map(|plane| plane / plane.truncate().length());
+        Self { planes }
+    }
+
+    pub fn intersects_sphere(&self, center: glam::Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Camera {
     pub eye: glam::Vec3,
@@ -33,6 +60,10 @@ impl Default for Camera {
 }
 
 impl Camera {
+    pub fn fov_y(&self) -> f32 {
+        self.fov_y
+    }
+
     pub fn build_view_matrix(&self) -> glam::Mat4 {
         glam::Mat4::look_at_rh(self.eye, self.target, self.up)
     }
@@ -46,6 +77,10 @@ impl Camera {
         )
     }
 
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_proj(self.build_projection_matrix() * self.build_view_matrix())
+    }
+
     pub fn to_raw(&self) -> CameraUniform {
         let view_proj = self.build_projection_matrix() * self.build_view_matrix();
         CameraUniform {
@@ -90,3 +125,134 @@ impl Camera {
         (uniform_buffer, bind_group_layout, bind_group)
     }
 }
+
+/// Camera circling a target point, angles follow the fly camera's yaw and pitch.
+#[derive(Debug, Clone)]
+pub struct OrbitCamera {
+    pub target: glam::Vec3,
+    pub distance: f32,
+    pub yaw_degrees: f32,
+    pub pitch_degrees: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            target: glam::Vec3::ZERO,
+            distance: 100.0,
+            yaw_degrees: 135.0,
+            pitch_degrees: -30.0,
+        }
+    }
+}
+
+impl OrbitCamera {
+    /// Looks at `target` from `eye`.
+    pub fn looking_at(eye: glam::Vec3, target: glam::Vec3) -> Self {
+        let (yaw_degrees, pitch_degrees) = yaw_pitch_degrees(target - eye);
+        Self {
+            target,
+            distance: eye.distance(target).max(1.0),
+            yaw_degrees,
+            pitch_degrees,
+        }
+    }
+
+    pub fn forward(&self) -> glam::Vec3 {
+        // yaw 0 looks along +y like the z-up dolly rig
+        let (yaw, pitch) = (
+            (self.yaw_degrees + 90.0).to_radians(),
+            self.pitch_degrees.to_radians(),
+        );
+        glam::vec3(
+            yaw.cos() * pitch.cos(),
+            yaw.sin() * pitch.cos(),
+            pitch.sin(),
+        )
+    }
+
+    pub fn eye(&self) -> glam::Vec3 {
+        self.target - self.forward() * self.distance
+    }
+
+    pub fn rotate(&mut self, yaw_degrees: f32, pitch_degrees: f32) {
+        self.yaw_degrees = (self.yaw_degrees + yaw_degrees).rem_euclid(360.0);
+        // stop short of the poles, the view flips over them with z up
+        self.pitch_degrees = (self.pitch_degrees + pitch_degrees).clamp(-89.0, 89.0);
+    }
+
+    /// Moves the target across the view, by fractions of the distance to it.
+    pub fn pan(&mut self, right: f32, up: f32) {
+        let forward = self.forward();
+        let right_axis = forward.cross(glam::Vec3::Z).normalize_or_zero();
+        let up_axis = right_axis.cross(forward);
+        self.target += (right_axis * right + up_axis * up) * self.distance;
+    }
+
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).max(1.0);
+    }
+
+    /// Centres the bounds and backs off until they fill the view.
+    pub fn fit(&mut self, min: glam::Vec3, max: glam::Vec3, fov_y_degrees: f32) {
+        let radius = ((max - min).length() / 2.0).max(1.0);
+        self.target = (min + max) / 2.0;
+        self.distance = radius / (fov_y_degrees.to_radians() / 2.0).tan();
+    }
+}
+
+/// Yaw and pitch of the fly and orbit cameras looking along `forward`.
+pub fn yaw_pitch_degrees(forward: glam::Vec3) -> (f32, f32) {
+    let forward = forward.normalize_or_zero();
+    (
+        forward.y.atan2(forward.x).to_degrees() - 90.0,
+        forward.z.clamp(-1.0, 1.0).asin().to_degrees(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Camera 10 units in front of the origin, looking along +Y.
+    fn frustum() -> Frustum {
+        let camera = Camera {
+            eye: glam::vec3(0.0, -10.0, 0.0),
+            target: glam::Vec3::ZERO,
+            ..Default::default()
+        };
+        camera.frustum()
+    }
+
+    #[test]
+    fn sphere_in_view_is_visible() {
+        assert!(frustum().intersects_sphere(glam::Vec3::ZERO, 1.0));
+    }
+
+    #[test]
+    fn sphere_behind_camera_is_culled() {
+        assert!(!frustum().intersects_sphere(glam::vec3(0.0, -20.0, 0.0), 1.0));
+    }
+
+    #[test]
+    fn sphere_beyond_far_plane_is_culled() {
+        assert!(!frustum().intersects_sphere(glam::vec3(0.0, 200000.0, 0.0), 1.0));
+    }
+
+    #[test]
+    fn sphere_outside_the_sides_is_culled() {
+        let frustum = frustum();
+        assert!(!frustum.intersects_sphere(glam::vec3(100.0, 0.0, 0.0), 1.0));
+        assert!(!frustum.intersects_sphere(glam::vec3(-100.0, 0.0, 0.0), 1.0));
+        assert!(!frustum.intersects_sphere(glam::vec3(0.0, 0.0, 100.0), 1.0));
+        assert!(!frustum.intersects_sphere(glam::vec3(0.0, 0.0, -100.0), 1.0));
+    }
+
+    #[test]
+    fn sphere_straddling_a_plane_is_visible() {
+        // the 60 degree view is about 5.8 units wide either side at the origin
+        let frustum = frustum();
+        assert!(!frustum.intersects_sphere(glam::vec3(8.0, 0.0, 0.0), 1.0));
+        assert!(frustum.intersects_sphere(glam::vec3(8.0, 0.0, 0.0), 3.0));
+    }
+}
@@ -0,0 +1,72 @@
+struct CameraUniform {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(2) @binding(0)
+var<storage, read> bones: array<mat4x4<f32>>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(14) bone_indices: vec4<u32>,
+    @location(15) bone_weights: vec4<f32>,
+}
+
+struct InstanceInput {
+    @location(2) model_matrix_0: vec4<f32>,
+    @location(3) model_matrix_1: vec4<f32>,
+    @location(4) model_matrix_2: vec4<f32>,
+    @location(5) model_matrix_3: vec4<f32>,
+    @location(6) normal_matrix_0: vec3<f32>,
+    @location(7) normal_matrix_1: vec3<f32>,
+    @location(8) normal_matrix_2: vec3<f32>,
+    @location(9) tint: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> @builtin(position) vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    // follow the skinned mesh, vertices without weights are not skinned
+    var position = vec4<f32>(model.position, 1.0);
+    if (dot(model.bone_weights, vec4<f32>(1.0)) >= 0.001) {
+        position = (bones[model.bone_indices.x] * model.bone_weights.x
+            + bones[model.bone_indices.y] * model.bone_weights.y
+            + bones[model.bone_indices.z] * model.bone_weights.z
+            + bones[model.bone_indices.w] * model.bone_weights.w) * position;
+    }
+    var clip_position = camera.view_proj * model_matrix * position;
+    // pull lines slightly towards the camera so they win against the faces they lie on
+    clip_position.z = clip_position.z - 0.0001 * clip_position.w;
+    return clip_position;
+}
+
+@fragment
+fn fs_wireframe() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.05, 0.2, 0.3, 1.0);
+}
+
+@fragment
+fn fs_normals() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 1.0, 1.0, 1.0);
+}
+
+@fragment
+fn fs_bounds() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.5, 0.0, 1.0);
+}
+
+@fragment
+fn fs_degenerate() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 1.0, 1.0);
+}
@@ -1,26 +1,82 @@
 use eframe::wgpu::{self, util::DeviceExt};
+use nif::{
+    blocks::{Block, NiLight},
+    common::Color3,
+    Nif,
+};
 
+use super::shape_colors::local_transform;
+
+/// Most lights the shader takes, extra ones are ignored.
+pub const MAX_LIGHTS: usize = 4;
+
+const KIND_DIRECTIONAL: u32 = 0;
+const KIND_POINT: u32 = 1;
+
+/// A light as the shader sees it.
 #[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Light {
-    pub position: [f32; 3],
-    _padding: u32,
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SceneLight {
+    /// Direction towards a directional light, or the position of a point light.
+    position: [f32; 3],
+    kind: u32,
     color: [f32; 3],
-    _padding2: u32,
+    _padding: u32,
 }
 
-impl Default for Light {
-    fn default() -> Self {
+impl SceneLight {
+    pub fn directional(direction_to_light: glam::Vec3, color: glam::Vec3) -> Self {
+        Self {
+            position: direction_to_light.normalize_or_zero().into(),
+            kind: KIND_DIRECTIONAL,
+            color: color.into(),
+            _padding: 0,
+        }
+    }
+
+    pub fn point(position: glam::Vec3, color: glam::Vec3) -> Self {
         Self {
-            position: [5.0, 0.0, 10.0],
+            position: position.into(),
+            kind: KIND_POINT,
+            color: color.into(),
             _padding: 0,
-            color: [1.0, 1.0, 1.0],
-            _padding2: 0,
         }
     }
+
+    pub fn rotated(&self, rotation: glam::Quat) -> Self {
+        Self {
+            position: (rotation * glam::Vec3::from(self.position)).into(),
+            ..*self
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Light {
+    lights: [SceneLight; MAX_LIGHTS],
+    ambient: [f32; 3],
+    count: u32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self::new(&[], glam::Vec3::splat(0.05))
+    }
 }
 
 impl Light {
+    pub fn new(lights: &[SceneLight], ambient: glam::Vec3) -> Self {
+        let count = lights.len().min(MAX_LIGHTS);
+        let mut me = Self {
+            lights: [SceneLight::default(); MAX_LIGHTS],
+            ambient: ambient.into(),
+            count: count as u32,
+        };
+        me.lights[..count].copy_from_slice(&lights[..count]);
+        me
+    }
+
     pub fn create(
         &self,
         device: &wgpu::Device,
@@ -57,3 +113,96 @@ impl Light {
         (uniform_buffer, bind_group_layout, bind_group)
     }
 }
+
+/// Lights defined by NiDirectionalLight, NiPointLight and NiAmbientLight blocks, in model space.
+#[derive(Debug, Clone, Default)]
+pub struct NifLights {
+    pub lights: Vec<SceneLight>,
+    /// Sum of the ambient lights, None without any.
+    pub ambient: Option<glam::Vec3>,
+}
+
+impl NifLights {
+    pub fn new(nif: &Nif) -> Self {
+        let mut placed = Vec::new();
+        if let Some(root) = nif.blocks.get(0) {
+            find_lights(&nif.blocks, root, glam::Mat4::IDENTITY, &mut placed);
+        }
+        // lights not reached as children, e.g. only listed as effects, keep their own transform
+        for block in nif.blocks.iter() {
+            if !placed.iter().any(|(light, _)| std::ptr::eq(*light, block)) {
+                if let Some(light) = light_base(block) {
+                    placed.push((block, local_transform(&light.base)));
+                }
+            }
+        }
+
+        let mut me = Self::default();
+        for (block, transform) in placed {
+            me.add_light(block, transform);
+        }
+        me
+    }
+
+    pub fn extend(&mut self, other: &NifLights) {
+        self.lights.extend_from_slice(&other.lights);
+        self.ambient = match (self.ambient, other.ambient) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+
+    fn add_light(&mut self, block: &Block, transform: glam::Mat4) {
+        let rgb = |color: &Color3, dimmer: f32| glam::vec3(color.r, color.g, color.b) * dimmer;
+        let light = match light_base(block) {
+            Some(light) => light,
+            None => return,
+        };
+        let ambient = rgb(&light.ambient_color, light.dimmer);
+        let diffuse = rgb(&light.diffuse_color, light.dimmer);
+        match block {
+            Block::NiAmbientLight(_) => {
+                self.ambient = Some(self.ambient.unwrap_or_default() + ambient);
+                return;
+            }
+            // directional lights shine along their local x axis
+            Block::NiDirectionalLight(_) => self.lights.push(SceneLight::directional(
+                -transform.transform_vector3(glam::Vec3::X),
+                diffuse,
+            )),
+            Block::NiPointLight(_) => self.lights.push(SceneLight::point(
+                transform.transform_point3(glam::Vec3::ZERO),
+                diffuse,
+            )),
+            _ => return,
+        }
+        if ambient != glam::Vec3::ZERO {
+            self.ambient = Some(self.ambient.unwrap_or_default() + ambient);
+        }
+    }
+}
+
+fn light_base(block: &Block) -> Option<&NiLight> {
+    match block {
+        Block::NiDirectionalLight(light) => Some(&light.base),
+        Block::NiAmbientLight(light) => Some(&light.base),
+        Block::NiPointLight(light) => Some(&light.base),
+        _ => None,
+    }
+}
+
+fn find_lights<'a>(
+    blocks: &'a [Block],
+    block: &'a Block,
+    parent_transform: glam::Mat4,
+    placed: &mut Vec<(&'a Block, glam::Mat4)>,
+) {
+    if let Some(light) = light_base(block) {
+        placed.push((block, parent_transform * local_transform(&light.base)));
+    } else if let Block::NiNode(node) = block {
+        let transform = parent_transform * local_transform(&node.base);
+        for (_, child) in block.children(blocks).unwrap_or_default() {
+            find_lights(blocks, child, transform, placed);
+        }
+    }
+}
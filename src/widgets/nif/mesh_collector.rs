@@ -0,0 +1,325 @@
+//! Shapes of a NIF in model space, each collected once with its vertex colours and the
+//! camera distances its LOD nodes draw it at.
+
+use std::{collections::HashSet, ops::Range};
+
+use nif::{
+    blocks::{Block, NiAlphaProperty, NiAvObject, NiLODNode, NiTriShape},
+    common::BlockRef,
+    Nif,
+};
+
+use super::{
+    scene_graph::{as_node, SceneGraph},
+    shape_colors::{local_transform, vertex_colors, InheritedProperties, VertexColors},
+    skin::ShapeSkin,
+    untextured_mesh_pipeline::AlphaMode,
+};
+
+/// Distances a shape without LOD nodes above it is drawn at.
+pub const ALL_DISTANCES: Range<f32> = 0.0..f32::INFINITY;
+
+/// A shape's triangles in model space.
+#[derive(Debug, Clone)]
+pub struct CollectedShape {
+    pub block_index: usize,
+    /// Transform from the shape's own space to model space, as stored.
+    pub world: glam::Mat4,
+    /// Whether a controller moves the shape or a node above it.
+    pub animated: bool,
+    pub vertices: Vec<glam::Vec3>,
+    pub normals: Vec<glam::Vec3>,
+    pub indices: Vec<u32>,
+    /// Vertex colours merged with the material the shape inherits, one per vertex.
+    pub colors: Vec<VertexColors>,
+    /// From the NiAlphaProperty the shape inherits, picks the pipeline it is drawn with.
+    pub alpha_mode: AlphaMode,
+    /// Alpha at or below which alpha tested fragments are discarded.
+    pub alpha_threshold: f32,
+    /// Camera distances the shape is drawn at, the overlap of every LOD range above it.
+    pub lod_range: Range<f32>,
+    /// Bones and vertex weights from the shape's NiSkinInstance.
+    pub skin: Option<ShapeSkin>,
+}
+
+pub fn collect_shapes(nif: &Nif) -> Vec<CollectedShape> {
+    let mut collector = Collector {
+        blocks: &nif.blocks,
+        graph: SceneGraph::new(nif),
+        visited: HashSet::new(),
+        shapes: Vec::new(),
+    };
+    collector.add_block(
+        0,
+        Inherited {
+            transform: glam::Mat4::IDENTITY,
+            properties: InheritedProperties::default(),
+            lod_range: ALL_DISTANCES,
+            animated: false,
+        },
+    );
+    collector.shapes
+}
+
+/// What a block takes from the blocks above it.
+#[derive(Clone)]
+struct Inherited<'a> {
+    transform: glam::Mat4,
+    properties: InheritedProperties<'a>,
+    lod_range: Range<f32>,
+    /// Whether a controller moves the block or one above it.
+    animated: bool,
+}
+
+impl<'a> Inherited<'a> {
+    fn with_object(&self, blocks: &'a [Block], av_object: &NiAvObject) -> Self {
+        Self {
+            transform: self.transform * local_transform(av_object),
+            properties: self.properties.with_object(blocks, av_object),
+            lod_range: self.lod_range.clone(),
+            animated: self.animated || av_object.base.controller_ref.get(blocks).is_some(),
+        }
+    }
+}
+
+struct Collector<'a> {
+    blocks: &'a [Block],
+    /// The stored pose, which skinned vertices are bound in.
+    graph: SceneGraph,
+    visited: HashSet<usize>,
+    shapes: Vec<CollectedShape>,
+}
+
+impl<'a> Collector<'a> {
+    fn add_child(&mut self, child_ref: &BlockRef, inherited: Inherited<'a>) {
+        if let Ok(child_idx) = usize::try_from(child_ref.0) {
+            self.add_block(child_idx, inherited);
+        }
+    }
+
+    fn add_block(&mut self, idx: usize, inherited: Inherited<'a>) {
+        // guards against cycles
+        if !self.visited.insert(idx) {
+            return;
+        }
+        let block = match self.blocks.get(idx) {
+            Some(block) => block,
+            None => return,
+        };
+        if let Block::NiTriShape(shape) = block {
+            let inherited = inherited.with_object(self.blocks, &shape.base);
+            self.add_shape(idx, shape, inherited);
+            return;
+        }
+        let node = match as_node(block) {
+            Some(node) => node,
+            None => return,
+        };
+        let inherited = inherited.with_object(self.blocks, &node.base);
+        match block {
+            Block::NiLODNode(lod_node) => {
+                // children past the last range are never drawn
+                for (child_ref, range) in node.child_refs.iter().zip(lod_ranges(lod_node)) {
+                    let lod_range = overlap(&inherited.lod_range, &range);
+                    if !lod_range.is_empty() {
+                        let child = Inherited {
+                            lod_range,
+                            ..inherited.clone()
+                        };
+                        self.add_child(child_ref, child);
+                    }
+                }
+            }
+            _ => {
+                for child_ref in node.child_refs.iter() {
+                    self.add_child(child_ref, inherited.clone());
+                }
+            }
+        }
+    }
+
+    fn add_shape(&mut self, block_index: usize, shape: &NiTriShape, inherited: Inherited) {
+        let Inherited {
+            transform,
+            properties,
+            lod_range,
+            animated,
+        } = inherited;
+        let data = match shape.data_ref.get(self.blocks) {
+            Some(Block::NiTriShapeData(data)) => data,
+            _ => return,
+        };
+        let vertices = data
+            .base
+            .vertices
+            .iter()
+            .map(|v| transform.transform_point3(glam::vec3(v.x, v.y, v.z)))
+            .collect::<Vec<_>>();
+        let indices = data
+            .triangles
+            .iter()
+            .map(|t| [t.v1, t.v2, t.v3].map(u32::from))
+            .filter(|triangle| triangle.iter().all(|&i| (i as usize) < vertices.len()))
+            .flatten()
+            .collect::<Vec<_>>();
+        let normals = if data.base.normals.len() == vertices.len() {
+            let normal_matrix = glam::Mat3::from_mat4(transform).inverse().transpose();
+            data.base
+                .normals
+                .iter()
+                .map(|n| (normal_matrix * glam::vec3(n.x, n.y, n.z)).normalize_or_zero())
+                .collect()
+        } else {
+            smooth_normals(&vertices, &indices)
+        };
+        let skin = ShapeSkin::new(
+            self.blocks,
+            &self.graph,
+            (block_index, transform),
+            shape,
+            vertices.len(),
+        );
+        self.shapes.push(CollectedShape {
+            block_index,
+            world: transform,
+            animated,
+            colors: vertex_colors(&data.base, properties),
+            alpha_mode: properties.alpha.map(alpha_mode).unwrap_or_default(),
+            alpha_threshold: properties
+                .alpha
+                .map(|alpha| alpha.threshold as f32 / 255.0)
+                .unwrap_or_default(),
+            vertices,
+            normals,
+            indices,
+            lod_range,
+            skin,
+        });
+    }
+}
+
+/// Testing wins when a property enables both, so cut-outs like foliage keep writing depth.
+fn alpha_mode(property: &NiAlphaProperty) -> AlphaMode {
+    if property.flags & (1 << 9) != 0 {
+        AlphaMode::Tested
+    } else if property.flags & 1 != 0 {
+        AlphaMode::Blended
+    } else {
+        AlphaMode::Opaque
+    }
+}
+
+/// Near and far distances of each child of a LOD node, in child order.
+fn lod_ranges(lod_node: &NiLODNode) -> impl Iterator<Item = Range<f32>> + '_ {
+    lod_node
+        .lod_levels
+        .iter()
+        .map(|level| level.near_extent..level.far_extent)
+}
+
+fn overlap(a: &Range<f32>, b: &Range<f32>) -> Range<f32> {
+    a.start.max(b.start)..a.end.min(b.end)
+}
+
+/// Normals averaged from the faces around each vertex, for shapes stored without normals.
+fn smooth_normals(vertices: &[glam::Vec3], indices: &[u32]) -> Vec<glam::Vec3> {
+    let mut normals = vec![glam::Vec3::ZERO; vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+        let face = (vertices[b] - vertices[a]).cross(vertices[c] - vertices[a]);
+        for i in [a, b, c] {
+            normals[i] += face;
+        }
+    }
+    normals.iter().map(|n| n.normalize_or_zero()).collect()
+}
+
+/// Shapes drawn at a fixed distance, by index into `lod_ranges`.
+pub fn shapes_at(lod_ranges: &[Range<f32>], distance: f32) -> Vec<usize> {
+    lod_ranges
+        .iter()
+        .enumerate()
+        .filter(|(_, range)| range.contains(&distance))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// The distances the set of drawn shapes changes at, each with the shapes drawn from there
+/// on. Starts at 0, with any shape whose range starts further away left out.
+pub fn lod_levels(lod_ranges: &[Range<f32>]) -> Vec<(f32, Vec<usize>)> {
+    let mut switch_distances = lod_ranges
+        .iter()
+        .flat_map(|range| [range.start, range.end])
+        .filter(|distance| distance.is_finite() && *distance > 0.0)
+        .collect::<Vec<_>>();
+    switch_distances.push(0.0);
+    switch_distances.sort_by(f32::total_cmp);
+    switch_distances.dedup();
+
+    let mut levels: Vec<(f32, Vec<usize>)> = Vec::new();
+    for distance in switch_distances {
+        let drawn = shapes_at(lod_ranges, distance);
+        if levels.last().map(|(_, last)| last) != Some(&drawn) {
+            levels.push((distance, drawn));
+        }
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_without_lod_are_a_single_level() {
+        assert_eq!(
+            lod_levels(&[ALL_DISTANCES, ALL_DISTANCES]),
+            vec![(0.0, vec![0, 1])]
+        );
+    }
+
+    #[test]
+    fn lod_children_switch_at_their_ranges() {
+        let levels = lod_levels(&[0.0..100.0, 100.0..400.0, 400.0..f32::INFINITY]);
+        assert_eq!(
+            levels,
+            vec![(0.0, vec![0]), (100.0, vec![1]), (400.0, vec![2])]
+        );
+    }
+
+    #[test]
+    fn shapes_outside_lod_nodes_are_drawn_at_every_level() {
+        let levels = lod_levels(&[ALL_DISTANCES, 0.0..50.0, 50.0..f32::INFINITY]);
+        assert_eq!(levels, vec![(0.0, vec![0, 1]), (50.0, vec![0, 2])]);
+    }
+
+    #[test]
+    fn last_range_ending_leaves_nothing_drawn() {
+        let levels = lod_levels(&[0.0..10.0, 10.0..20.0]);
+        assert_eq!(
+            levels,
+            vec![(0.0, vec![0]), (10.0, vec![1]), (20.0, vec![])]
+        );
+    }
+
+    #[test]
+    fn touching_ranges_of_the_same_shapes_are_one_level() {
+        // nested LOD nodes can split a range without changing what is drawn
+        let levels = lod_levels(&[0.0..30.0, 0.0..30.0, 30.0..f32::INFINITY]);
+        assert_eq!(levels, vec![(0.0, vec![0, 1]), (30.0, vec![2])]);
+    }
+
+    #[test]
+    fn nested_lod_ranges_overlap() {
+        assert_eq!(overlap(&(0.0..100.0), &(50.0..200.0)), 50.0..100.0);
+        assert!(overlap(&(0.0..10.0), &(20.0..30.0)).is_empty());
+        assert_eq!(overlap(&ALL_DISTANCES, &(5.0..15.0)), 5.0..15.0);
+    }
+
+    #[test]
+    fn fixed_distance_picks_the_ranges_containing_it() {
+        let ranges = [ALL_DISTANCES, 0.0..100.0, 100.0..f32::INFINITY];
+        assert_eq!(shapes_at(&ranges, 0.0), vec![0, 1]);
+        assert_eq!(shapes_at(&ranges, 100.0), vec![0, 2]);
+    }
+}
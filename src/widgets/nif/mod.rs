@@ -1,4 +1,10 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use dolly::{
     prelude::{Position, Smooth, YawPitch},
@@ -9,21 +15,159 @@ use egui_gizmo::{Gizmo, GizmoMode, GizmoOrientation};
 use nif::Nif;
 
 use self::{
-    camera::Camera, light::Light, nif_render_resources::NifRenderResources,
+    camera::{yaw_pitch_degrees, Camera, OrbitCamera},
+    light::{Light, NifLights, SceneLight},
+    nif_render_resources::{NifRenderResources, NifRenderResourcesMap},
+    scene_graph::SceneGraph,
     untextured_mesh::UntexturedMeshInstance,
 };
 
 mod camera;
 mod light;
+mod mesh_collector;
 mod nif_render_resources;
+mod scene_graph;
+mod shape_colors;
+mod skin;
 mod texture;
 pub mod untextured_mesh;
 mod untextured_mesh_pipeline;
 
+pub use self::scene_graph::{object_bounds, Pose};
+
+/// Camera placement that can be carried over when a widget is re-created.
+#[derive(Debug, Clone)]
+pub struct CameraState {
+    mode: CameraMode,
+    position: dolly::glam::Vec3,
+    yaw_degrees: f32,
+    pitch_degrees: f32,
+    orbit: OrbitCamera,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Free camera moved with WASD/QE and turned by dragging.
+    Fly,
+    /// Circles a target, drag to rotate, scroll to zoom and middle-drag to pan.
+    Orbit,
+}
+
+/// Shapes drawn over the viewport, in world space.
+#[derive(Debug, Clone)]
+pub enum OverlayShape {
+    Box {
+        min: glam::Vec3,
+        max: glam::Vec3,
+        color: egui::Color32,
+    },
+    Line {
+        from: glam::Vec3,
+        to: glam::Vec3,
+        color: egui::Color32,
+    },
+    Point {
+        position: glam::Vec3,
+        radius: f32,
+        color: egui::Color32,
+    },
+}
+
+/// Size of a mesh group, for listing the groups of a widget.
+#[derive(Debug, Clone)]
+pub struct GroupStats {
+    pub name: String,
+    pub triangle_count: usize,
+    pub instance_count: usize,
+}
+
+/// Drawn and total counts of the last frame, after culling.
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    pub drawn_instances: usize,
+    pub total_instances: usize,
+    pub drawn_triangles: usize,
+    pub total_triangles: usize,
+    pub degenerate_triangles: usize,
+}
+
+/// Per-widget view options, handed to the render resources every frame.
+#[derive(Debug, Clone, Default)]
+pub struct RenderSettings {
+    pub hidden_groups: HashSet<String>,
+    pub group_tints: HashMap<String, glam::Vec3>,
+    pub debug: DebugView,
+}
+
+/// Debug visualizations toggled from the viewport.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugView {
+    pub wireframe: bool,
+    pub normals: bool,
+    pub bounds: bool,
+    pub double_sided: bool,
+    pub degenerate_triangles: bool,
+    pub skeleton: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightMode {
+    Directional,
+    Point,
+    /// Directional light shining along the camera's view.
+    Headlight,
+}
+
+/// Lighting picked in the viewport, NIF lights take over when enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightSettings {
+    pub mode: LightMode,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub ambient_color: [f32; 3],
+    pub ambient_intensity: f32,
+    /// Direction towards the directional light.
+    pub azimuth_degrees: f32,
+    pub elevation_degrees: f32,
+    /// World position of the point light.
+    pub point_position: glam::Vec3,
+    pub use_nif_lights: bool,
+}
+
+impl Default for LightSettings {
+    fn default() -> Self {
+        Self {
+            mode: LightMode::Headlight,
+            color: [1.0; 3],
+            intensity: 1.0,
+            ambient_color: [1.0; 3],
+            ambient_intensity: 0.05,
+            azimuth_degrees: 45.0,
+            elevation_degrees: 45.0,
+            point_position: glam::Vec3::ZERO,
+            use_nif_lights: true,
+        }
+    }
+}
+
+static NEXT_WIDGET_ID: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug)]
 pub struct NifWidget {
-    light: Light,
+    /// Key of this widget's entry in the shared `NifRenderResourcesMap`.
+    id: u64,
+    /// Keeps the render resources alive, they are dropped with the widget.
+    _resources_owner: Arc<()>,
+    render_settings: RenderSettings,
+    overlay: Vec<OverlayShape>,
+    light_settings: LightSettings,
+    /// Lights of the NIFs shown without instances, by group.
+    nif_lights: HashMap<String, NifLights>,
+    /// Bone to parent lines of the same NIFs, by group.
+    skeletons: HashMap<String, Vec<(glam::Vec3, glam::Vec3)>>,
+    camera_mode: CameraMode,
     dolly_camera: CameraRig,
+    orbit_camera: OrbitCamera,
     camera: Camera,
     model_rotation: glam::Quat,
     combined_bounds: [f32; 3],
@@ -40,19 +184,37 @@ impl NifWidget {
             &camera,
         );
 
-        render_state
-            .egui_rpass
-            .write()
-            .paint_callback_resources
-            .insert(nif_render_resources);
+        let id = NEXT_WIDGET_ID.fetch_add(1, Ordering::Relaxed);
+        let resources_owner = Arc::new(());
+
+        let paint_callback_resources =
+            &mut render_state.egui_rpass.write().paint_callback_resources;
+        if paint_callback_resources
+            .get::<NifRenderResourcesMap>()
+            .is_none()
+        {
+            paint_callback_resources.insert(NifRenderResourcesMap::default());
+        }
+        paint_callback_resources
+            .get_mut::<NifRenderResourcesMap>()
+            .unwrap()
+            .insert(id, Arc::downgrade(&resources_owner), nif_render_resources);
 
         Self {
-            light,
+            id,
+            _resources_owner: resources_owner,
+            render_settings: Default::default(),
+            overlay: Vec::new(),
+            light_settings: Default::default(),
+            nif_lights: HashMap::new(),
+            skeletons: HashMap::new(),
+            camera_mode: CameraMode::Fly,
             dolly_camera: CameraRig::builder()
                 .with(Position::new(dolly::glam::Vec3::Z * 100.0))
                 .with(YawPitch::new().yaw_degrees(135.0).pitch_degrees(-45.0))
                 .with(Smooth::new_position_rotation(1.0, 1.0))
                 .build(),
+            orbit_camera: Default::default(),
             camera,
             model_rotation: glam::Quat::IDENTITY,
             combined_bounds: [0.0; 3],
@@ -66,12 +228,152 @@ impl NifWidget {
             dolly::glam::Vec3::new(horiz_distance, horiz_distance, bounds[2] * 2.0);
     }
 
+    pub fn camera_state(&mut self) -> CameraState {
+        let yaw_pitch = self.dolly_camera.driver_mut::<YawPitch>();
+        let (yaw_degrees, pitch_degrees) = (yaw_pitch.yaw_degrees, yaw_pitch.pitch_degrees);
+        CameraState {
+            mode: self.camera_mode,
+            position: self.dolly_camera.driver_mut::<Position>().position,
+            yaw_degrees,
+            pitch_degrees,
+            orbit: self.orbit_camera.clone(),
+        }
+    }
+
+    pub fn set_camera_state(&mut self, state: CameraState) {
+        self.camera_mode = state.mode;
+        self.orbit_camera = state.orbit;
+        self.dolly_camera.driver_mut::<Position>().position = state.position;
+        let yaw_pitch = self.dolly_camera.driver_mut::<YawPitch>();
+        yaw_pitch.yaw_degrees = state.yaw_degrees;
+        yaw_pitch.pitch_degrees = state.pitch_degrees;
+    }
+
+    pub fn is_group_visible(&self, group: &str) -> bool {
+        !self.render_settings.hidden_groups.contains(group)
+    }
+
+    pub fn set_group_visible(&mut self, group: &str, visible: bool) {
+        if visible {
+            self.render_settings.hidden_groups.remove(group);
+        } else {
+            self.render_settings.hidden_groups.insert(group.to_string());
+        }
+    }
+
+    pub fn group_tint(&self, group: &str) -> glam::Vec3 {
+        self.render_settings
+            .group_tints
+            .get(group)
+            .copied()
+            .unwrap_or(glam::Vec3::ONE)
+    }
+
+    pub fn set_group_tint(&mut self, group: &str, tint: glam::Vec3) {
+        if tint == glam::Vec3::ONE {
+            self.render_settings.group_tints.remove(group);
+        } else {
+            self.render_settings
+                .group_tints
+                .insert(group.to_string(), tint);
+        }
+    }
+
+    pub fn group_stats(&self, render_state: &eframe::egui_wgpu::RenderState) -> Vec<GroupStats> {
+        render_state
+            .egui_rpass
+            .read()
+            .paint_callback_resources
+            .get::<NifRenderResourcesMap>()
+            .and_then(|resources| resources.get(self.id))
+            .map(|resources| resources.group_stats())
+            .unwrap_or_default()
+    }
+
+    pub fn render_stats(&self, render_state: &eframe::egui_wgpu::RenderState) -> RenderStats {
+        render_state
+            .egui_rpass
+            .read()
+            .paint_callback_resources
+            .get::<NifRenderResourcesMap>()
+            .and_then(|resources| resources.get(self.id))
+            .map(|resources| resources.render_stats.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_overlay(&mut self, overlay: Vec<OverlayShape>) {
+        self.overlay = overlay;
+    }
+
+    /// Model space bounds of a mesh group, None for unknown or empty groups.
+    pub fn group_bounds(
+        &self,
+        render_state: &eframe::egui_wgpu::RenderState,
+        group: &str,
+    ) -> Option<(glam::Vec3, glam::Vec3)> {
+        render_state
+            .egui_rpass
+            .read()
+            .paint_callback_resources
+            .get::<NifRenderResourcesMap>()
+            .and_then(|resources| resources.get(self.id))
+            .and_then(|resources| resources.group_bounds(group))
+    }
+
+    /// Switches camera modes, keeping the current view.
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        if mode == self.camera_mode {
+            return;
+        }
+        match mode {
+            CameraMode::Fly => {
+                let (yaw_degrees, pitch_degrees) =
+                    yaw_pitch_degrees(self.camera.target - self.camera.eye);
+                self.dolly_camera.driver_mut::<Position>().position = self.camera.eye;
+                let yaw_pitch = self.dolly_camera.driver_mut::<YawPitch>();
+                yaw_pitch.yaw_degrees = yaw_degrees;
+                yaw_pitch.pitch_degrees = pitch_degrees;
+            }
+            CameraMode::Orbit => {
+                // orbit whatever is in front, as far away as the last orbit
+                let forward = (self.camera.target - self.camera.eye).normalize_or_zero();
+                let target = self.camera.eye + forward * self.orbit_camera.distance;
+                self.orbit_camera = OrbitCamera::looking_at(self.camera.eye, target);
+            }
+        }
+        self.camera_mode = mode;
+    }
+
+    /// Fits model space bounds to the view, following the model's gizmo rotation.
+    pub fn focus_on_model_bounds(&mut self, min: glam::Vec3, max: glam::Vec3) {
+        let (rotated_min, rotated_max) = rotated_bounds(self.model_rotation, min, max);
+        self.focus_on_bounds(rotated_min, rotated_max);
+    }
+
+    /// Moves the camera back along its view direction until the bounds fill the view, the
+    /// orbit camera also centres on them.
+    pub fn focus_on_bounds(&mut self, min: glam::Vec3, max: glam::Vec3) {
+        if self.camera_mode == CameraMode::Orbit {
+            self.orbit_camera.fit(min, max, self.camera.fov_y());
+            return;
+        }
+        let center = (min + max) / 2.0;
+        let radius = ((max - min).length() / 2.0).max(1.0);
+        let distance = radius / (self.camera.fov_y().to_radians() / 2.0).tan();
+        let forward = self.dolly_camera.final_transform.forward();
+        self.dolly_camera.driver_mut::<Position>().position =
+            dolly::glam::Vec3::from(center.to_array()) - forward * distance;
+    }
+
     pub fn clear_nifs(&mut self, render_state: &eframe::egui_wgpu::RenderState) {
+        self.nif_lights.clear();
+        self.skeletons.clear();
         let paint_callback_resources =
             &mut render_state.egui_rpass.write().paint_callback_resources;
 
         let nif_render_resources = paint_callback_resources
-            .get_mut::<NifRenderResources>()
+            .get_mut::<NifRenderResourcesMap>()
+            .and_then(|resources| resources.get_mut(self.id))
             .unwrap();
 
         nif_render_resources.clear_nifs();
@@ -81,7 +383,7 @@ impl NifWidget {
         &mut self,
         nif: &Nif,
         render_state: &eframe::egui_wgpu::RenderState,
-        lod_distance: f32,
+        lod_distance: Option<f32>,
         group: Option<String>,
         instances: Option<Vec<UntexturedMeshInstance>>,
     ) {
@@ -89,19 +391,55 @@ impl NifWidget {
             &mut render_state.egui_rpass.write().paint_callback_resources;
 
         let nif_render_resources = paint_callback_resources
-            .get_mut::<NifRenderResources>()
+            .get_mut::<NifRenderResourcesMap>()
+            .and_then(|resources| resources.get_mut(self.id))
             .unwrap();
 
+        let group_key = group.clone().unwrap_or_default();
+        self.nif_lights.remove(&group_key);
+        self.skeletons.remove(&group_key);
+        if instances.is_none() {
+            self.nif_lights
+                .insert(group_key.clone(), NifLights::new(nif));
+            self.skeletons
+                .insert(group_key, skin::skeleton(nif, &SceneGraph::new(nif)));
+        }
+
         nif_render_resources.set_nif(nif, lod_distance, group, instances);
 
         self.combined_bounds = nif_render_resources.combined_bounds;
     }
 
+    /// Moves the objects of a NIF shown with `set_nif` to an animated pose, without
+    /// collecting its shapes again.
+    pub fn set_pose(
+        &mut self,
+        nif: &Nif,
+        render_state: &eframe::egui_wgpu::RenderState,
+        group: Option<String>,
+        pose: &Pose,
+    ) {
+        let paint_callback_resources =
+            &mut render_state.egui_rpass.write().paint_callback_resources;
+
+        let nif_render_resources = paint_callback_resources
+            .get_mut::<NifRenderResourcesMap>()
+            .and_then(|resources| resources.get_mut(self.id))
+            .unwrap();
+
+        let group_key = group.unwrap_or_default();
+        let graph = SceneGraph::posed(nif, pose);
+        nif_render_resources.set_pose(&group_key, &graph);
+        if let Some(skeleton) = self.skeletons.get_mut(&group_key) {
+            *skeleton = skin::skeleton(nif, &graph);
+        }
+    }
+
     pub fn add_nif(
         &mut self,
         nif: &Nif,
         render_state: &eframe::egui_wgpu::RenderState,
-        lod_distance: f32,
+        lod_distance: Option<f32>,
         group: Option<String>,
         instances: Option<Vec<UntexturedMeshInstance>>,
     ) {
@@ -109,21 +447,292 @@ impl NifWidget {
             &mut render_state.egui_rpass.write().paint_callback_resources;
 
         let nif_render_resources = paint_callback_resources
-            .get_mut::<NifRenderResources>()
+            .get_mut::<NifRenderResourcesMap>()
+            .and_then(|resources| resources.get_mut(self.id))
             .unwrap();
 
+        // lights of placed models would be repeated for every instance, only take them from
+        // NIFs shown on their own
+        if instances.is_none() {
+            let group_key = group.clone().unwrap_or_default();
+            self.nif_lights
+                .entry(group_key.clone())
+                .or_default()
+                .extend(&NifLights::new(nif));
+            self.skeletons
+                .entry(group_key)
+                .or_default()
+                .extend(skin::skeleton(nif, &SceneGraph::new(nif)));
+        }
+
         nif_render_resources.add_nif(nif, lod_distance, group, instances);
 
         self.combined_bounds = nif_render_resources.combined_bounds;
     }
 
+    fn nif_lights(&self) -> NifLights {
+        let mut lights = NifLights::default();
+        for group_lights in self.nif_lights.values() {
+            lights.extend(group_lights);
+        }
+        lights
+    }
+
+    /// Lights for the shader, from the NIF when it has any and they are enabled.
+    fn scene_light(&self) -> Light {
+        let settings = &self.light_settings;
+        let mut ambient = glam::Vec3::from(settings.ambient_color) * settings.ambient_intensity;
+        let color = glam::Vec3::from(settings.color) * settings.intensity;
+        let mut lights = vec![match settings.mode {
+            LightMode::Directional => {
+                let (azimuth, elevation) = (
+                    settings.azimuth_degrees.to_radians(),
+                    settings.elevation_degrees.to_radians(),
+                );
+                let direction = glam::vec3(
+                    elevation.cos() * azimuth.cos(),
+                    elevation.cos() * azimuth.sin(),
+                    elevation.sin(),
+                );
+                SceneLight::directional(direction, color)
+            }
+            LightMode::Point => SceneLight::point(settings.point_position, color),
+            LightMode::Headlight => {
+                SceneLight::directional(self.camera.eye - self.camera.target, color)
+            }
+        }];
+
+        if settings.use_nif_lights {
+            // NIF lights follow the model when it is rotated with the gizmo
+            let nif_lights = self.nif_lights();
+            if !nif_lights.lights.is_empty() {
+                lights = nif_lights
+                    .lights
+                    .iter()
+                    .map(|light| light.rotated(self.model_rotation))
+                    .collect();
+            }
+            ambient = nif_lights.ambient.unwrap_or(ambient);
+        }
+
+        Light::new(&lights, ambient)
+    }
+
+    fn lighting_menu(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.light_settings;
+        let nif_light_count = self
+            .nif_lights
+            .values()
+            .map(|lights| lights.lights.len() + lights.ambient.is_some() as usize)
+            .sum::<usize>();
+        if nif_light_count > 0 {
+            ui.checkbox(
+                &mut settings.use_nif_lights,
+                format!("Use NIF lights ({})", nif_light_count),
+            );
+            ui.separator();
+        }
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut settings.mode, LightMode::Headlight, "Headlight");
+            ui.radio_value(&mut settings.mode, LightMode::Directional, "Directional");
+            ui.radio_value(&mut settings.mode, LightMode::Point, "Point");
+        });
+        match settings.mode {
+            LightMode::Directional => {
+                ui.add(
+                    egui::Slider::new(&mut settings.azimuth_degrees, 0.0..=360.0).text("Azimuth"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.elevation_degrees, -90.0..=90.0)
+                        .text("Elevation"),
+                );
+            }
+            LightMode::Point => {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut settings.point_position.x).prefix("x: "));
+                    ui.add(egui::DragValue::new(&mut settings.point_position.y).prefix("y: "));
+                    ui.add(egui::DragValue::new(&mut settings.point_position.z).prefix("z: "));
+                });
+                if ui.button("Place at camera").clicked() {
+                    settings.point_position = self.camera.eye;
+                }
+            }
+            LightMode::Headlight => {}
+        }
+        ui.horizontal(|ui| {
+            ui.color_edit_button_rgb(&mut settings.color);
+            ui.add(egui::Slider::new(&mut settings.intensity, 0.0..=4.0).text("Intensity"));
+        });
+        ui.horizontal(|ui| {
+            ui.color_edit_button_rgb(&mut settings.ambient_color);
+            ui.add(egui::Slider::new(&mut settings.ambient_intensity, 0.0..=1.0).text("Ambient"));
+        });
+    }
+
+    fn paint_overlay(&self, painter: egui::Painter, rect: egui::Rect) {
+        let view_proj = self.camera.build_projection_matrix() * self.camera.build_view_matrix();
+        let project = |point: glam::Vec3| {
+            let clip = view_proj * point.extend(1.0);
+            if clip.w <= 0.0 {
+                return None;
+            }
+            let ndc = clip.truncate() / clip.w;
+            Some(egui::pos2(
+                rect.left() + (ndc.x + 1.0) / 2.0 * rect.width(),
+                rect.top() + (1.0 - ndc.y) / 2.0 * rect.height(),
+            ))
+        };
+        let line = |from: glam::Vec3, to: glam::Vec3, color: egui::Color32| {
+            if let (Some(from), Some(to)) = (project(from), project(to)) {
+                painter.line_segment([from, to], egui::Stroke::new(1.5, color));
+            }
+        };
+
+        if self.render_settings.debug.skeleton {
+            let skeleton_color = egui::Color32::from_rgb(255, 220, 0);
+            for &(parent, bone) in self.skeletons.values().flatten() {
+                let (parent, bone) = (self.model_rotation * parent, self.model_rotation * bone);
+                line(parent, bone, skeleton_color);
+                if let Some(bone) = project(bone) {
+                    painter.circle_filled(bone, 2.5, skeleton_color);
+                }
+            }
+        }
+
+        for shape in self.overlay.iter() {
+            match *shape {
+                OverlayShape::Box { min, max, color } => {
+                    let corner = |idx: usize| {
+                        glam::vec3(
+                            if idx & 1 == 0 { min.x } else { max.x },
+                            if idx & 2 == 0 { min.y } else { max.y },
+                            if idx & 4 == 0 { min.z } else { max.z },
+                        )
+                    };
+                    for idx in 0..8 {
+                        for axis in [1, 2, 4] {
+                            if idx & axis == 0 {
+                                line(corner(idx), corner(idx | axis), color);
+                            }
+                        }
+                    }
+                }
+                OverlayShape::Line { from, to, color } => line(from, to, color),
+                OverlayShape::Point {
+                    position,
+                    radius,
+                    color,
+                } => {
+                    if let Some(position) = project(position) {
+                        painter.circle_filled(position, radius, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Turns the fly camera by dragging and moves it with WASD/QE, shift moves faster.
+    fn update_fly_camera(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        dt: f32,
+        moving: &mut bool,
+    ) -> (glam::Vec3, glam::Vec3, glam::Vec3) {
+        // rotation from dragging mouse
+        let drag_horizontal = response.drag_delta().x.min(359.0);
+        let drag_vertical = response.drag_delta().y.min(359.0);
+        let yaw_speed = -1.0 / 3.0;
+        let pitch_speed = -1.0 / 3.0;
+
+        // position from keyboard
+        let input = ui.input();
+        let forward = if input.key_down(egui::Key::W) {
+            1.0
+        } else if input.key_down(egui::Key::S) {
+            -1.0
+        } else {
+            0.0
+        };
+        let right = if input.key_down(egui::Key::D) {
+            1.0
+        } else if input.key_down(egui::Key::A) {
+            -1.0
+        } else {
+            0.0
+        };
+        let up = if input.key_down(egui::Key::Q) {
+            1.0
+        } else if input.key_down(egui::Key::E) {
+            -1.0
+        } else {
+            0.0
+        };
+        let boost = if input.modifiers.shift { 1.0 } else { 0.0 };
+        *moving |= forward != 0.0 || right != 0.0 || up != 0.0;
+
+        let move_vec = self.dolly_camera.final_transform.rotation
+            * dolly::glam::Vec3::new(right, forward, up).clamp_length_max(1.0)
+            * 10.0f32.powf(boost);
+
+        self.dolly_camera
+            .driver_mut::<YawPitch>()
+            .rotate_yaw_pitch(drag_horizontal * yaw_speed, drag_vertical * pitch_speed);
+
+        self.dolly_camera
+            .driver_mut::<Position>()
+            .translate(move_vec * dt * 50.0);
+
+        let transform = self.dolly_camera.update(dt);
+        (
+            transform.position,
+            transform.up(),
+            transform.position + transform.forward(),
+        )
+    }
+
+    /// Rotates the orbit camera by dragging, pans it by middle-dragging and zooms by
+    /// scrolling.
+    fn update_orbit_camera(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        rect: egui::Rect,
+    ) -> (glam::Vec3, glam::Vec3, glam::Vec3) {
+        let drag = response.drag_delta();
+        if response.dragged_by(egui::PointerButton::Middle) {
+            // keeps the point under the cursor under it at the target's depth
+            let view_height = 2.0 * (self.camera.fov_y().to_radians() / 2.0).tan();
+            let scale = view_height / rect.height().max(1.0);
+            self.orbit_camera.pan(-drag.x * scale, drag.y * scale);
+        } else if response.dragged_by(egui::PointerButton::Primary) {
+            self.orbit_camera
+                .rotate(drag.x.min(359.0) / -3.0, drag.y.min(359.0) / -3.0);
+        }
+
+        if response.hovered() {
+            let scroll = ui.input().scroll_delta.y;
+            if scroll != 0.0 {
+                self.orbit_camera.zoom((-scroll * 0.002).exp());
+            }
+        }
+
+        (
+            self.orbit_camera.eye(),
+            glam::Vec3::Z,
+            self.orbit_camera.target,
+        )
+    }
+
     pub fn show(
         &mut self,
         ui: &mut egui::Ui,
-        _frame: &mut eframe::Frame,
+        frame: &mut eframe::Frame,
         space_aside: Option<egui::Vec2>,
     ) {
         let dt = ui.input().stable_dt;
+        let render_stats = self.render_stats(frame.wgpu_render_state().unwrap());
 
         egui::Frame::canvas(ui.style()).show(ui, |ui| {
             // Create canvas
@@ -136,85 +745,48 @@ impl NifWidget {
             // Set new aspect ratio, canvas could've been resized
             self.camera.aspect_ratio = rect.aspect_ratio();
 
-            let dolly_transform = {
-                // rotation from dragging mouse
-                let drag_horizontal = response.drag_delta().x.min(359.0);
-                let drag_vertical = response.drag_delta().y.min(359.0);
-                let yaw_speed = -1.0 / 3.0;
-                let pitch_speed = -1.0 / 3.0;
-
-                // position from keyboard
-                let input = ui.input();
-                let forward = if input.key_down(egui::Key::W) {
-                    1.0
-                } else if input.key_down(egui::Key::S) {
-                    -1.0
-                } else {
-                    0.0
-                };
-                let right = if input.key_down(egui::Key::D) {
-                    1.0
-                } else if input.key_down(egui::Key::A) {
-                    -1.0
-                } else {
-                    0.0
-                };
-                let up = if input.key_down(egui::Key::Q) {
-                    1.0
-                } else if input.key_down(egui::Key::E) {
-                    -1.0
-                } else {
-                    0.0
-                };
-                let boost = if input.modifiers.shift { 1.0 } else { 0.0 };
-
-                let move_vec = self.dolly_camera.final_transform.rotation
-                    * dolly::glam::Vec3::new(right, forward, up).clamp_length_max(1.0)
-                    * 10.0f32.powf(boost);
-
-                self.dolly_camera
-                    .driver_mut::<YawPitch>()
-                    .rotate_yaw_pitch(drag_horizontal * yaw_speed, drag_vertical * pitch_speed);
-
-                self.dolly_camera
-                    .driver_mut::<Position>()
-                    .translate(move_vec * dt * 50.0);
-
-                self.dolly_camera.update(dt)
+            let mut moving = response.dragged();
+            let (eye, up, target) = match self.camera_mode {
+                CameraMode::Fly => self.update_fly_camera(ui, &response, dt, &mut moving),
+                CameraMode::Orbit => self.update_orbit_camera(ui, &response, rect),
             };
 
-            self.camera.eye = dolly_transform.position;
-            self.camera.up = dolly_transform.up();
-            self.camera.target = dolly_transform.position + dolly_transform.forward();
-
-            // Scroll to zoom in and out
-            // if response.hovered() {
-            //     let mut speed_mult = 0.02;
-            //     if ui.input().modifiers.alt {
-            //         speed_mult = 0.2;
-            //     }
-            //     self.camera.eye.z += ui.input().scroll_delta.y * speed_mult;
-            // }
-            // self.camera.eye.x = (self.camera_yaw.cos() * self.camera_distance) as f32;
-            // self.camera.eye.y = (self.camera_yaw.sin() * self.camera_distance) as f32;
-
-            // let light_yaw = self.camera_yaw as f32 + FRAC_PI_2 + FRAC_PI_4;
-            // self.light.position[0] = light_yaw.cos() * self.camera_distance as f32 * 2.0;
-            // self.light.position[1] = light_yaw.sin() * self.camera_distance as f32 * 2.0;
+            let previous_eye = self.camera.eye;
+            let previous_target = self.camera.target;
+            self.camera.eye = eye;
+            self.camera.up = up;
+            self.camera.target = target;
+            // the camera keeps easing towards its target after input stops
+            moving |= self.camera.eye.distance_squared(previous_eye) > 1e-6
+                || self.camera.target.distance_squared(previous_target) > 1e-6;
 
             let camera = self.camera.clone();
-            let light = self.light;
+            let light = self.scene_light();
             let model_rotation = self.model_rotation;
+            let widget_id = self.id;
+            let render_settings = Arc::new(self.render_settings.clone());
+            let paint_render_settings = render_settings.clone();
 
             let cb = egui_wgpu::CallbackFn::new()
                 .prepare(move |device, queue, paint_callback_resources| {
-                    let resources: &mut NifRenderResources =
+                    let resources: &mut NifRenderResourcesMap =
                         paint_callback_resources.get_mut().unwrap();
-                    resources.prepare(device, queue, &camera, &light, &model_rotation);
+                    if let Some(resources) = resources.get_mut(widget_id) {
+                        resources.prepare(
+                            device,
+                            queue,
+                            &camera,
+                            &light,
+                            &model_rotation,
+                            &render_settings,
+                        );
+                    }
                 })
                 .paint(move |_info, rpass, paint_callback_resources| {
-                    let resources: &NifRenderResources = paint_callback_resources.get().unwrap();
-                    resources.paint(rpass)
+                    let resources: &NifRenderResourcesMap = paint_callback_resources.get().unwrap();
+                    if let Some(resources) = resources.get(widget_id) {
+                        resources.paint(rpass, &paint_render_settings)
+                    }
                 });
             let callback = egui::PaintCallback {
                 rect,
@@ -237,13 +809,60 @@ impl NifWidget {
                                 "/jump {:02} {:02} {:02}",
                                 self.camera.eye.x,
                                 self.camera.eye.y,
-                                self.dolly_camera.driver_mut::<YawPitch>().yaw_degrees + 90.0
+                                yaw_pitch_degrees(self.camera.target - self.camera.eye).0 + 90.0
+                            );
+                        }
+                        let mut camera_mode = self.camera_mode;
+                        ui.selectable_value(&mut camera_mode, CameraMode::Fly, "Fly")
+                            .on_hover_text("WASD/QE to move, drag to look around");
+                        ui.selectable_value(&mut camera_mode, CameraMode::Orbit, "Orbit")
+                            .on_hover_text("Drag to rotate, scroll to zoom, middle-drag to pan");
+                        if camera_mode != self.camera_mode {
+                            self.set_camera_mode(camera_mode);
+                            ui.ctx().request_repaint();
+                        }
+                        ui.label(format!(
+                            "Drawn: {}/{} instances, {}/{} triangles",
+                            render_stats.drawn_instances,
+                            render_stats.total_instances,
+                            render_stats.drawn_triangles,
+                            render_stats.total_triangles
+                        ));
+                        let debug = &mut self.render_settings.debug;
+                        let previous_debug = debug.clone();
+                        ui.menu_button("Debug", |ui| {
+                            ui.checkbox(&mut debug.wireframe, "Wireframe");
+                            ui.checkbox(&mut debug.normals, "Vertex normals");
+                            ui.checkbox(&mut debug.bounds, "Instance bounds");
+                            ui.checkbox(&mut debug.double_sided, "Disable back-face culling");
+                            ui.checkbox(&mut debug.degenerate_triangles, "Degenerate triangles");
+                            ui.checkbox(&mut debug.skeleton, "Skeleton");
+                        });
+                        // the paint callback already holds this frame's settings
+                        if *debug != previous_debug {
+                            ui.ctx().request_repaint();
+                        }
+                        let previous_light_settings = self.light_settings.clone();
+                        ui.menu_button("Lighting", |ui| self.lighting_menu(ui));
+                        if self.light_settings != previous_light_settings {
+                            ui.ctx().request_repaint();
+                        }
+                        let debug = &self.render_settings.debug;
+                        if debug.degenerate_triangles {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "{} degenerate triangles",
+                                    render_stats.degenerate_triangles
+                                ))
+                                .color(egui::Color32::from_rgb(255, 0, 255)),
                             );
                         }
                     })
                 })
             });
 
+            self.paint_overlay(ui.painter_at(rect), rect);
+
             let gizmo = Gizmo::new("nif_gizmo")
                 .view_matrix(self.camera.build_view_matrix().to_cols_array_2d())
                 .projection_matrix(self.camera.build_projection_matrix().to_cols_array_2d())
@@ -259,8 +878,50 @@ impl NifWidget {
                 self.model_rotation = transform_rot;
             }
 
-            // Keep animating (non-reactive)
-            response.ctx.request_repaint();
+            // Only keep repainting while the camera moves, culling follows the camera
+            if moving {
+                response.ctx.request_repaint();
+            }
         });
     }
 }
+
+/// Bounds around a rotated box, which needs all of its corners since any of them can end up
+/// furthest out.
+fn rotated_bounds(
+    rotation: glam::Quat,
+    min: glam::Vec3,
+    max: glam::Vec3,
+) -> (glam::Vec3, glam::Vec3) {
+    (0..8)
+        .map(|corner| {
+            let pick_max = glam::BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0);
+            rotation * glam::Vec3::select(pick_max, max, min)
+        })
+        .fold(
+            (
+                glam::Vec3::splat(f32::INFINITY),
+                glam::Vec3::splat(f32::NEG_INFINITY),
+            ),
+            |(low, high), corner| (low.min(corner), high.max(corner)),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_bounds_cover_every_corner() {
+        let rotation = glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_4);
+        let (min, max) = rotated_bounds(
+            rotation,
+            glam::vec3(0.0, 0.0, 0.0),
+            glam::vec3(2.0, 1.0, 1.0),
+        );
+        let half_diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        // (0, 1) swings to negative x, (2, 0) goes furthest along x and (2, 1) along y
+        assert!((min - glam::vec3(-half_diagonal, 0.0, 0.0)).length() < 1e-5);
+        assert!((max - glam::vec3(2.0 * half_diagonal, 3.0 * half_diagonal, 1.0)).length() < 1e-5);
+    }
+}
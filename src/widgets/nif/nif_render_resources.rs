@@ -1,20 +1,48 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Weak};
 
 use eframe::wgpu;
 use nif::Nif;
 
 use super::{
     light::Light,
+    scene_graph::SceneGraph,
     texture::Texture,
     untextured_mesh::{UntexturedMesh, UntexturedMeshInstance},
-    untextured_mesh_pipeline::UntexturedMeshPipeline,
-    Camera,
+    untextured_mesh_pipeline::{AlphaMode, DebugLines, UntexturedMeshPipeline},
+    Camera, GroupStats, RenderSettings, RenderStats,
 };
 
+/// Render resources of every live `NifWidget`, keyed by widget id.
+///
+/// Entries are dropped once the widget owning them is gone.
+#[derive(Default)]
+pub struct NifRenderResourcesMap {
+    resources: HashMap<u64, (Weak<()>, NifRenderResources)>,
+}
+
+impl NifRenderResourcesMap {
+    pub fn insert(&mut self, id: u64, owner: Weak<()>, resources: NifRenderResources) {
+        self.resources
+            .retain(|_, (owner, _)| owner.strong_count() > 0);
+        self.resources.insert(id, (owner, resources));
+    }
+
+    pub fn get(&self, id: u64) -> Option<&NifRenderResources> {
+        self.resources.get(&id).map(|(_, resources)| resources)
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut NifRenderResources> {
+        self.resources.get_mut(&id).map(|(_, resources)| resources)
+    }
+}
+
 pub struct NifRenderResources {
     untextured_mesh_pipeline: UntexturedMeshPipeline,
     pub meshes: HashMap<String, UntexturedMesh>,
     pub combined_bounds: [f32; 3],
+    pub render_stats: RenderStats,
+    /// Draws with blended triangles as (group, index into the mesh's draws), back to front.
+    blended_draws: Vec<(String, usize)>,
 }
 
 impl NifRenderResources {
@@ -30,6 +58,8 @@ impl NifRenderResources {
             untextured_mesh_pipeline,
             meshes: Default::default(),
             combined_bounds: [0.0; 3],
+            render_stats: Default::default(),
+            blended_draws: Vec::new(),
         }
     }
 
@@ -41,7 +71,7 @@ impl NifRenderResources {
     pub fn add_nif(
         &mut self,
         nif: &Nif,
-        lod_distance: f32,
+        lod_distance: Option<f32>,
         group: Option<String>,
         instances: Option<Vec<UntexturedMeshInstance>>,
     ) {
@@ -74,7 +104,7 @@ impl NifRenderResources {
     pub fn set_nif(
         &mut self,
         nif: &Nif,
-        lod_distance: f32,
+        lod_distance: Option<f32>,
         group: Option<String>,
         instances: Option<Vec<UntexturedMeshInstance>>,
     ) {
@@ -82,6 +112,13 @@ impl NifRenderResources {
         self.add_nif(nif, lod_distance, group, instances)
     }
 
+    /// Poses the mesh of a group holding a single NIF.
+    pub fn set_pose(&mut self, group: &str, graph: &SceneGraph) {
+        if let Some(mesh) = self.meshes.get_mut(group) {
+            mesh.set_pose(graph);
+        }
+    }
+
     pub fn prepare(
         &mut self,
         device: &wgpu::Device,
@@ -89,45 +126,197 @@ impl NifRenderResources {
         camera: &Camera,
         light: &Light,
         model_rotation: &glam::Quat,
+        settings: &RenderSettings,
     ) {
         self.untextured_mesh_pipeline.update_camera(queue, camera);
         self.untextured_mesh_pipeline.update_light(queue, light);
 
-        for mesh in self.meshes.values_mut() {
+        if let Some(mesh) = self.meshes.values_mut().next() {
+            mesh.set_instance_rotation(0, *model_rotation);
+        }
+
+        let frustum = camera.frustum();
+        let mut stats = RenderStats::default();
+        for (group, mesh) in self.meshes.iter_mut() {
+            let tint = settings.group_tints.get(group).copied();
+            mesh.set_tint(tint.unwrap_or(glam::Vec3::ONE));
             if mesh.buffers().is_none() {
-                mesh.upload(device);
+                mesh.upload(
+                    device,
+                    self.untextured_mesh_pipeline.bones_bind_group_layout(),
+                );
             }
+            mesh.write_bones(queue);
+            mesh.prepare_debug(device, &settings.debug);
+            stats.degenerate_triangles += mesh.degenerate_triangle_count;
+
+            stats.total_instances += mesh.instances.len();
+            stats.total_triangles += mesh.triangle_count() * mesh.instances.len();
+            if settings.hidden_groups.contains(group) {
+                continue;
+            }
+            mesh.cull(queue, &frustum, camera.eye);
+            stats.drawn_instances += mesh.visible_instances.len();
+            stats.drawn_triangles += mesh.visible_triangle_count();
         }
+        self.render_stats = stats;
 
-        if let Some(mesh) = self.meshes.values_mut().next() {
-            let instance = mesh.instances.get_mut(0).unwrap();
-            instance.rotation = *model_rotation;
-            let instance = instance.clone();
-            if let Some((_, _, instance_buffer)) = mesh.buffers() {
-                queue.write_buffer(
-                    instance_buffer,
-                    0,
-                    bytemuck::cast_slice(&[instance.to_raw()]),
-                );
+        let mut blended_draws = Vec::new();
+        for (group, mesh) in self.meshes.iter() {
+            if settings.hidden_groups.contains(group) || mesh.visible_instances.is_empty() {
+                continue;
+            }
+            for (idx, draw) in mesh.draws.iter().enumerate() {
+                if !draw.alpha_ranges[AlphaMode::Blended as usize].is_empty() {
+                    blended_draws.push((draw.distance, group.clone(), idx));
+                }
             }
         }
+        blended_draws.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+        self.blended_draws = blended_draws
+            .into_iter()
+            .map(|(_, group, idx)| (group, idx))
+            .collect();
     }
 
-    pub fn paint<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
-        self.untextured_mesh_pipeline.set(rpass);
+    /// Triangle and instance counts of every mesh group, sorted by name.
+    pub fn group_stats(&self) -> Vec<GroupStats> {
+        let mut stats = self
+            .meshes
+            .iter()
+            .map(|(group, mesh)| GroupStats {
+                name: group.clone(),
+                triangle_count: mesh.triangle_count(),
+                instance_count: mesh.instances.len(),
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
 
-        for (group, mesh) in self.meshes.iter() {
-            if let Some((vertex_buffer, index_buffer, instance_buffer)) = mesh.buffers() {
+    /// Model space bounds of a mesh group.
+    pub fn group_bounds(&self, group: &str) -> Option<(glam::Vec3, glam::Vec3)> {
+        self.meshes
+            .get(group)
+            .filter(|mesh| !mesh.vertices.is_empty())
+            .map(|mesh| (mesh.bounds_min, mesh.bounds_max))
+    }
+
+    pub fn paint<'rpass>(
+        &'rpass self,
+        rpass: &mut wgpu::RenderPass<'rpass>,
+        settings: &RenderSettings,
+    ) {
+        let debug = &settings.debug;
+        let pipeline = &self.untextured_mesh_pipeline;
+
+        let visible_meshes = || {
+            self.meshes
+                .iter()
+                .filter(|(group, mesh)| {
+                    !settings.hidden_groups.contains(*group) && !mesh.visible_instances.is_empty()
+                })
+                .filter_map(|(group, mesh)| Some((group, mesh, mesh.buffers()?)))
+        };
+
+        for alpha_mode in [AlphaMode::Opaque, AlphaMode::Tested] {
+            for (group, mesh, (vertex_buffer, index_buffer, instance_buffer)) in visible_meshes() {
                 rpass.push_debug_group(group);
+                pipeline.set(rpass, alpha_mode, !debug.double_sided, mesh.is_skinned());
+                mesh.set_bones(rpass);
+                rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                rpass.set_vertex_buffer(1, instance_buffer.slice(..));
+                rpass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                for draw in mesh.draws.iter() {
+                    let index_range = draw.alpha_ranges[alpha_mode as usize].clone();
+                    if !index_range.is_empty() {
+                        rpass.draw_indexed(index_range, 0, draw.instance_range.clone());
+                    }
+                }
+                rpass.pop_debug_group();
+            }
+        }
+
+        // blended draws go last and back to front, across groups
+        for (group, draw_idx) in self.blended_draws.iter() {
+            let mesh = &self.meshes[group];
+            if let Some((vertex_buffer, index_buffer, instance_buffer)) = mesh.buffers() {
+                let draw = &mesh.draws[*draw_idx];
+                pipeline.set(
+                    rpass,
+                    AlphaMode::Blended,
+                    !debug.double_sided,
+                    mesh.is_skinned(),
+                );
+                mesh.set_bones(rpass);
                 rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
                 rpass.set_vertex_buffer(1, instance_buffer.slice(..));
                 rpass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
                 rpass.draw_indexed(
-                    0..(mesh.indices.len() as _),
+                    draw.alpha_ranges[AlphaMode::Blended as usize].clone(),
                     0,
-                    0..mesh.instances.len() as _,
+                    draw.instance_range.clone(),
                 );
-                rpass.pop_debug_group();
+            }
+        }
+
+        if debug.wireframe {
+            pipeline.set_lines(rpass, DebugLines::Wireframe);
+            for (_, mesh, (vertex_buffer, _, instance_buffer)) in visible_meshes() {
+                if let Some(wireframe_indices) = &mesh.debug_buffers.wireframe_indices {
+                    mesh.set_bones(rpass);
+                    rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                    rpass.set_vertex_buffer(1, instance_buffer.slice(..));
+                    rpass.set_index_buffer(wireframe_indices.slice(..), wgpu::IndexFormat::Uint32);
+                    for draw in mesh.draws.iter() {
+                        let index_range = draw.index_range.start * 2..draw.index_range.end * 2;
+                        rpass.draw_indexed(index_range, 0, draw.instance_range.clone());
+                    }
+                }
+            }
+        }
+        if debug.normals {
+            pipeline.set_lines(rpass, DebugLines::Normals);
+            for (_, mesh, (_, _, instance_buffer)) in visible_meshes() {
+                if let Some(normal_vertices) = &mesh.debug_buffers.normal_vertices {
+                    mesh.set_bones(rpass);
+                    rpass.set_vertex_buffer(0, normal_vertices.slice(..));
+                    rpass.set_vertex_buffer(1, instance_buffer.slice(..));
+                    for draw in mesh.draws.iter() {
+                        let vertex_range = draw.vertex_range.start * 2..draw.vertex_range.end * 2;
+                        rpass.draw(vertex_range, draw.instance_range.clone());
+                    }
+                }
+            }
+        }
+        if debug.bounds {
+            pipeline.set_lines(rpass, DebugLines::Bounds);
+            for (_, mesh, (_, _, instance_buffer)) in visible_meshes() {
+                if let Some((bounds_vertices, bounds_indices)) = &mesh.debug_buffers.bounds {
+                    mesh.set_bones(rpass);
+                    rpass.set_vertex_buffer(0, bounds_vertices.slice(..));
+                    rpass.set_vertex_buffer(1, instance_buffer.slice(..));
+                    rpass.set_index_buffer(bounds_indices.slice(..), wgpu::IndexFormat::Uint32);
+                    rpass.draw_indexed(0..24, 0, 0..mesh.visible_instances.len() as _);
+                }
+            }
+        }
+        if debug.degenerate_triangles {
+            pipeline.set_lines(rpass, DebugLines::Degenerate);
+            for (_, mesh, (vertex_buffer, _, instance_buffer)) in visible_meshes() {
+                if let Some((Some(degenerate_indices), offsets)) = &mesh.debug_buffers.degenerate {
+                    mesh.set_bones(rpass);
+                    rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                    rpass.set_vertex_buffer(1, instance_buffer.slice(..));
+                    rpass.set_index_buffer(degenerate_indices.slice(..), wgpu::IndexFormat::Uint32);
+                    for draw in mesh.draws.iter() {
+                        let index_range = offsets[draw.index_range.start as usize / 3]
+                            ..offsets[draw.index_range.end as usize / 3];
+                        if !index_range.is_empty() {
+                            rpass.draw_indexed(index_range, 0, draw.instance_range.clone());
+                        }
+                    }
+                }
             }
         }
     }
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use nif::{
+    blocks::{Block, NiAvObject, NiNode},
+    Nif,
+};
+
+use super::shape_colors::{local_transform, rotation_matrix};
+
+/// Animated parts of an object's local transform, the rest stays as stored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PosedTransform {
+    pub translation: Option<glam::Vec3>,
+    pub rotation: Option<glam::Quat>,
+    pub scale: Option<f32>,
+}
+
+/// Animated transforms of a NIF's objects, by block index.
+pub type Pose = HashMap<usize, PosedTransform>;
+
+/// World transforms of the objects reachable from the root, and the parent of each, by
+/// block index.
+pub(super) struct SceneGraph {
+    pub world: HashMap<usize, glam::Mat4>,
+    pub parents: HashMap<usize, usize>,
+}
+
+impl SceneGraph {
+    pub fn new(nif: &Nif) -> Self {
+        Self::posed(nif, &Pose::new())
+    }
+
+    /// World transforms with the objects in `pose` moved to their animated transforms.
+    pub fn posed(nif: &Nif, pose: &Pose) -> Self {
+        let mut me = Self {
+            world: HashMap::new(),
+            parents: HashMap::new(),
+        };
+        if !nif.blocks.is_empty() {
+            me.add_block(&nif.blocks, pose, 0, glam::Mat4::IDENTITY);
+        }
+        me
+    }
+
+    fn add_block(
+        &mut self,
+        blocks: &[Block],
+        pose: &Pose,
+        idx: usize,
+        parent_transform: glam::Mat4,
+    ) {
+        let block = match blocks.get(idx) {
+            Some(block) => block,
+            None => return,
+        };
+        let av_object = match (block, as_node(block)) {
+            (_, Some(node)) => &node.base,
+            (Block::NiTriShape(shape), _) => &shape.base,
+            _ => return,
+        };
+        // guards against cycles
+        if self.world.contains_key(&idx) {
+            return;
+        }
+        let local = match pose.get(&idx) {
+            Some(posed) => posed_transform(av_object, posed),
+            None => local_transform(av_object),
+        };
+        let transform = parent_transform * local;
+        self.world.insert(idx, transform);
+        let child_refs = as_node(block).map(|node| node.child_refs.as_slice());
+        for child_ref in child_refs.unwrap_or_default() {
+            if let Ok(child_idx) = usize::try_from(child_ref.0) {
+                self.parents.insert(child_idx, idx);
+                self.add_block(blocks, pose, child_idx, transform);
+            }
+        }
+    }
+}
+
+fn posed_transform(av_object: &NiAvObject, posed: &PosedTransform) -> glam::Mat4 {
+    let t = &av_object.translation;
+    let translation = posed
+        .translation
+        .unwrap_or_else(|| glam::vec3(t.x, t.y, t.z));
+    let rotation = posed
+        .rotation
+        .map(glam::Mat3::from_quat)
+        .unwrap_or_else(|| rotation_matrix(&av_object.rotation));
+    let scale = posed.scale.unwrap_or(av_object.scale);
+    glam::Mat4::from_translation(translation)
+        * glam::Mat4::from_mat3(rotation)
+        * glam::Mat4::from_scale(glam::Vec3::splat(scale))
+}
+
+/// The node part of blocks with children, LOD nodes included.
+pub(super) fn as_node(block: &Block) -> Option<&NiNode> {
+    match block {
+        Block::NiNode(node) => Some(node),
+        Block::NiLODNode(lod_node) => Some(&lod_node.base.base),
+        _ => None,
+    }
+}
+
+/// Model space bounds of the shapes at and below a block in a pose, a shape's data block
+/// counts as the shape. None when nothing with vertices is found.
+pub fn object_bounds(nif: &Nif, pose: &Pose, block_idx: usize) -> Option<(glam::Vec3, glam::Vec3)> {
+    let graph = SceneGraph::posed(nif, pose);
+    let mut bounds: Option<(glam::Vec3, glam::Vec3)> = None;
+    for (idx, block) in nif.blocks.iter().enumerate() {
+        let (shape, world) = match (block, graph.world.get(&idx)) {
+            (Block::NiTriShape(shape), Some(world)) => (shape, world),
+            _ => continue,
+        };
+        let is_selected = idx == block_idx
+            || usize::try_from(shape.data_ref.0) == Ok(block_idx)
+            || is_descendant(&graph, idx, block_idx);
+        if !is_selected {
+            continue;
+        }
+        let data = match shape.data_ref.get(&nif.blocks) {
+            Some(Block::NiTriShapeData(data)) => &data.base,
+            _ => continue,
+        };
+        for vertex in data.vertices.iter() {
+            let position = world.transform_point3(glam::vec3(vertex.x, vertex.y, vertex.z));
+            bounds = Some(match bounds {
+                Some((min, max)) => (min.min(position), max.max(position)),
+                None => (position, position),
+            });
+        }
+    }
+    bounds
+}
+
+fn is_descendant(graph: &SceneGraph, mut idx: usize, ancestor: usize) -> bool {
+    // a broken file can link a node back above itself, so stop after visiting every parent
+    for _ in 0..graph.parents.len() {
+        match graph.parents.get(&idx) {
+            Some(&parent) if parent == ancestor => return true,
+            Some(&parent) => idx = parent,
+            None => return false,
+        }
+    }
+    false
+}
@@ -0,0 +1,102 @@
+//! Colours of a shape's vertices from its vertex colours and the material it inherits.
+
+use nif::{
+    blocks::{Block, NiAlphaProperty, NiAvObject, NiGeometryData, NiMaterialProperty},
+    common::{Color3, Matrix33, Vector3},
+};
+
+/// Colours a vertex gets from its shape's vertex colours and material.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexColors {
+    /// Diffuse colour and alpha.
+    pub color: [f32; 4],
+    pub ambient: [f32; 3],
+    pub emissive: [f32; 3],
+}
+
+impl Default for VertexColors {
+    fn default() -> Self {
+        Self {
+            color: [1.0; 4],
+            ambient: [1.0; 3],
+            emissive: [0.0; 3],
+        }
+    }
+}
+
+/// Properties inherited down the scene graph.
+#[derive(Clone, Copy, Default)]
+pub struct InheritedProperties<'a> {
+    material: Option<&'a NiMaterialProperty>,
+    pub alpha: Option<&'a NiAlphaProperty>,
+}
+
+impl<'a> InheritedProperties<'a> {
+    /// Properties below an object, its own replace the inherited ones.
+    pub fn with_object(mut self, blocks: &'a [Block], av_object: &NiAvObject) -> Self {
+        for property_ref in av_object.property_refs.iter() {
+            match property_ref.get(blocks) {
+                Some(Block::NiMaterialProperty(material)) => self.material = Some(material),
+                Some(Block::NiAlphaProperty(alpha)) => self.alpha = Some(alpha),
+                _ => {}
+            }
+        }
+        self
+    }
+}
+
+impl VertexColors {
+    fn from_material(material: &NiMaterialProperty) -> Self {
+        let rgb = |color: &Color3| [color.r, color.g, color.b];
+        let [r, g, b] = rgb(&material.diffuse_color);
+        Self {
+            color: [r, g, b, material.alpha],
+            ambient: rgb(&material.ambient_color),
+            emissive: rgb(&material.emissive_color),
+        }
+    }
+}
+
+/// Colours of each vertex of a shape, from its vertex colours and the properties it inherits.
+pub fn vertex_colors(data: &NiGeometryData, properties: InheritedProperties) -> Vec<VertexColors> {
+    let material_colors = properties
+        .material
+        .map(VertexColors::from_material)
+        .unwrap_or_default();
+
+    (0..data.vertices.len())
+        .map(|idx| match data.vertex_colors.get(idx) {
+            // without a vertex colour property, vertex colours replace ambient and diffuse
+            Some(vertex_color) => VertexColors {
+                color: [
+                    vertex_color.r,
+                    vertex_color.g,
+                    vertex_color.b,
+                    vertex_color.a * material_colors.color[3],
+                ],
+                ambient: [vertex_color.r, vertex_color.g, vertex_color.b],
+                ..material_colors
+            },
+            None => material_colors,
+        })
+        .collect()
+}
+
+pub(super) fn local_transform(av_object: &NiAvObject) -> glam::Mat4 {
+    transform_matrix(&av_object.rotation, &av_object.translation, av_object.scale)
+}
+
+pub(super) fn transform_matrix(r: &Matrix33, t: &Vector3, scale: f32) -> glam::Mat4 {
+    glam::Mat4::from_translation(glam::vec3(t.x, t.y, t.z))
+        * glam::Mat4::from_mat3(rotation_matrix(r))
+        * glam::Mat4::from_scale(glam::Vec3::splat(scale))
+}
+
+pub(super) fn rotation_matrix(r: &Matrix33) -> glam::Mat3 {
+    glam::Mat3::from_cols_array_2d(&[
+        [r.m11, r.m12, r.m13],
+        [r.m21, r.m22, r.m23],
+        [r.m31, r.m32, r.m33],
+    ])
+    .transpose()
+}
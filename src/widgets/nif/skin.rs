@@ -0,0 +1,268 @@
+use nif::{
+    blocks::{Block, NiSkinData, NiSkinInstance, NiSkinPartition, NiTriShape, SkinTransform},
+    Nif,
+};
+
+use super::{scene_graph::SceneGraph, shape_colors::transform_matrix};
+
+/// Bones a vertex follows, as indices into the mesh's bone palette, or into its shape's
+/// bones while collecting.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VertexWeights {
+    pub bones: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+impl VertexWeights {
+    /// Follows a single bone entirely.
+    pub fn rigid(bone: u32) -> Self {
+        Self {
+            bones: [bone, 0, 0, 0],
+            weights: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    pub fn offset(self, palette_base: u32) -> Self {
+        Self {
+            bones: self.bones.map(|bone| bone + palette_base),
+            ..self
+        }
+    }
+}
+
+/// Where a skin sits between its bones and model space.
+#[derive(Debug, Clone, Copy)]
+pub struct SkinSpace {
+    shape: usize,
+    /// Parent of the skeleton root, `None` when the root is the top of the scene.
+    root_parent: Option<usize>,
+    /// NiSkinData's overall transform, from the root parent's space to the skin's.
+    root_parent_to_skin: glam::Mat4,
+}
+
+/// How a bone palette matrix follows the scene graph.
+#[derive(Debug, Clone, Copy)]
+pub struct BoneBinding {
+    /// Block whose world transform moves the vertices.
+    pub node: usize,
+    /// Applied to collected vertices before the node's world transform.
+    pub offset: glam::Mat4,
+    /// Brings skinned vertices from the skeleton back to their shape, `None` for objects
+    /// moving as a whole.
+    pub skin: Option<SkinSpace>,
+}
+
+impl BoneBinding {
+    /// Moves the vertices of `node` along with it, from where `world` placed them.
+    pub fn rigid(node: usize, world: glam::Mat4) -> Self {
+        Self {
+            node,
+            offset: world.inverse(),
+            skin: None,
+        }
+    }
+
+    pub fn matrix(&self, graph: &SceneGraph) -> Option<glam::Mat4> {
+        let bone = *graph.world.get(&self.node)? * self.offset;
+        let skin = match &self.skin {
+            Some(skin) => skin,
+            None => return Some(bone),
+        };
+        let shape_world = graph.world.get(&skin.shape)?;
+        let root_parent_world = match skin.root_parent {
+            Some(root_parent) => *graph.world.get(&root_parent)?,
+            None => glam::Mat4::IDENTITY,
+        };
+        Some(*shape_world * skin.root_parent_to_skin * root_parent_world.inverse() * bone)
+    }
+}
+
+/// Bones of a skinned shape and the weights of its vertices.
+#[derive(Debug, Clone)]
+pub struct ShapeSkin {
+    /// One per bone of the skin instance, moving a collected vertex from its bind pose to
+    /// the bone's current pose.
+    pub bones: Vec<BoneBinding>,
+    /// One per vertex, indexing `bones`.
+    pub weights: Vec<VertexWeights>,
+}
+
+impl ShapeSkin {
+    /// The skin of the shape at `shape_idx`, whose vertices `shape_world` moved to model
+    /// space. `None` for shapes without a skin instance.
+    pub fn new(
+        blocks: &[Block],
+        graph: &SceneGraph,
+        (shape_idx, shape_world): (usize, glam::Mat4),
+        shape: &NiTriShape,
+        vertex_count: usize,
+    ) -> Option<Self> {
+        let (skin_instance, skin_data) = skin_blocks(blocks, shape)?;
+
+        let skin = SkinSpace {
+            shape: shape_idx,
+            root_parent: usize::try_from(skin_instance.skeleton_root_ref.0)
+                .ok()
+                .and_then(|root| graph.parents.get(&root))
+                .copied(),
+            root_parent_to_skin: skin_matrix(&skin_data.skin_transform),
+        };
+        // collected vertices are already in model space, the palette takes them back to
+        // skin space and then through each bone
+        let shape_world_inverse = shape_world.inverse();
+        let bones = skin_instance
+            .bone_refs
+            .iter()
+            .zip(&skin_data.bone_list)
+            .map(|(bone_ref, bone)| BoneBinding {
+                node: usize::try_from(bone_ref.0)
+                    .ok()
+                    .filter(|idx| graph.world.contains_key(idx))
+                    .unwrap_or(shape_idx),
+                offset: skin_matrix(&bone.skin_transform) * shape_world_inverse,
+                skin: Some(skin),
+            })
+            .collect();
+
+        let mut vertex_bones = vec![Vec::new(); vertex_count];
+        match skin_instance.skin_partition_ref.get(blocks) {
+            Some(Block::NiSkinPartition(partition))
+                if !partition.skin_partition_blocks.is_empty() =>
+            {
+                partition_weights(partition, &mut vertex_bones)
+            }
+            _ => {
+                for (bone_idx, bone) in skin_data.bone_list.iter().enumerate() {
+                    for weight in bone.vertex_weights.iter() {
+                        if let Some(bones) = vertex_bones.get_mut(weight.index as usize) {
+                            bones.push((bone_idx as u32, weight.weight));
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(Self {
+            bones,
+            weights: vertex_bones.into_iter().map(strongest_bones).collect(),
+        })
+    }
+}
+
+fn skin_matrix(transform: &SkinTransform) -> glam::Mat4 {
+    transform_matrix(&transform.rotation, &transform.translation, transform.scale)
+}
+
+/// Weights as the game draws them, split into partitions with their own bone lists. Missing
+/// vertex maps and bone indices mean the partition's vertices and bones in order.
+fn partition_weights(partition: &NiSkinPartition, vertex_bones: &mut [Vec<(u32, f32)>]) {
+    for block in partition.skin_partition_blocks.iter() {
+        for (local_vertex, weights) in block.vertex_weights.iter().enumerate() {
+            let vertex = match block.vertex_map.get(local_vertex) {
+                Some(&vertex) => vertex as usize,
+                None if block.vertex_map.is_empty() => local_vertex,
+                None => continue,
+            };
+            let bone_indices = block.bone_indices.get(local_vertex);
+            for (slot, &weight) in weights.iter().enumerate() {
+                let local_bone = bone_indices
+                    .and_then(|indices| indices.get(slot))
+                    .map_or(slot, |&bone| bone as usize);
+                if let (Some(bones), Some(&bone)) =
+                    (vertex_bones.get_mut(vertex), block.bones.get(local_bone))
+                {
+                    bones.push((bone as u32, weight));
+                }
+            }
+        }
+    }
+}
+
+/// The four strongest bones, which the shader takes, with their weights normalized. Vertices
+/// without weights stay where they are.
+fn strongest_bones(mut bones: Vec<(u32, f32)>) -> VertexWeights {
+    bones.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    bones.truncate(4);
+    let total = bones.iter().map(|(_, weight)| weight).sum::<f32>();
+    let mut weights = VertexWeights::default();
+    if total <= 0.0 {
+        return weights;
+    }
+    for (slot, (bone, weight)) in bones.into_iter().enumerate() {
+        weights.bones[slot] = bone;
+        weights.weights[slot] = weight / total;
+    }
+    weights
+}
+
+fn skin_blocks<'a>(
+    blocks: &'a [Block],
+    shape: &NiTriShape,
+) -> Option<(&'a NiSkinInstance, &'a NiSkinData)> {
+    let skin_instance = match shape.skin_instance_ref.get(blocks)? {
+        Block::NiSkinInstance(skin_instance) => skin_instance,
+        _ => return None,
+    };
+    match skin_instance.data_ref.get(blocks)? {
+        Block::NiSkinData(skin_data) => Some((skin_instance, skin_data)),
+        _ => None,
+    }
+}
+
+/// Lines from each bone of the skinned shapes to its parent node, in model space.
+pub fn skeleton(nif: &Nif, graph: &SceneGraph) -> Vec<(glam::Vec3, glam::Vec3)> {
+    let mut bones = nif
+        .blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::NiTriShape(shape) => skin_blocks(&nif.blocks, shape),
+            _ => None,
+        })
+        .flat_map(|(skin_instance, _)| skin_instance.bone_refs.iter())
+        .filter_map(|bone_ref| usize::try_from(bone_ref.0).ok())
+        .collect::<Vec<_>>();
+    bones.sort_unstable();
+    bones.dedup();
+
+    let position = |idx: &usize| {
+        graph
+            .world
+            .get(idx)
+            .map(|world| world.transform_point3(glam::Vec3::ZERO))
+    };
+    bones
+        .iter()
+        .filter_map(|bone| {
+            let parent = graph.parents.get(bone)?;
+            Some((position(parent)?, position(bone)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strongest_four_bones_are_kept_normalized() {
+        let weights = strongest_bones(vec![(0, 0.1), (1, 0.4), (2, 0.2), (3, 0.05), (4, 0.25)]);
+        assert_eq!(weights.bones, [1, 4, 2, 0]);
+        let total = 0.4 + 0.25 + 0.2 + 0.1;
+        for (weight, expected) in weights.weights.iter().zip([0.4, 0.25, 0.2, 0.1]) {
+            assert!((weight - expected / total).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn unweighted_vertex_follows_no_bone() {
+        assert_eq!(strongest_bones(Vec::new()).weights, [0.0; 4]);
+        assert_eq!(strongest_bones(vec![(2, 0.0)]).weights, [0.0; 4]);
+    }
+
+    #[test]
+    fn palette_base_moves_every_bone_index() {
+        let weights = VertexWeights::rigid(0).offset(5);
+        assert_eq!(weights.bones, [5, 5, 5, 5]);
+        assert_eq!(weights.weights, [1.0, 0.0, 0.0, 0.0]);
+    }
+}
@@ -1,19 +1,48 @@
+use std::ops::Range;
+
 use eframe::wgpu::{self, util::DeviceExt};
 use nif::Nif;
 
+use super::{
+    camera::Frustum,
+    mesh_collector,
+    scene_graph::SceneGraph,
+    skin::{BoneBinding, VertexWeights},
+    untextured_mesh_pipeline::AlphaMode,
+    DebugView,
+};
+
 #[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct UntexturedMeshVertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
+    /// Diffuse colour and alpha, from vertex colours or the material.
+    pub color: [f32; 4],
+    pub ambient: [f32; 3],
+    pub emissive: [f32; 3],
+    /// Alpha at or below which alpha tested fragments are discarded.
+    pub alpha_threshold: f32,
+    /// Bone palette entries of skinned vertices, all weights are zero for the rest.
+    pub bone_indices: [u32; 4],
+    pub bone_weights: [f32; 4],
 }
 
 impl UntexturedMeshVertex {
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
-            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+        // 2..=9 are taken by the instance attributes
+        const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+            0 => Float32x3,
+            1 => Float32x3,
+            10 => Float32x4,
+            11 => Float32x3,
+            12 => Float32x3,
+            13 => Float32,
+            14 => Uint32x4,
+            15 => Float32x4
+        ];
         wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+            array_stride: std::mem::size_of::<UntexturedMeshVertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &ATTRIBUTES,
         }
@@ -26,18 +55,20 @@ impl UntexturedMeshVertex {
 pub struct UntexturedMeshInstanceRaw {
     model: [[f32; 4]; 4],
     normal: [[f32; 3]; 3],
+    tint: [f32; 3],
 }
 
 impl UntexturedMeshInstanceRaw {
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+        const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
             2 => Float32x4,
             3 => Float32x4,
             4 => Float32x4,
             5 => Float32x4,
             6 => Float32x3,
             7 => Float32x3,
-            8 => Float32x3
+            8 => Float32x3,
+            9 => Float32x3
         ];
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<UntexturedMeshInstanceRaw>() as wgpu::BufferAddress,
@@ -52,6 +83,8 @@ pub struct UntexturedMeshInstance {
     pub position: glam::Vec3,
     pub rotation: glam::Quat,
     pub scale: f32,
+    /// Multiplied with the shaded colour, white leaves it unchanged.
+    pub tint: glam::Vec3,
 }
 
 impl Default for UntexturedMeshInstance {
@@ -60,6 +93,7 @@ impl Default for UntexturedMeshInstance {
             position: glam::Vec3::ZERO,
             rotation: glam::Quat::IDENTITY,
             scale: 1.0,
+            tint: glam::Vec3::ONE,
         }
     }
 }
@@ -74,10 +108,100 @@ impl UntexturedMeshInstance {
             )
             .to_cols_array_2d(),
             normal: glam::Mat3::from_quat(self.rotation).to_cols_array_2d(),
+            tint: self.tint.to_array(),
         }
     }
 }
 
+/// One LOD level of a chunk, used from `min_distance` until the next level's.
+#[derive(Debug, Clone)]
+pub struct LodLevel {
+    pub min_distance: f32,
+    pub index_range: Range<u32>,
+    /// Vertices the level's indices fall within, levels share the vertices of shapes
+    /// they both draw.
+    pub vertex_range: Range<u32>,
+    /// Parts of `index_range` drawn by each `AlphaMode` pipeline.
+    pub alpha_ranges: [Range<u32>; 3],
+}
+
+fn offset_range(range: &Range<u32>, offset: u32) -> Range<u32> {
+    range.start + offset..range.end + offset
+}
+
+/// A draw call left after culling.
+#[derive(Debug, Clone)]
+pub struct MeshDraw {
+    pub index_range: Range<u32>,
+    pub alpha_ranges: [Range<u32>; 3],
+    /// Distance of the farthest drawn instance, for sorting blended draws.
+    pub distance: f32,
+    /// Vertices the indices point at, for drawing per-vertex debug lines.
+    pub vertex_range: Range<u32>,
+    pub instance_range: Range<u32>,
+}
+
+/// Line buffers for the debug views, built the first time a view is enabled.
+#[derive(Debug, Default)]
+pub struct DebugBuffers {
+    /// Edges of every triangle, indexing the mesh vertex buffer, two per triangle index.
+    pub wireframe_indices: Option<wgpu::Buffer>,
+    /// A line along the normal of every vertex, two vertices per mesh vertex.
+    pub normal_vertices: Option<wgpu::Buffer>,
+    /// Corners and edges of the mesh bounds.
+    pub bounds: Option<(wgpu::Buffer, wgpu::Buffer)>,
+    /// Edges of degenerate triangles, with the number of edge indices before each triangle.
+    pub degenerate: Option<(Option<wgpu::Buffer>, Vec<u32>)>,
+}
+
+fn is_degenerate(vertices: &[UntexturedMeshVertex], triangle: &[u32]) -> bool {
+    let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+    if a == b || b == c || a == c {
+        return true;
+    }
+    let [a, b, c] = [a, b, c].map(|idx| glam::Vec3::from(vertices[idx as usize].position));
+    (b - a).cross(c - a).length_squared() <= f32::EPSILON * f32::EPSILON
+}
+
+fn triangle_edges(triangle: &[u32]) -> [u32; 6] {
+    [
+        triangle[0],
+        triangle[1],
+        triangle[1],
+        triangle[2],
+        triangle[2],
+        triangle[0],
+    ]
+}
+
+/// Levels and bounds of one NIF merged into a mesh, culled on their own.
+#[derive(Debug, Clone)]
+pub struct MeshChunk {
+    pub levels: Vec<LodLevel>,
+    pub bounds_min: glam::Vec3,
+    pub bounds_max: glam::Vec3,
+}
+
+impl MeshChunk {
+    fn world_center(&self, instance: &UntexturedMeshInstance) -> glam::Vec3 {
+        let center = (self.bounds_min + self.bounds_max) / 2.0;
+        instance.position + instance.rotation * (center * instance.scale)
+    }
+
+    fn is_visible(&self, frustum: &Frustum, instance: &UntexturedMeshInstance) -> bool {
+        let radius = (self.bounds_max - self.bounds_min).length() / 2.0;
+        frustum.intersects_sphere(self.world_center(instance), radius * instance.scale.abs())
+    }
+
+    fn level_at(&self, distance: f32) -> &LodLevel {
+        self.levels
+            .iter()
+            .rev()
+            .find(|level| distance >= level.min_distance)
+            .unwrap_or(&self.levels[0])
+    }
+}
+
 #[derive(Debug)]
 pub struct UntexturedMesh {
     pub vertices: Vec<UntexturedMeshVertex>,
@@ -85,10 +209,33 @@ pub struct UntexturedMesh {
     pub indices: Vec<u32>,
     pub instances: Vec<UntexturedMeshInstance>,
     pub bounds_from_origin: [f32; 3],
+    pub bounds_min: glam::Vec3,
+    pub bounds_max: glam::Vec3,
+    /// Group wide tint, multiplied with each instance's own tint.
+    pub tint: glam::Vec3,
+    instances_dirty: bool,
+    pub chunks: Vec<MeshChunk>,
+    /// Instances that passed culling, uploaded to the start of the instance buffer
+    /// ordered by the LOD levels they use.
+    pub visible_instances: Vec<u32>,
+    /// Draw calls left after the last `cull`.
+    pub draws: Vec<MeshDraw>,
+    pub degenerate_triangle_count: usize,
+    pub debug_buffers: DebugBuffers,
+    /// Bone matrices of skinned and animated vertices, empty when nothing moves.
+    pub bone_palette: Vec<glam::Mat4>,
+    /// Nodes of the source NIF each palette matrix follows, for posing meshes of one NIF.
+    bone_bindings: Vec<BoneBinding>,
+    bones_dirty: bool,
+    bones: Option<(wgpu::Buffer, wgpu::BindGroup)>,
 }
 
 impl UntexturedMesh {
-    pub fn upload(&mut self, device: &wgpu::Device) {
+    pub fn is_skinned(&self) -> bool {
+        !self.bone_palette.is_empty()
+    }
+
+    pub fn upload(&mut self, device: &wgpu::Device, bones_layout: &wgpu::BindGroupLayout) {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("nif_vertex_buffer"),
             contents: bytemuck::cast_slice(&self.vertices),
@@ -101,6 +248,7 @@ impl UntexturedMesh {
             usage: wgpu::BufferUsages::INDEX,
         });
 
+        // sized for every instance, culling only ever uploads a subset
         let instances_data = self
             .instances
             .iter()
@@ -112,30 +260,412 @@ impl UntexturedMesh {
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
 
+        // every pipeline reads the palette, unskinned meshes get a single unused matrix
+        let palette = match self.bone_palette.as_slice() {
+            [] => vec![glam::Mat4::IDENTITY.to_cols_array_2d()],
+            palette => palette.iter().map(|bone| bone.to_cols_array_2d()).collect(),
+        };
+        let bones_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("nif_bones_buffer"),
+            contents: bytemuck::cast_slice(&palette),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let bones_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("nif_bones_bind_group"),
+            layout: bones_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: bones_buffer.as_entire_binding(),
+            }],
+        });
+
         self.buffers_v_idx_i = Some((vertex_buffer, index_buffer, instance_buffer));
+        self.bones = Some((bones_buffer, bones_bind_group));
+        self.bones_dirty = false;
+        self.visible_instances.clear();
+        self.instances_dirty = true;
+    }
+
+    /// Moves the palette matrices to the nodes' transforms in `graph`, matrices of nodes
+    /// missing from it stay as they are.
+    pub(super) fn set_pose(&mut self, graph: &SceneGraph) {
+        for (matrix, binding) in self.bone_palette.iter_mut().zip(self.bone_bindings.iter()) {
+            if let Some(posed) = binding.matrix(graph) {
+                *matrix = posed;
+            }
+        }
+        self.bones_dirty = true;
+    }
+
+    /// Rewrites the bone buffer after the pose changed.
+    pub fn write_bones(&mut self, queue: &wgpu::Queue) {
+        if !self.bones_dirty || self.bone_palette.is_empty() {
+            return;
+        }
+        if let Some((bones_buffer, _)) = &self.bones {
+            let palette = self
+                .bone_palette
+                .iter()
+                .map(|bone| bone.to_cols_array_2d())
+                .collect::<Vec<_>>();
+            queue.write_buffer(bones_buffer, 0, bytemuck::cast_slice(&palette));
+            self.bones_dirty = false;
+        }
+    }
+
+    fn instances_raw(&self) -> Vec<UntexturedMeshInstanceRaw> {
+        self.visible_instances
+            .iter()
+            .filter_map(|&idx| self.instance_raw(idx as usize))
+            .collect()
+    }
+
+    pub fn instance_raw(&self, idx: usize) -> Option<UntexturedMeshInstanceRaw> {
+        let mut raw = self.instances.get(idx)?.to_raw();
+        raw.tint = (glam::Vec3::from(raw.tint) * self.tint).to_array();
+        Some(raw)
+    }
+
+    pub fn set_tint(&mut self, tint: glam::Vec3) {
+        if self.tint != tint {
+            self.tint = tint;
+            self.instances_dirty = true;
+        }
+    }
+
+    pub fn set_instance_rotation(&mut self, idx: usize, rotation: glam::Quat) {
+        if let Some(instance) = self.instances.get_mut(idx) {
+            if instance.rotation != rotation {
+                instance.rotation = rotation;
+                self.instances_dirty = true;
+            }
+        }
+    }
+
+    /// Culls instances, and the chunks of single instance meshes, against the frustum and
+    /// picks the LOD level of each by its distance to the camera.
+    /// The instance buffer is rewritten when the visible set or the instances changed.
+    pub fn cull(&mut self, queue: &wgpu::Queue, frustum: &Frustum, camera_position: glam::Vec3) {
+        let whole_mesh = MeshChunk {
+            levels: Vec::new(),
+            bounds_min: self.bounds_min,
+            bounds_max: self.bounds_max,
+        };
+
+        let mut draws: Vec<MeshDraw> = Vec::new();
+        let visible_instances = match self.instances.as_slice() {
+            [instance] => {
+                if whole_mesh.is_visible(frustum, instance) {
+                    for chunk in self
+                        .chunks
+                        .iter()
+                        .filter(|chunk| chunk.is_visible(frustum, instance))
+                    {
+                        let distance = chunk.world_center(instance).distance(camera_position);
+                        let level = chunk.level_at(distance);
+                        draws.push(MeshDraw {
+                            index_range: level.index_range.clone(),
+                            alpha_ranges: level.alpha_ranges.clone(),
+                            distance,
+                            vertex_range: level.vertex_range.clone(),
+                            instance_range: 0..1,
+                        });
+                    }
+                }
+                if draws.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![0]
+                }
+            }
+            instances => {
+                // instances between the same pair of switch distances share their levels
+                let mut switch_distances = self
+                    .chunks
+                    .iter()
+                    .flat_map(|chunk| chunk.levels.iter().skip(1))
+                    .map(|level| level.min_distance)
+                    .collect::<Vec<_>>();
+                switch_distances.sort_by(f32::total_cmp);
+                switch_distances.dedup();
+
+                let mut visible = instances
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, instance)| whole_mesh.is_visible(frustum, instance))
+                    .map(|(idx, instance)| {
+                        let distance = whole_mesh.world_center(instance).distance(camera_position);
+                        let band = switch_distances
+                            .iter()
+                            .take_while(|switch_distance| distance >= **switch_distance)
+                            .count();
+                        (band, distance, idx as u32)
+                    })
+                    .collect::<Vec<_>>();
+                // back to front within a band, for the blended parts
+                visible.sort_by(|(band_a, distance_a, _), (band_b, distance_b, _)| {
+                    band_a.cmp(band_b).then(distance_b.total_cmp(distance_a))
+                });
+
+                let mut start = 0;
+                while start < visible.len() {
+                    let (band, distance, _) = visible[start];
+                    let count = visible[start..]
+                        .iter()
+                        .take_while(|(other_band, _, _)| *other_band == band)
+                        .count();
+                    let instance_range = start as u32..(start + count) as u32;
+                    for chunk in self.chunks.iter() {
+                        let level = chunk.level_at(distance);
+                        draws.push(MeshDraw {
+                            index_range: level.index_range.clone(),
+                            alpha_ranges: level.alpha_ranges.clone(),
+                            distance,
+                            vertex_range: level.vertex_range.clone(),
+                            instance_range: instance_range.clone(),
+                        });
+                    }
+                    start += count;
+                }
+                visible.into_iter().map(|(_, _, idx)| idx).collect()
+            }
+        };
+        draws.retain(|draw| !draw.index_range.is_empty());
+        self.draws = draws;
+
+        if visible_instances != self.visible_instances || self.instances_dirty {
+            self.visible_instances = visible_instances;
+            if let Some((_, _, instance_buffer)) = self.buffers() {
+                queue.write_buffer(
+                    instance_buffer,
+                    0,
+                    bytemuck::cast_slice(&self.instances_raw()),
+                );
+            }
+            self.instances_dirty = false;
+        }
+    }
+
+    /// Triangles of the most detailed level.
+    pub fn triangle_count(&self) -> usize {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.levels[0].index_range.len() / 3)
+            .sum()
+    }
+
+    /// Triangles drawn after the last `cull`.
+    pub fn visible_triangle_count(&self) -> usize {
+        self.draws
+            .iter()
+            .map(|draw| draw.index_range.len() / 3 * draw.instance_range.len())
+            .sum()
+    }
+
+    /// Builds the line buffers of the enabled debug views that don't have them yet.
+    pub fn prepare_debug(&mut self, device: &wgpu::Device, debug: &DebugView) {
+        let index_buffer = |label, indices: &[u32]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(indices),
+                usage: wgpu::BufferUsages::INDEX,
+            })
+        };
+        let vertex_buffer = |label, vertices: &[UntexturedMeshVertex]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        };
+        let buffers = &mut self.debug_buffers;
+
+        if debug.wireframe && buffers.wireframe_indices.is_none() && !self.indices.is_empty() {
+            let indices = self
+                .indices
+                .chunks_exact(3)
+                .flat_map(triangle_edges)
+                .collect::<Vec<_>>();
+            buffers.wireframe_indices = Some(index_buffer("nif_wireframe_index_buffer", &indices));
+        }
+
+        if debug.normals && buffers.normal_vertices.is_none() && !self.vertices.is_empty() {
+            let length = ((self.bounds_max - self.bounds_min).length() * 0.02).max(0.05);
+            let vertices = self
+                .vertices
+                .iter()
+                .flat_map(|vertex| {
+                    let position = glam::Vec3::from(vertex.position);
+                    let tip = position + glam::Vec3::from(vertex.normal) * length;
+                    [
+                        *vertex,
+                        UntexturedMeshVertex {
+                            position: tip.to_array(),
+                            ..*vertex
+                        },
+                    ]
+                })
+                .collect::<Vec<_>>();
+            buffers.normal_vertices = Some(vertex_buffer("nif_normal_vertex_buffer", &vertices));
+        }
+
+        if debug.bounds && buffers.bounds.is_none() {
+            let (min, max) = (self.bounds_min, self.bounds_max);
+            let vertices = (0..8)
+                .map(|idx| UntexturedMeshVertex {
+                    position: [
+                        if idx & 1 == 0 { min.x } else { max.x },
+                        if idx & 2 == 0 { min.y } else { max.y },
+                        if idx & 4 == 0 { min.z } else { max.z },
+                    ],
+                    normal: [0.0, 0.0, 1.0],
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>();
+            let indices = (0..8u32)
+                .flat_map(|idx| {
+                    [1, 2, 4]
+                        .into_iter()
+                        .filter(move |axis| idx & axis == 0)
+                        .flat_map(move |axis| [idx, idx | axis])
+                })
+                .collect::<Vec<_>>();
+            buffers.bounds = Some((
+                vertex_buffer("nif_bounds_vertex_buffer", &vertices),
+                index_buffer("nif_bounds_index_buffer", &indices),
+            ));
+        }
+
+        if debug.degenerate_triangles && buffers.degenerate.is_none() {
+            let mut indices = Vec::new();
+            let mut offsets = vec![0];
+            for triangle in self.indices.chunks_exact(3) {
+                if is_degenerate(&self.vertices, triangle) {
+                    indices.extend(triangle_edges(triangle));
+                }
+                offsets.push(indices.len() as u32);
+            }
+            let buffer = (!indices.is_empty())
+                .then(|| index_buffer("nif_degenerate_index_buffer", &indices));
+            buffers.degenerate = Some((buffer, offsets));
+        }
     }
 
     pub fn buffers(&self) -> Option<&(wgpu::Buffer, wgpu::Buffer, wgpu::Buffer)> {
         self.buffers_v_idx_i.as_ref()
     }
 
+    /// Binds the bone palette, needed by every pipeline drawing this mesh.
+    pub fn set_bones<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
+        if let Some((_, bones_bind_group)) = &self.bones {
+            rpass.set_bind_group(2, bones_bind_group, &[]);
+        }
+    }
+
+    /// Builds the mesh for a fixed LOD distance, or with every LOD level when `None` so the
+    /// level can be picked by camera distance while drawing.
     pub fn create_from_nif_lod(
         nif: &Nif,
-        lod_distance: f32,
+        lod_distance: Option<f32>,
         instances: Option<Vec<UntexturedMeshInstance>>,
     ) -> Self {
-        let mut mesh = nif::collectors::single_mesh::Mesh::default();
-        mesh.add_nif(nif, lod_distance).unwrap();
-
-        let vertices: Vec<UntexturedMeshVertex> = mesh
-            .vertices
-            .into_iter()
-            .zip(mesh.normals.into_iter())
-            .map(|(position, normal)| UntexturedMeshVertex {
-                position: position.into(),
-                normal: normal.into(),
-            })
-            .collect();
+        let graph = SceneGraph::new(nif);
+        let mut bone_bindings = Vec::new();
+        let shapes = mesh_collector::collect_shapes(nif);
+        let lod_ranges = shapes
+            .iter()
+            .map(|shape| shape.lod_range.clone())
+            .collect::<Vec<_>>();
+        let collected_levels = match lod_distance {
+            Some(lod_distance) => vec![(0.0, mesh_collector::shapes_at(&lod_ranges, lod_distance))],
+            None => mesh_collector::lod_levels(&lod_ranges),
+        };
+
+        let mut vertices: Vec<UntexturedMeshVertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        // where each shape's vertices went, added the first time a level draws the shape
+        let mut shape_vertices: Vec<Option<Range<u32>>> = vec![None; shapes.len()];
+        let mut levels = Vec::new();
+        for (min_distance, drawn) in collected_levels {
+            for &shape_idx in drawn.iter() {
+                if shape_vertices[shape_idx].is_some() {
+                    continue;
+                }
+                let shape = &shapes[shape_idx];
+                let vertex_start = vertices.len() as u32;
+                // skinned shapes follow their bones, other animated shapes their own object
+                // as a whole
+                let palette_base = bone_bindings.len() as u32;
+                let (skin_weights, rigid_weights) = match &shape.skin {
+                    Some(skin) => {
+                        bone_bindings.extend(skin.bones.iter().copied());
+                        (skin.weights.as_slice(), None)
+                    }
+                    None if shape.animated => {
+                        bone_bindings.push(BoneBinding::rigid(shape.block_index, shape.world));
+                        (&[][..], Some(VertexWeights::rigid(0)))
+                    }
+                    None => (&[][..], None),
+                };
+                let shape_vertices_iter = shape
+                    .vertices
+                    .iter()
+                    .zip(shape.normals.iter())
+                    .zip(shape.colors.iter());
+                vertices.extend(shape_vertices_iter.enumerate().map(
+                    |(idx, ((&position, normal), colors))| {
+                        let weights = skin_weights
+                            .get(idx)
+                            .copied()
+                            .or(rigid_weights)
+                            .map(|weights| weights.offset(palette_base))
+                            .unwrap_or_default();
+                        UntexturedMeshVertex {
+                            position: position.into(),
+                            normal: normal.to_array(),
+                            color: colors.color,
+                            ambient: colors.ambient,
+                            emissive: colors.emissive,
+                            alpha_threshold: shape.alpha_threshold,
+                            bone_indices: weights.bones,
+                            bone_weights: weights.weights,
+                        }
+                    },
+                ));
+                shape_vertices[shape_idx] = Some(vertex_start..vertices.len() as u32);
+            }
+
+            let drawn_vertices = drawn
+                .iter()
+                .filter_map(|&shape_idx| Some((shape_idx, shape_vertices[shape_idx].clone()?)))
+                .collect::<Vec<_>>();
+            let vertex_range = drawn_vertices
+                .iter()
+                .map(|(_, range)| range.clone())
+                .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end))
+                .unwrap_or(0..0);
+
+            // triangles are grouped by their shape's alpha mode, one range per pipeline
+            let index_start = indices.len() as u32;
+            let mut alpha_ranges = [0..0, 0..0, 0..0];
+            for (mode, alpha_range) in AlphaMode::ALL.into_iter().zip(alpha_ranges.iter_mut()) {
+                let range_start = indices.len() as u32;
+                for (shape_idx, shape_vertices) in drawn_vertices.iter() {
+                    let shape = &shapes[*shape_idx];
+                    if shape.alpha_mode == mode {
+                        let index_base = shape_vertices.start;
+                        indices.extend(shape.indices.iter().map(|i| i + index_base));
+                    }
+                }
+                *alpha_range = range_start..indices.len() as u32;
+            }
+            levels.push(LodLevel {
+                min_distance,
+                index_range: index_start..indices.len() as u32,
+                vertex_range,
+                alpha_ranges,
+            });
+        }
 
         let bounds = vertices
             .iter()
@@ -149,18 +679,53 @@ impl UntexturedMesh {
             })
             .unwrap_or([0.0; 3]);
 
-        let indices = mesh.indices.clone();
+        let (bounds_min, bounds_max) = vertices
+            .iter()
+            .map(|v| glam::Vec3::from(v.position))
+            .fold(None, |bounds: Option<(glam::Vec3, glam::Vec3)>, v| {
+                Some(match bounds {
+                    Some((min, max)) => (min.min(v), max.max(v)),
+                    None => (v, v),
+                })
+            })
+            .unwrap_or((glam::Vec3::ZERO, glam::Vec3::ZERO));
+
+        let degenerate_triangle_count = indices
+            .chunks_exact(3)
+            .filter(|triangle| is_degenerate(&vertices, triangle))
+            .count();
 
         let instances = instances
             .unwrap_or_else(|| vec![UntexturedMeshInstance::default()])
             .to_vec();
+        let chunks = vec![MeshChunk {
+            levels,
+            bounds_min,
+            bounds_max,
+        }];
 
         Self {
+            visible_instances: Vec::new(),
+            draws: Vec::new(),
+            degenerate_triangle_count,
+            debug_buffers: Default::default(),
             vertices,
             indices,
             instances,
             bounds_from_origin: bounds,
+            bounds_min,
+            bounds_max,
+            tint: glam::Vec3::ONE,
+            instances_dirty: false,
+            chunks,
             buffers_v_idx_i: None,
+            bone_palette: bone_bindings
+                .iter()
+                .map(|binding| binding.matrix(&graph).unwrap_or(glam::Mat4::IDENTITY))
+                .collect(),
+            bone_bindings,
+            bones_dirty: false,
+            bones: None,
         }
     }
 
@@ -170,6 +735,35 @@ impl UntexturedMesh {
             self.bounds_from_origin[1].max(other.bounds_from_origin[1]),
             self.bounds_from_origin[2].max(other.bounds_from_origin[2]),
         ];
+        if self.vertices.is_empty() {
+            self.bounds_min = other.bounds_min;
+            self.bounds_max = other.bounds_max;
+        } else if !other.vertices.is_empty() {
+            self.bounds_min = self.bounds_min.min(other.bounds_min);
+            self.bounds_max = self.bounds_max.max(other.bounds_max);
+        }
+        let index_offset = self.indices.len() as u32;
+        let vertex_offset = self.vertices.len() as u32;
+        self.chunks.extend(other.chunks.drain(..).map(|mut chunk| {
+            for level in chunk.levels.iter_mut() {
+                level.index_range = offset_range(&level.index_range, index_offset);
+                level.vertex_range = offset_range(&level.vertex_range, vertex_offset);
+                for alpha_range in level.alpha_ranges.iter_mut() {
+                    *alpha_range = offset_range(alpha_range, index_offset);
+                }
+            }
+            chunk
+        }));
+        self.degenerate_triangle_count += other.degenerate_triangle_count;
+        self.debug_buffers = Default::default();
+        let bone_offset = self.bone_palette.len() as u32;
+        self.bone_palette.append(&mut other.bone_palette);
+        self.bone_bindings.append(&mut other.bone_bindings);
+        for vertex in other.vertices.iter_mut() {
+            if vertex.bone_weights != [0.0; 4] {
+                vertex.bone_indices = vertex.bone_indices.map(|bone| bone + bone_offset);
+            }
+        }
         let index_base = self.vertices.len() as u32;
         self.vertices.append(&mut other.vertices);
         self.indices
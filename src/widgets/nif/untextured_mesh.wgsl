@@ -5,16 +5,33 @@ struct CameraUniform {
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
-struct LightUniform {
+struct SceneLight {
+    // direction towards a directional light (kind 0), or the position of a point light (kind 1)
     position: vec3<f32>,
+    kind: u32,
     color: vec3<f32>,
 };
+
+struct LightUniform {
+    lights: array<SceneLight, 4>,
+    ambient: vec3<f32>,
+    count: u32,
+};
 @group(1) @binding(0)
 var<uniform> light: LightUniform;
 
+@group(2) @binding(0)
+var<storage, read> bones: array<mat4x4<f32>>;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
+    @location(10) color: vec4<f32>,
+    @location(11) ambient: vec3<f32>,
+    @location(12) emissive: vec3<f32>,
+    @location(13) alpha_threshold: f32,
+    @location(14) bone_indices: vec4<u32>,
+    @location(15) bone_weights: vec4<f32>,
 }
 
 struct InstanceInput {
@@ -25,19 +42,21 @@ struct InstanceInput {
     @location(6) normal_matrix_0: vec3<f32>,
     @location(7) normal_matrix_1: vec3<f32>,
     @location(8) normal_matrix_2: vec3<f32>,
+    @location(9) tint: vec3<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) world_normal: vec3<f32>,
     @location(1) world_position: vec3<f32>,
+    @location(2) tint: vec3<f32>,
+    @location(3) color: vec4<f32>,
+    @location(4) ambient: vec3<f32>,
+    @location(5) emissive: vec3<f32>,
+    @location(6) alpha_threshold: f32,
 }
 
-@vertex
-fn vs_main(
-    model: VertexInput,
-    instance: InstanceInput,
-) -> VertexOutput {
+fn vertex(model: VertexInput, instance: InstanceInput, skin: mat4x4<f32>) -> VertexOutput {
     let model_matrix = mat4x4<f32>(
         instance.model_matrix_0,
         instance.model_matrix_1,
@@ -50,30 +69,95 @@ fn vs_main(
         instance.normal_matrix_2,
     );
     var out: VertexOutput;
-    out.world_normal = normal_matrix * model.normal;
-    var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
+    let skin_normal_matrix = mat3x3<f32>(skin[0].xyz, skin[1].xyz, skin[2].xyz);
+    out.world_normal = normal_matrix * normalize(skin_normal_matrix * model.normal);
+    var world_position: vec4<f32> = model_matrix * skin * vec4<f32>(model.position, 1.0);
     out.world_position = world_position.xyz;
+    out.tint = instance.tint;
+    out.color = model.color;
+    out.ambient = model.ambient;
+    out.emissive = model.emissive;
+    out.alpha_threshold = model.alpha_threshold;
     out.clip_position = camera.view_proj * world_position;
     return out;
 }
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let identity = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    return vertex(model, instance, identity);
+}
 
-    let ambient_strength = 0.05;
-    let ambient_color = light.color * ambient_strength;
+// vertices of unskinned shapes in a skinned mesh have no weights and stay where they are
+fn skin_matrix(model: VertexInput) -> mat4x4<f32> {
+    if (dot(model.bone_weights, vec4<f32>(1.0)) < 0.001) {
+        return mat4x4<f32>(
+            vec4<f32>(1.0, 0.0, 0.0, 0.0),
+            vec4<f32>(0.0, 1.0, 0.0, 0.0),
+            vec4<f32>(0.0, 0.0, 1.0, 0.0),
+            vec4<f32>(0.0, 0.0, 0.0, 1.0),
+        );
+    }
+    return bones[model.bone_indices.x] * model.bone_weights.x
+        + bones[model.bone_indices.y] * model.bone_weights.y
+        + bones[model.bone_indices.z] * model.bone_weights.z
+        + bones[model.bone_indices.w] * model.bone_weights.w;
+}
 
-    let light_dir = normalize(light.position - in.world_position);
+@vertex
+fn vs_skinned(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    return vertex(model, instance, skin_matrix(model));
+}
+
+fn shade(in: VertexOutput) -> vec3<f32> {
+    var result = light.ambient * in.ambient + in.emissive;
     let view_dir = normalize(camera.view_pos.xyz - in.world_position);
-    let half_dir = normalize(view_dir + light_dir);
 
-    let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+    for (var i = 0u; i < light.count; i = i + 1u) {
+        let scene_light = light.lights[i];
+        var light_dir = normalize(scene_light.position);
+        if (scene_light.kind == 1u) {
+            light_dir = normalize(scene_light.position - in.world_position);
+        }
+        let half_dir = normalize(view_dir + light_dir);
+
+        let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
+        let diffuse_color = scene_light.color * diffuse_strength * in.color.rgb;
+
+        let specular_strength = pow(max(dot(in.world_normal, half_dir), 0.0), 32.0);
+        let specular_color = scene_light.color * specular_strength;
 
-    let specular_strength = pow(max(dot(in.world_normal, half_dir), 0.0), 32.0);
-    let specular_color = light.color * specular_strength;
+        result = result + diffuse_color + specular_color;
+    }
+
+    return result * in.tint;
+}
 
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
-    return vec4<f32>(result, object_color.a);
-}
\ No newline at end of file
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(shade(in), 1.0);
+}
+
+@fragment
+fn fs_alpha_test(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (in.color.a <= in.alpha_threshold) {
+        discard;
+    }
+    return vec4<f32>(shade(in), 1.0);
+}
+
+@fragment
+fn fs_alpha_blend(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(shade(in), in.color.a);
+}
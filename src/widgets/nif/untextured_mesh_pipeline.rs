@@ -6,12 +6,40 @@ use super::{
     untextured_mesh::{UntexturedMeshInstanceRaw, UntexturedMeshVertex},
 };
 
+/// How a shape's alpha is used, picks the pipeline its triangles are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    Tested,
+    Blended,
+}
+
+impl AlphaMode {
+    pub const ALL: [AlphaMode; 3] = [AlphaMode::Opaque, AlphaMode::Tested, AlphaMode::Blended];
+}
+
+/// Kinds of debug lines, each drawn with its own colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugLines {
+    Wireframe,
+    Normals,
+    Bounds,
+    Degenerate,
+}
+
 pub struct UntexturedMeshPipeline {
-    pipeline: wgpu::RenderPipeline,
+    /// Indexed by skinned, double-sided and then `AlphaMode`.
+    mesh_pipelines: [[[wgpu::RenderPipeline; 3]; 2]; 2],
+    wireframe_pipeline: wgpu::RenderPipeline,
+    normals_pipeline: wgpu::RenderPipeline,
+    bounds_pipeline: wgpu::RenderPipeline,
+    degenerate_pipeline: wgpu::RenderPipeline,
     camera_uniform_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
     light_uniform_buffer: wgpu::Buffer,
     light_bind_group: wgpu::BindGroup,
+    bones_bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl UntexturedMeshPipeline {
@@ -32,52 +60,142 @@ impl UntexturedMeshPipeline {
         let (camera_uniform_buffer, camera_bind_group_layout, camera_bind_group) =
             camera.create(device);
 
+        let bones_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("nif_bones_bind_group_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("nif_pipeline_layout"),
-            bind_group_layouts: &[&camera_bind_group_layout, &light_bind_group_layout],
+            bind_group_layouts: &[
+                &camera_bind_group_layout,
+                &light_bind_group_layout,
+                &bones_bind_group_layout,
+            ],
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("nif_render_pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    UntexturedMeshVertex::desc(),
-                    UntexturedMeshInstanceRaw::desc(),
-                ],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(target)],
-            }),
-            primitive: wgpu::PrimitiveState {
-                cull_mode: Some(wgpu::Face::Back),
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
+        let mesh_pipeline = |alpha_mode, cull_mode, skinned: bool| {
+            // blended triangles are sorted back to front instead of hiding what is behind them
+            let (entry_point, blend, depth_write_enabled) = match alpha_mode {
+                AlphaMode::Opaque => ("fs_main", None, true),
+                AlphaMode::Tested => ("fs_alpha_test", None, true),
+                AlphaMode::Blended => (
+                    "fs_alpha_blend",
+                    Some(wgpu::BlendState::ALPHA_BLENDING),
+                    false,
+                ),
+            };
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("nif_render_pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: if skinned { "vs_skinned" } else { "vs_main" },
+                    buffers: &[
+                        UntexturedMeshVertex::desc(),
+                        UntexturedMeshInstanceRaw::desc(),
+                    ],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        blend,
+                        ..target.clone()
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let mesh_pipelines = [false, true].map(|skinned| {
+            [Some(wgpu::Face::Back), None]
+                .map(|cull_mode| AlphaMode::ALL.map(|mode| mesh_pipeline(mode, cull_mode, skinned)))
+        });
+
+        let lines_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("nif_debug_lines_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("./debug_lines.wgsl").into()),
         });
+        let lines_pipeline = |entry_point, depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("nif_debug_lines_pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &lines_shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        UntexturedMeshVertex::desc(),
+                        UntexturedMeshInstanceRaw::desc(),
+                    ],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &lines_shader,
+                    entry_point,
+                    targets: &[Some(target.clone())],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let less_equal = wgpu::CompareFunction::LessEqual;
+        let wireframe_pipeline = lines_pipeline("fs_wireframe", less_equal);
+        let normals_pipeline = lines_pipeline("fs_normals", less_equal);
+        let bounds_pipeline = lines_pipeline("fs_bounds", less_equal);
+        // degenerate triangles are hard to spot, draw them through everything
+        let degenerate_pipeline = lines_pipeline("fs_degenerate", wgpu::CompareFunction::Always);
 
         Self {
-            pipeline,
+            mesh_pipelines,
+            wireframe_pipeline,
+            normals_pipeline,
+            bounds_pipeline,
+            degenerate_pipeline,
             camera_uniform_buffer,
             camera_bind_group,
             light_uniform_buffer,
             light_bind_group,
+            bones_bind_group_layout,
         }
     }
 
+    pub fn bones_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bones_bind_group_layout
+    }
+
     pub fn update_light(&self, queue: &wgpu::Queue, light: &Light) {
         queue.write_buffer(
             &self.light_uniform_buffer,
@@ -94,8 +212,31 @@ impl UntexturedMeshPipeline {
         );
     }
 
-    pub fn set<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
-        rpass.set_pipeline(&self.pipeline);
+    pub fn set<'rpass>(
+        &'rpass self,
+        rpass: &mut wgpu::RenderPass<'rpass>,
+        alpha_mode: AlphaMode,
+        cull_back_faces: bool,
+        skinned: bool,
+    ) {
+        rpass.set_pipeline(
+            &self.mesh_pipelines[skinned as usize][!cull_back_faces as usize][alpha_mode as usize],
+        );
+        rpass.set_bind_group(0, &self.camera_bind_group, &[]);
+        rpass.set_bind_group(1, &self.light_bind_group, &[]);
+    }
+
+    pub fn set_lines<'rpass>(
+        &'rpass self,
+        rpass: &mut wgpu::RenderPass<'rpass>,
+        lines: DebugLines,
+    ) {
+        rpass.set_pipeline(match lines {
+            DebugLines::Wireframe => &self.wireframe_pipeline,
+            DebugLines::Normals => &self.normals_pipeline,
+            DebugLines::Bounds => &self.bounds_pipeline,
+            DebugLines::Degenerate => &self.degenerate_pipeline,
+        });
         rpass.set_bind_group(0, &self.camera_bind_group, &[]);
         rpass.set_bind_group(1, &self.light_bind_group, &[]);
     }
@@ -0,0 +1,295 @@
+//! Reading the shared and per-track files of a world directory.
+//!
+//! A world directory holds terrain0.lf, blockObj0.LBF and modeltable0.LOF, plus a
+//! "Main" directory and Track1..N directories with each track's terrain0.LIF and object0.loI.
+//! Everything is read through the project, so worlds inside archives work the same.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Cursor,
+};
+
+use camino::Utf8Path;
+use slidetown::parsers::{
+    lf::Lf,
+    lif::Lif,
+    loi::{Loi, LoiObject},
+};
+
+use crate::project::FileLister;
+
+/// Identifies an object placed by a track's object0.loI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldObjectRef {
+    pub track: String,
+    pub block_index: u32,
+    /// Position of the object within its LOI block.
+    pub object_index: usize,
+}
+
+pub fn available_tracks(files: &FileLister, dir_path: &Utf8Path) -> Vec<String> {
+    let mut available_tracks = vec!["Main".to_string()];
+    for i in 1.. {
+        let name = format!("Track{}", i);
+        if !files.is_dir(&dir_path.join(&name)) {
+            break;
+        }
+        available_tracks.push(name);
+    }
+    available_tracks
+}
+
+pub fn read_lf(files: &FileLister, dir_path: &Utf8Path) -> anyhow::Result<Lf> {
+    let data = files.read_file(&dir_path.join("terrain0.lf"))?;
+    Lf::read(&mut Cursor::new(data))
+}
+
+/// Indices of the terrain blocks the track uses.
+pub fn enabled_blocks(
+    files: &FileLister,
+    dir_path: &Utf8Path,
+    track: &str,
+) -> anyhow::Result<HashSet<u32>> {
+    let data = files.read_file(&dir_path.join(track).join("terrain0.LIF"))?;
+    let lif = Lif::read(&mut Cursor::new(data))?;
+    Ok(lif
+        .blocks
+        .iter()
+        .filter(|block| block.unk > 0)
+        .map(|block| block.index)
+        .collect())
+}
+
+pub fn read_loi(
+    files: &FileLister,
+    dir_path: &Utf8Path,
+    track: &str,
+    block_count: u32,
+) -> anyhow::Result<Loi> {
+    let data = files.read_file(&dir_path.join(track).join("object0.loI"))?;
+    Loi::read(&mut Cursor::new(data), block_count as _)
+}
+
+pub fn object_position(object: &LoiObject) -> glam::Vec3 {
+    glam::vec3(object.position.0, object.position.1, object.position.2)
+}
+
+pub fn object_rotation(object: &LoiObject) -> glam::Quat {
+    let rm = object.rotation;
+    let rotation_mat = glam::Mat3::from_cols_array_2d(&[
+        [rm.0 .0, rm.0 .1, rm.0 .2],
+        [rm.1 .0, rm.1 .1, rm.1 .2],
+        [rm.2 .0, rm.2 .1, rm.2 .2],
+    ])
+    .transpose();
+    glam::Quat::from_mat3(&rotation_mat)
+}
+
+/// A placed object as (block index, object index).
+pub type ObjectKey = (u32, usize);
+
+/// Placements of a model further apart than this are a removal and an addition, not a move.
+pub const MAX_MOVE_DISTANCE: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placement {
+    position: glam::Vec3,
+    rotation: glam::Quat,
+    scale: f32,
+}
+
+/// Differences between a base track A and a compared track B.
+#[derive(Debug, Default)]
+pub struct TrackDiff {
+    pub blocks_only_a: Vec<u32>,
+    pub blocks_only_b: Vec<u32>,
+    /// Objects of B without a counterpart in A.
+    pub added: Vec<ObjectKey>,
+    /// Objects of A without a counterpart in B.
+    pub removed: Vec<ObjectKey>,
+    /// Objects placed with a different transform, as (object in A, object in B).
+    pub moved: Vec<(ObjectKey, ObjectKey)>,
+}
+
+impl TrackDiff {
+    /// Objects are matched by block and model. Identical placements are unchanged, the
+    /// remaining ones are paired closest first, up to `MAX_MOVE_DISTANCE` apart.
+    pub fn new(blocks_a: &HashSet<u32>, loi_a: &Loi, blocks_b: &HashSet<u32>, loi_b: &Loi) -> Self {
+        let mut blocks_only_a = blocks_a.difference(blocks_b).copied().collect::<Vec<_>>();
+        let mut blocks_only_b = blocks_b.difference(blocks_a).copied().collect::<Vec<_>>();
+        blocks_only_a.sort_unstable();
+        blocks_only_b.sort_unstable();
+
+        let placements_a = placements_by_model(blocks_a, loi_a);
+        let mut placements_b = placements_by_model(blocks_b, loi_b);
+
+        let mut diff = Self {
+            blocks_only_a,
+            blocks_only_b,
+            ..Default::default()
+        };
+
+        for (key, objects_a) in placements_a {
+            let objects_b = placements_b.remove(&key).unwrap_or_default();
+            diff.match_placements(objects_a, objects_b);
+        }
+        for objects_b in placements_b.into_values() {
+            diff.added
+                .extend(objects_b.into_iter().map(|(key_b, _)| key_b));
+        }
+
+        diff.added.sort_unstable();
+        diff.removed.sort_unstable();
+        diff.moved.sort_unstable();
+        diff
+    }
+
+    /// Sorts the placements of one model in one block into unchanged, moved, removed and added.
+    fn match_placements(
+        &mut self,
+        mut objects_a: Vec<(ObjectKey, Placement)>,
+        mut objects_b: Vec<(ObjectKey, Placement)>,
+    ) {
+        objects_a.retain(|(_, a)| match objects_b.iter().position(|(_, b)| a == b) {
+            Some(idx) => {
+                objects_b.remove(idx);
+                false
+            }
+            None => true,
+        });
+
+        let mut pairs = Vec::new();
+        for (idx_a, (_, a)) in objects_a.iter().enumerate() {
+            for (idx_b, (_, b)) in objects_b.iter().enumerate() {
+                let distance = a.position.distance(b.position);
+                if distance <= MAX_MOVE_DISTANCE {
+                    pairs.push((distance, idx_a, idx_b));
+                }
+            }
+        }
+        pairs.sort_by(|(a, _, _), (b, _, _)| a.total_cmp(b));
+
+        let mut paired_a = vec![false; objects_a.len()];
+        let mut paired_b = vec![false; objects_b.len()];
+        for (_, idx_a, idx_b) in pairs {
+            if !paired_a[idx_a] && !paired_b[idx_b] {
+                paired_a[idx_a] = true;
+                paired_b[idx_b] = true;
+                self.moved.push((objects_a[idx_a].0, objects_b[idx_b].0));
+            }
+        }
+        self.removed.extend(
+            objects_a
+                .iter()
+                .zip(paired_a)
+                .filter(|(_, paired)| !paired)
+                .map(|((key_a, _), _)| *key_a),
+        );
+        self.added.extend(
+            objects_b
+                .iter()
+                .zip(paired_b)
+                .filter(|(_, paired)| !paired)
+                .map(|((key_b, _), _)| *key_b),
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks_only_a.is_empty()
+            && self.blocks_only_b.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+    }
+}
+
+/// Objects of the enabled blocks, grouped by (block index, model index).
+fn placements_by_model(
+    blocks: &HashSet<u32>,
+    loi: &Loi,
+) -> BTreeMap<(u32, u32), Vec<(ObjectKey, Placement)>> {
+    let mut placements: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for block in loi
+        .blocks
+        .iter()
+        .filter(|block| blocks.contains(&block.block_index))
+    {
+        for (object_index, object) in block.objects.iter().enumerate() {
+            placements
+                .entry((block.block_index, object.model_table_index))
+                .or_default()
+                .push((
+                    (block.block_index, object_index),
+                    Placement {
+                        position: object_position(object),
+                        rotation: object_rotation(object),
+                        scale: object.scale,
+                    },
+                ));
+        }
+    }
+    placements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(object_index: usize, x: f32) -> (ObjectKey, Placement) {
+        (
+            (0, object_index),
+            Placement {
+                position: glam::vec3(x, 0.0, 0.0),
+                rotation: glam::Quat::IDENTITY,
+                scale: 1.0,
+            },
+        )
+    }
+
+    fn diff(
+        objects_a: Vec<(ObjectKey, Placement)>,
+        objects_b: Vec<(ObjectKey, Placement)>,
+    ) -> TrackDiff {
+        let mut diff = TrackDiff::default();
+        diff.match_placements(objects_a, objects_b);
+        diff
+    }
+
+    #[test]
+    fn unchanged_placements_are_not_reported() {
+        let diff = diff(vec![at(0, 0.0), at(1, 10.0)], vec![at(0, 10.0), at(1, 0.0)]);
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn extra_placement_is_added() {
+        let diff = diff(vec![at(0, 0.0)], vec![at(0, 0.0), at(1, 10.0)]);
+        assert_eq!(diff.added, vec![(0, 1)]);
+        assert!(diff.removed.is_empty() && diff.moved.is_empty());
+    }
+
+    #[test]
+    fn missing_placement_is_removed() {
+        let diff = diff(vec![at(0, 0.0), at(1, 10.0)], vec![at(0, 0.0)]);
+        assert_eq!(diff.removed, vec![(0, 1)]);
+        assert!(diff.added.is_empty() && diff.moved.is_empty());
+    }
+
+    #[test]
+    fn nearby_placement_is_moved() {
+        let diff = diff(
+            vec![at(0, 0.0), at(1, 100.0)],
+            vec![at(0, 95.0), at(1, 3.0)],
+        );
+        assert_eq!(diff.moved, vec![((0, 0), (0, 1)), ((0, 1), (0, 0))]);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+    }
+
+    #[test]
+    fn unrelated_swap_is_removed_and_added() {
+        let diff = diff(vec![at(0, 0.0)], vec![at(0, MAX_MOVE_DISTANCE * 4.0)]);
+        assert_eq!(diff.removed, vec![(0, 0)]);
+        assert_eq!(diff.added, vec![(0, 0)]);
+        assert!(diff.moved.is_empty());
+    }
+}